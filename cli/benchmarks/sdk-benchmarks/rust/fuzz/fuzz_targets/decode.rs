@@ -0,0 +1,26 @@
+//! One envelope through the owned and borrowed decoders in both directions,
+//! then back through the encoder.
+
+#![no_main]
+
+use acp_benchmark::codec::{self, CodecState, Direction};
+use acp_benchmark::conformance::{self, RoundTripError};
+use acp_benchmark::{borrowed, trace};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(json) = std::str::from_utf8(data) else {
+        return;
+    };
+    let _ = trace::parse_frame(json);
+    let _ = codec::infer_direction(json);
+
+    for direction in [Direction::FromClient, Direction::FromAgent] {
+        let _ = borrowed::decode(direction, &mut CodecState::default(), json);
+        // Decodes once more inside, then encodes whatever decoded.
+        let trip = conformance::round_trip(direction, &mut CodecState::default(), json);
+        if let Err(RoundTripError::InvalidEncoding(e)) = trip {
+            panic!("encoder produced invalid JSON: {e}");
+        }
+    }
+});
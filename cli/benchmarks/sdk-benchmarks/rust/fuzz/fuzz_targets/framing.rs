@@ -0,0 +1,46 @@
+//! Raw bytes through framing detection and both frame readers. Frames must
+//! not depend on how the input happens to be split across reads.
+
+#![no_main]
+
+use acp_benchmark::framing::{FrameReader, Framing};
+use libfuzzer_sys::fuzz_target;
+use std::io::BufReader;
+
+/// Small, so inputs reach the size limit.
+const MAX_FRAME_BYTES: usize = 1024;
+
+/// Each frame's text, or its finding code, keyed by position.
+fn drain(mut reader: FrameReader<impl std::io::BufRead>, len: usize) -> Vec<(usize, String)> {
+    let mut frames = Vec::new();
+    while let Some(frame) = reader.next_frame() {
+        frames.push(match frame {
+            Ok(frame) => {
+                assert!(frame.text.len() <= MAX_FRAME_BYTES);
+                (frame.line, frame.text.to_owned())
+            }
+            Err(finding) => (
+                finding.trace_index.unwrap_or(0),
+                finding.failure.map(|f| f.code).unwrap_or_default(),
+            ),
+        });
+    }
+    assert!(reader.bytes_read() <= len as u64);
+    frames
+}
+
+fuzz_target!(|data: &[u8]| {
+    let detected = FrameReader::detect(data, MAX_FRAME_BYTES).expect("reading a slice");
+    drain(detected, data.len());
+
+    for framing in [Framing::Ndjson, Framing::ContentLength] {
+        let whole = FrameReader::new(framing, data, MAX_FRAME_BYTES);
+        let bytewise =
+            FrameReader::new(framing, BufReader::with_capacity(1, data), MAX_FRAME_BYTES);
+        assert_eq!(
+            drain(whole, data.len()),
+            drain(bytewise, data.len()),
+            "{framing} frames depend on read boundaries"
+        );
+    }
+});
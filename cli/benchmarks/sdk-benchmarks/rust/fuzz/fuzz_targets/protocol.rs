@@ -0,0 +1,44 @@
+//! A trace, one message or trace frame per line, decoded with threaded codec
+//! state and stepped through the protocol spec and session checks.
+
+#![no_main]
+
+use acp_benchmark::codec::{self, CodecState};
+use acp_benchmark::protocol::{self, Phase};
+use acp_benchmark::{trace, validation};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+    let mut state = CodecState::default();
+    let messages: Vec<_> = text
+        .lines()
+        .filter_map(|line| {
+            let decoded = match trace::parse_frame(line) {
+                Ok(frame) => codec::decode(frame.direction, &mut state, &frame.json),
+                Err(_) => codec::decode(codec::infer_direction(line), &mut state, line),
+            };
+            decoded.ok().map(|d| d.message)
+        })
+        .collect();
+
+    let spec = protocol::spec();
+    let mut phase = spec.initial.clone();
+    for message in &messages {
+        let before = phase.clone();
+        match (spec.step)(&mut phase, message) {
+            Err(_) => assert_eq!(phase, before, "a rejected message changed the phase"),
+            Ok(()) => {
+                if let Phase::Ready(ctx) = &phase {
+                    for (id, session) in &ctx.sessions {
+                        assert_eq!(id, &session.session_id);
+                    }
+                }
+            }
+        }
+    }
+
+    let _ = validation::run_with_validation(&spec, &messages);
+});
@@ -0,0 +1,40 @@
+#!/bin/bash
+set -euo pipefail
+
+# Rebuild the checked-in seed corpus from the benchmark scenarios and the
+# sentinel traces. Requires jq.
+#   framing:  each file's messages as NDJSON and with Content-Length headers
+#   decode:   one message per file
+#   protocol: each file as-is (trace frames keep their direction)
+
+export LC_ALL=C
+
+FUZZ_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
+ROOT="$(cd "$FUZZ_DIR/../../../../.." && pwd)"
+CORPUS="$FUZZ_DIR/corpus"
+
+rm -rf "$CORPUS"
+mkdir -p "$CORPUS/framing" "$CORPUS/decode" "$CORPUS/protocol"
+
+for file in "$ROOT"/cli/benchmarks/scenarios/*.json "$ROOT"/sentinel/tests/traces/*.jsonl; do
+    name=$(basename "$file")
+    name=${name%.*}
+
+    if [[ $file == *.jsonl ]]; then
+        messages=$(jq -r .json "$file")
+    else
+        messages=$(grep -v '^[[:space:]]*$' "$file")
+    fi
+
+    cp "$file" "$CORPUS/protocol/$name"
+    printf '%s\n' "$messages" > "$CORPUS/framing/$name.ndjson"
+
+    i=0
+    while IFS= read -r line; do
+        i=$((i + 1))
+        printf 'Content-Length: %d\r\n\r\n%s' "${#line}" "$line" >> "$CORPUS/framing/$name.content-length"
+        printf '%s' "$line" > "$CORPUS/decode/$name-$i"
+    done <<< "$messages"
+done
+
+echo "Seeded $(find "$CORPUS" -type f | wc -l | tr -d ' ') files into $CORPUS"
@@ -0,0 +1,27 @@
+#!/bin/bash
+set -euo pipefail
+
+# Vendor upstream's schema/schema.json verbatim at the tag that
+# Acp.Domain.Spec.Schema pins, and record its sha256 next to it.
+# Requires curl and sha256sum.
+#   ./update.sh          fetch the pinned tag and record its hash
+#   ./update.sh --check  verify the vendored file against the recorded hash
+
+VERSION=0.10.5
+TAG="v$VERSION"
+URL="https://raw.githubusercontent.com/agentclientprotocol/agent-client-protocol/$TAG/schema/schema.json"
+
+SCHEMA_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
+cd "$SCHEMA_DIR"
+FILE="acp-$VERSION.json"
+
+if [[ ${1:-} == --check ]]; then
+    sha256sum -c "$FILE.sha256"
+    exit
+fi
+
+curl -fsSL "$URL" -o "$FILE.tmp"
+mv "$FILE.tmp" "$FILE"
+sha256sum "$FILE" > "$FILE.sha256"
+echo "Vendored $TAG from $URL"
+cat "$FILE.sha256"
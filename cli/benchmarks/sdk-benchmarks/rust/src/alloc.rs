@@ -0,0 +1,135 @@
+//! Counting global allocator for the memory mode. Counting is off until
+//! [`enable`] is called, so the timing modes pay one relaxed load per call.
+//! Binaries opt in by installing [`CountingAlloc`] as their `#[global_allocator]`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering::Relaxed};
+
+pub struct CountingAlloc;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static BYTES: AtomicUsize = AtomicUsize::new(0);
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+fn on_alloc(size: usize) {
+    if ENABLED.load(Relaxed) {
+        ALLOCATIONS.fetch_add(1, Relaxed);
+        BYTES.fetch_add(size, Relaxed);
+        let live = LIVE.fetch_add(size, Relaxed) + size;
+        PEAK.fetch_max(live, Relaxed);
+    }
+}
+
+fn on_dealloc(size: usize) {
+    if ENABLED.load(Relaxed) {
+        // Blocks allocated before counting started are not in LIVE.
+        let _ = LIVE.fetch_update(Relaxed, Relaxed, |live| Some(live.saturating_sub(size)));
+    }
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            on_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            on_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        on_dealloc(layout.size());
+    }
+
+    // A realloc counts as one allocation of the new size.
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new = System.realloc(ptr, layout, new_size);
+        if !new.is_null() {
+            on_dealloc(layout.size());
+            on_alloc(new_size);
+        }
+        new
+    }
+}
+
+/// Cumulative counters since counting was enabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Snapshot {
+    pub allocations: usize,
+    pub bytes: usize,
+}
+
+impl Snapshot {
+    pub fn since(self, earlier: Snapshot) -> Snapshot {
+        Snapshot {
+            allocations: self.allocations - earlier.allocations,
+            bytes: self.bytes - earlier.bytes,
+        }
+    }
+}
+
+pub fn enable() {
+    ENABLED.store(true, Relaxed);
+}
+
+pub fn snapshot() -> Snapshot {
+    Snapshot {
+        allocations: ALLOCATIONS.load(Relaxed),
+        bytes: BYTES.load(Relaxed),
+    }
+}
+
+/// Highest number of live heap bytes allocated since counting was enabled,
+/// or since the last [`reset_peak`].
+pub fn peak_heap_bytes() -> usize {
+    PEAK.load(Relaxed)
+}
+
+/// Heap bytes allocated since counting was enabled and not yet freed.
+pub fn live_heap_bytes() -> usize {
+    LIVE.load(Relaxed)
+}
+
+/// Restart peak tracking from the bytes live now.
+pub fn reset_peak() {
+    PEAK.store(LIVE.load(Relaxed), Relaxed);
+}
+
+/// Peak resident set size of the process (`VmHWM`), where the OS reports it.
+pub fn peak_rss_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmHWM:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[global_allocator]
+    static GLOBAL: CountingAlloc = CountingAlloc;
+
+    #[test]
+    fn counts_allocations_once_enabled() {
+        enable();
+        let before = snapshot();
+        let v: Vec<u8> = std::hint::black_box(Vec::with_capacity(4096));
+        let delta = snapshot().since(before);
+        // Other test threads may allocate concurrently, hence lower bounds.
+        assert!(delta.allocations >= 1);
+        assert!(delta.bytes >= 4096);
+        assert!(peak_heap_bytes() >= 4096);
+        drop(v);
+    }
+}
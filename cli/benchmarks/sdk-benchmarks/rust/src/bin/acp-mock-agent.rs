@@ -0,0 +1,552 @@
+//! Scriptable mock ACP agent over stdio, in newline-delimited or
+//! `Content-Length` framed JSON-RPC (whichever the client's first bytes use).
+//! Messages go through the crate's codec both ways.
+//!
+//! Answers initialize and session/new, and plays one scripted turn per
+//! session/prompt: tool calls (optionally gated on session/request_permission),
+//! `agent_message_chunk` updates, then a result with the turn's `stopReason`.
+//!
+//! A script is a JSON file; turns are played in order and cycle:
+//!
+//! ```json
+//! {"turns": [
+//!   {"chunks": 3, "toolCalls": [{"title": "Read config", "kind": "read", "permission": true}]},
+//!   {"chunks": 0, "stopReason": "refusal"}
+//! ]}
+//! ```
+
+use acp_benchmark::codec::{self, CodecState, DecodeError, Decoded, Direction};
+use acp_benchmark::connection::{internal_error, method_not_found};
+use acp_benchmark::domain::*;
+use acp_benchmark::framing::{FrameReader, Framing, DEFAULT_MAX_FRAME_BYTES};
+use clap::Parser;
+use serde::Deserialize;
+use serde_json::Value;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ScriptedToolCall {
+    title: String,
+    #[serde(default)]
+    kind: ToolKind,
+    /// Ask the client for permission before running the tool.
+    #[serde(default)]
+    permission: bool,
+}
+
+/// One prompt turn; unset fields fall back to the command-line defaults.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Turn {
+    chunks: Option<usize>,
+    chunk_text: Option<String>,
+    #[serde(default)]
+    tool_calls: Vec<ScriptedToolCall>,
+    stop_reason: Option<StopReason>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct Script {
+    #[serde(default)]
+    turns: Vec<Turn>,
+}
+
+/// A stop reason as spelled on the wire, e.g. `max_tokens`.
+fn parse_stop_reason(s: &str) -> Result<StopReason, String> {
+    serde_json::from_value(Value::String(s.to_owned())).map_err(|e| e.to_string())
+}
+
+#[derive(Parser, Debug)]
+#[command(name = "acp-mock-agent")]
+#[command(about = "Scriptable mock ACP agent over stdio")]
+struct Args {
+    /// JSON script of prompt turns
+    #[arg(long)]
+    script: Option<PathBuf>,
+
+    /// `agent_message_chunk` updates per turn
+    #[arg(long, default_value = "1")]
+    chunks: usize,
+
+    #[arg(long, default_value = "mock reply")]
+    chunk_text: String,
+
+    /// end_turn, max_tokens, max_turn_requests, refusal or cancelled
+    #[arg(long, default_value = "end_turn", value_parser = parse_stop_reason)]
+    stop_reason: StopReason,
+}
+
+/// What a resolved turn does.
+struct Plan {
+    chunks: usize,
+    chunk_text: String,
+    tool_calls: Vec<ScriptedToolCall>,
+    stop_reason: StopReason,
+}
+
+/// Client messages that open a request, as opposed to answering one.
+fn is_request(message: &ClientToAgentMessage) -> bool {
+    use ClientToAgentMessage as C;
+    matches!(
+        message,
+        C::Initialize(_)
+            | C::ProxyInitialize(_)
+            | C::Authenticate(_)
+            | C::SessionNew(_)
+            | C::SessionLoad(_)
+            | C::SessionPrompt(_)
+            | C::SessionSetMode(_)
+            | C::ProxySuccessorRequest(_)
+            | C::ExtRequest { .. }
+    )
+}
+
+fn codec_error(e: impl std::fmt::Display) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+struct Agent<R, W> {
+    reader: FrameReader<R>,
+    framing: Framing,
+    output: W,
+    state: CodecState,
+    script: Script,
+    defaults: Args,
+    sessions: usize,
+    turns: usize,
+    tool_calls: usize,
+    next_id: i64,
+}
+
+impl<R: BufRead, W: Write> Agent<R, W> {
+    fn new(input: R, output: W, script: Script, defaults: Args) -> io::Result<Self> {
+        let reader = FrameReader::detect(input, DEFAULT_MAX_FRAME_BYTES)?;
+        Ok(Agent {
+            framing: reader.framing(),
+            reader,
+            output,
+            state: CodecState::default(),
+            script,
+            defaults,
+            sessions: 0,
+            turns: 0,
+            tool_calls: 0,
+            next_id: 0,
+        })
+    }
+
+    /// Encode `message` and step the codec with it, as the client will.
+    fn send(&mut self, id: Option<&RequestId>, message: AgentToClientMessage) -> io::Result<()> {
+        let message = Message::FromAgent(message);
+        let json = codec::encode(id, &message).map_err(codec_error)?;
+        codec::decode(Direction::FromAgent, &mut self.state, &json).map_err(codec_error)?;
+        self.framing.write(&mut self.output, &json)
+    }
+
+    /// Answer request `id` with `error`.
+    fn fail(&mut self, id: &RequestId, error: JsonRpcError) -> io::Result<()> {
+        let reply = codec::error_reply(Direction::FromAgent, &self.state, id, error)
+            .map_err(codec_error)?;
+        let Message::FromAgent(reply) = reply else {
+            unreachable!("replies in the agent direction")
+        };
+        self.send(Some(id), reply)
+    }
+
+    fn update(&mut self, session_id: &SessionId, update: SessionUpdate) -> io::Result<()> {
+        self.send(
+            None,
+            AgentToClientMessage::SessionUpdate(SessionUpdateNotification {
+                session_id: session_id.clone(),
+                update,
+                meta: None,
+            }),
+        )
+    }
+
+    /// The next decoded client message; `None` when the client closes stdin.
+    /// Requests with invalid params are answered here; bad frames and other
+    /// undecodable messages are dropped.
+    fn read(&mut self) -> io::Result<Option<Decoded>> {
+        loop {
+            let malformed = match self.reader.next_frame() {
+                None => return Ok(None),
+                Some(Err(_)) => continue,
+                Some(Ok(frame)) => {
+                    match codec::decode(Direction::FromClient, &mut self.state, frame.text) {
+                        Ok(decoded) => return Ok(Some(decoded)),
+                        Err(e @ DecodeError::InvalidParams { .. }) => {
+                            codec::peek_id(frame.text).map(|id| (id, e))
+                        }
+                        Err(_) => None,
+                    }
+                }
+            };
+            if let Some((id, e)) = malformed {
+                let error = JsonRpcError {
+                    code: -32602,
+                    message: e.to_string(),
+                    data: None,
+                };
+                let json = codec::encode_error(&id, &error).map_err(codec_error)?;
+                self.framing.write(&mut self.output, &json)?;
+            }
+        }
+    }
+
+    fn run(&mut self) -> io::Result<()> {
+        use AgentToClientMessage as A;
+        use ClientToAgentMessage as C;
+
+        while let Some(Decoded { id, message }) = self.read()? {
+            let Message::FromClient(message) = message else {
+                unreachable!("decoded from the client direction")
+            };
+            let Some(id) = id else {
+                // Notifications outside a turn (a late session/cancel) need
+                // no answer.
+                continue;
+            };
+
+            match message {
+                C::Initialize(params) => {
+                    let result = InitializeResult {
+                        protocol_version: params.protocol_version,
+                        agent_capabilities: AgentCapabilities::default(),
+                        agent_info: Some(ImplementationInfo {
+                            name: "acp-mock-agent".to_owned(),
+                            title: None,
+                            version: env!("CARGO_PKG_VERSION").to_owned(),
+                        }),
+                        auth_methods: Vec::new(),
+                    };
+                    self.send(Some(&id), A::InitializeResult(result))?;
+                }
+                C::SessionNew(_) => {
+                    self.sessions += 1;
+                    let result = NewSessionResult {
+                        session_id: SessionId(format!("mock-session-{}", self.sessions)),
+                        modes: None,
+                    };
+                    self.send(Some(&id), A::SessionNewResult(result))?;
+                }
+                C::SessionPrompt(params) => self.prompt(&id, &params.session_id)?,
+                other if is_request(&other) => self.fail(&id, method_not_found(other.method()))?,
+                // Stray responses need no answer either.
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn plan(&self, index: usize) -> Plan {
+        let turns = &self.script.turns;
+        let turn = (!turns.is_empty()).then(|| &turns[index % turns.len()]);
+        Plan {
+            chunks: turn.and_then(|t| t.chunks).unwrap_or(self.defaults.chunks),
+            chunk_text: turn
+                .and_then(|t| t.chunk_text.clone())
+                .unwrap_or_else(|| self.defaults.chunk_text.clone()),
+            tool_calls: turn.map(|t| t.tool_calls.clone()).unwrap_or_default(),
+            stop_reason: turn
+                .and_then(|t| t.stop_reason)
+                .unwrap_or(self.defaults.stop_reason),
+        }
+    }
+
+    fn prompt(&mut self, id: &RequestId, session_id: &SessionId) -> io::Result<()> {
+        let index = self.turns;
+        self.turns += 1;
+
+        let plan = self.plan(index);
+
+        let mut cancelled = false;
+        for call in &plan.tool_calls {
+            if cancelled {
+                break;
+            }
+            cancelled = self.tool_call(session_id, call)?;
+        }
+
+        if !cancelled {
+            for _ in 0..plan.chunks {
+                let chunk = ContentChunk {
+                    content: ContentBlock::Text(TextContent {
+                        text: plan.chunk_text.clone(),
+                        annotations: None,
+                    }),
+                };
+                self.update(session_id, SessionUpdate::AgentMessageChunk(chunk))?;
+            }
+        }
+
+        // ACP: a cancelled turn must end with the `cancelled` stop reason.
+        let stop_reason = if cancelled {
+            StopReason::Cancelled
+        } else {
+            plan.stop_reason
+        };
+        let result = SessionPromptResult {
+            session_id: session_id.clone(),
+            stop_reason,
+            usage: None,
+            meta: None,
+        };
+        self.send(Some(id), AgentToClientMessage::SessionPromptResult(result))
+    }
+
+    /// Report a tool call, asking for permission first if scripted.
+    /// Returns whether the turn was cancelled meanwhile.
+    fn tool_call(&mut self, session_id: &SessionId, call: &ScriptedToolCall) -> io::Result<bool> {
+        self.tool_calls += 1;
+        let tool_call_id = format!("call-{}", self.tool_calls);
+
+        self.update(
+            session_id,
+            SessionUpdate::ToolCall(ToolCall {
+                tool_call_id: tool_call_id.clone(),
+                title: call.title.clone(),
+                kind: call.kind,
+                status: ToolCallStatus::Pending,
+                content: Vec::new(),
+                locations: Vec::new(),
+                raw_input: None,
+                raw_output: None,
+            }),
+        )?;
+
+        let (allowed, cancelled) = if call.permission {
+            self.request_permission(session_id, &tool_call_id, call)?
+        } else {
+            (true, false)
+        };
+
+        let status = if allowed {
+            ToolCallStatus::Completed
+        } else {
+            ToolCallStatus::Failed
+        };
+        self.update(
+            session_id,
+            SessionUpdate::ToolCallUpdate(ToolCallUpdate {
+                status: Some(status),
+                ..tool_call_update(tool_call_id)
+            }),
+        )?;
+        Ok(cancelled)
+    }
+
+    /// Returns (allowed, cancelled).
+    fn request_permission(
+        &mut self,
+        session_id: &SessionId,
+        tool_call_id: &str,
+        call: &ScriptedToolCall,
+    ) -> io::Result<(bool, bool)> {
+        use ClientToAgentMessage as C;
+
+        self.next_id += 1;
+        let request_id = RequestId::String(format!("mock-{}", self.next_id));
+        let option = |id: &str, name: &str, kind| PermissionOption {
+            option_id: id.to_owned(),
+            name: name.to_owned(),
+            kind,
+        };
+        let params = RequestPermissionParams {
+            session_id: session_id.clone(),
+            tool_call: ToolCallUpdate {
+                title: Some(call.title.clone()),
+                kind: Some(call.kind),
+                ..tool_call_update(tool_call_id.to_owned())
+            },
+            options: vec![
+                option("allow", "Allow", PermissionOptionKind::AllowOnce),
+                option("reject", "Reject", PermissionOptionKind::RejectOnce),
+            ],
+        };
+        self.send(
+            Some(&request_id),
+            AgentToClientMessage::SessionRequestPermissionRequest(params),
+        )?;
+
+        let mut cancelled = false;
+        while let Some(Decoded { id, message }) = self.read()? {
+            let Message::FromClient(message) = message else {
+                unreachable!("decoded from the client direction")
+            };
+            let answers = id.as_ref() == Some(&request_id);
+            match message {
+                C::SessionRequestPermissionResult(result) if answers => {
+                    return Ok(match result.outcome {
+                        RequestPermissionOutcome::Selected { option_id } => {
+                            (option_id == "allow", cancelled)
+                        }
+                        RequestPermissionOutcome::Cancelled => (false, true),
+                    });
+                }
+                C::SessionRequestPermissionError(..) if answers => return Ok((false, cancelled)),
+                C::SessionCancel(_) => cancelled = true,
+                other if is_request(&other) => {
+                    if let Some(id) = id {
+                        self.fail(&id, internal_error("a prompt turn is in progress"))?;
+                    }
+                }
+                _ => {}
+            }
+        }
+        // The client went away mid-turn.
+        Ok((false, true))
+    }
+}
+
+/// An update to tool call `tool_call_id` that changes nothing yet.
+fn tool_call_update(tool_call_id: String) -> ToolCallUpdate {
+    ToolCallUpdate {
+        tool_call_id,
+        title: None,
+        kind: None,
+        status: None,
+        content: None,
+        locations: None,
+        raw_input: None,
+        raw_output: None,
+    }
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let script = match &args.script {
+        Some(path) => {
+            let parsed = std::fs::read_to_string(path)
+                .map_err(|e| e.to_string())
+                .and_then(|text| serde_json::from_str(&text).map_err(|e| e.to_string()));
+            match parsed {
+                Ok(script) => script,
+                Err(e) => {
+                    eprintln!("Failed to load script {}: {e}", path.display());
+                    std::process::exit(1);
+                }
+            }
+        }
+        None => Script::default(),
+    };
+
+    let result = Agent::new(io::stdin().lock(), io::stdout().lock(), script, args)
+        .and_then(|mut agent| agent.run());
+    if let Err(e) = result {
+        eprintln!("Mock agent failed: {e}");
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn run(script: &str, input: &[&str]) -> Vec<Value> {
+        let args = Args::parse_from(["acp-mock-agent", "--chunks", "2"]);
+        let script = serde_json::from_str(script).unwrap();
+        let input = input.join("\n");
+        let mut output = Vec::new();
+        Agent::new(input.as_bytes(), &mut output, script, args)
+            .unwrap()
+            .run()
+            .unwrap();
+        serde_json::Deserializer::from_slice(&output)
+            .into_iter::<Value>()
+            .map(Result::unwrap)
+            .collect()
+    }
+
+    const SETUP: [&str; 2] = [
+        r#"{"jsonrpc":"2.0","method":"initialize","params":{"protocolVersion":1},"id":1}"#,
+        r#"{"jsonrpc":"2.0","method":"session/new","params":{"cwd":"/","mcpServers":[]},"id":2}"#,
+    ];
+    const PROMPT: &str = r#"{"jsonrpc":"2.0","method":"session/prompt","params":{"sessionId":"mock-session-1","prompt":[]},"id":3}"#;
+
+    #[test]
+    fn streams_default_chunks_then_ends_turn() {
+        let out = run("{}", &[SETUP[0], SETUP[1], PROMPT]);
+        let kinds: Vec<_> = out[2..4]
+            .iter()
+            .map(|m| m["params"]["update"]["sessionUpdate"].clone())
+            .collect();
+        assert_eq!(
+            kinds,
+            [json!("agent_message_chunk"), json!("agent_message_chunk")]
+        );
+        assert_eq!(out[4]["result"]["stopReason"], "end_turn");
+        assert_eq!(out.len(), 5);
+    }
+
+    #[test]
+    fn scripted_permission_gates_tool_call() {
+        let script = r#"{"turns":[{"chunks":0,"stopReason":"max_tokens","toolCalls":[{"title":"Edit","kind":"edit","permission":true}]}]}"#;
+        let reply = r#"{"jsonrpc":"2.0","result":{"outcome":{"outcome":"selected","optionId":"reject"}},"id":"mock-1"}"#;
+        let out = run(script, &[SETUP[0], SETUP[1], PROMPT, reply]);
+
+        assert_eq!(out[2]["params"]["update"]["sessionUpdate"], "tool_call");
+        assert_eq!(out[3]["method"], "session/request_permission");
+        assert_eq!(out[4]["params"]["update"]["status"], "failed");
+        assert_eq!(out[5]["result"]["stopReason"], "max_tokens");
+    }
+
+    #[test]
+    fn cancel_during_permission_ends_turn_cancelled() {
+        let script = r#"{"turns":[{"toolCalls":[{"title":"Run","permission":true}]}]}"#;
+        let cancel = r#"{"jsonrpc":"2.0","method":"session/cancel","params":{"sessionId":"mock-session-1"}}"#;
+        let reply =
+            r#"{"jsonrpc":"2.0","result":{"outcome":{"outcome":"cancelled"}},"id":"mock-1"}"#;
+        let out = run(script, &[SETUP[0], SETUP[1], PROMPT, cancel, reply]);
+
+        let last = out.last().unwrap();
+        assert_eq!(last["result"]["stopReason"], "cancelled");
+        // No message chunks after the cancel.
+        assert!(out
+            .iter()
+            .all(|m| m["params"]["update"]["sessionUpdate"] != "agent_message_chunk"));
+    }
+
+    #[test]
+    fn replies_in_the_clients_framing() {
+        let mut input = Vec::new();
+        for line in SETUP.iter().chain([&PROMPT]) {
+            Framing::ContentLength.write(&mut input, line).unwrap();
+        }
+        let args = Args::parse_from(["acp-mock-agent"]);
+        let mut output = Vec::new();
+        Agent::new(&input[..], &mut output, Script::default(), args)
+            .unwrap()
+            .run()
+            .unwrap();
+
+        let mut reader = FrameReader::new(Framing::ContentLength, &output[..], 1 << 20);
+        let mut replies = Vec::new();
+        while let Some(frame) = reader.next_frame() {
+            replies.push(serde_json::from_str::<Value>(frame.unwrap().text).unwrap());
+        }
+        assert_eq!(replies.len(), 4);
+        assert_eq!(replies[1]["result"]["sessionId"], "mock-session-1");
+        assert_eq!(replies[3]["result"]["stopReason"], "end_turn");
+    }
+
+    #[test]
+    fn answers_invalid_params_and_unsupported_methods() {
+        let bad_prompt = r#"{"jsonrpc":"2.0","method":"session/prompt","params":{},"id":7}"#;
+        let load = r#"{"jsonrpc":"2.0","method":"session/load","params":{"sessionId":"s","cwd":"/","mcpServers":[]},"id":8}"#;
+        let out = run("{}", &[bad_prompt, load]);
+        assert_eq!(out.len(), 2);
+        assert_eq!(
+            (&out[0]["id"], &out[0]["error"]["code"]),
+            (&json!(7), &json!(-32602))
+        );
+        assert_eq!(
+            (&out[1]["id"], &out[1]["error"]["code"]),
+            (&json!(8), &json!(-32601))
+        );
+    }
+}
@@ -0,0 +1,672 @@
+//! Mock ACP client driver: launches an agent command over stdio (NDJSON or
+//! `Content-Length` framing) through `acp_benchmark::stdio`, runs a list of
+//! prompts in one session, and answers the agent's `fs/*`, `terminal/*` and
+//! `session/request_permission` requests from a policy file. Every message
+//! goes through the codec, so an agent that sends something undecodable
+//! fails the run.
+//!
+//! ```json
+//! {
+//!   "fs": {"read": "virtual", "write": "virtual", "files": {"/repo/README.md": "# Hello"}},
+//!   "terminal": {"mode": "fake", "output": "ok\n", "exitCode": 0},
+//!   "permission": "allow"
+//! }
+//! ```
+//!
+//! `fs` access is `deny`, `virtual` (an in-memory file map, seeded by `files`)
+//! or `disk`; `terminal.mode` is `deny`, `fake` (canned output) or `run`;
+//! `permission` is `allow`, `reject` or `cancel`.
+//!
+//! `--permissions rules.json` decides `session/request_permission` from a
+//! rule file instead (see `acp_benchmark::permissions`), and `--audit` writes
+//! each of its decisions as a JSONL line.
+
+use acp_benchmark::domain::*;
+use acp_benchmark::framing::Framing;
+use acp_benchmark::permissions::{AuditEntry, PermissionEngine, PermissionRules};
+use acp_benchmark::stdio::{AgentProcess, Peer};
+use clap::{Parser, ValueEnum};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+// -------------
+// Policy
+// -------------
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum FsAccess {
+    Deny,
+    #[default]
+    Virtual,
+    Disk,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum TerminalAccess {
+    Deny,
+    #[default]
+    Fake,
+    Run,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum PermissionChoice {
+    #[default]
+    Allow,
+    Reject,
+    Cancel,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+struct FsPolicy {
+    read: FsAccess,
+    write: FsAccess,
+    files: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct TerminalPolicy {
+    mode: TerminalAccess,
+    /// Output of every `fake` terminal.
+    output: String,
+    exit_code: i32,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+struct Policy {
+    fs: FsPolicy,
+    terminal: TerminalPolicy,
+    permission: PermissionChoice,
+}
+
+// -------------
+// Client-side request handling
+// -------------
+
+const RESOURCE_NOT_FOUND: i32 = -32002;
+/// Implementation-defined server error, used for policy refusals.
+const DENIED: i32 = -32000;
+
+fn rpc_error(code: i32, message: String) -> JsonRpcError {
+    JsonRpcError {
+        code,
+        message,
+        data: None,
+    }
+}
+
+fn denied(what: &str) -> JsonRpcError {
+    rpc_error(DENIED, format!("{what} denied by policy"))
+}
+
+fn unknown_terminal(id: &str) -> JsonRpcError {
+    rpc_error(RESOURCE_NOT_FOUND, format!("unknown terminal '{id}'"))
+}
+
+struct Terminal {
+    output: String,
+    truncated: bool,
+    exit_code: Option<i32>,
+}
+
+impl Terminal {
+    fn exit_status(&self) -> TerminalExitStatus {
+        TerminalExitStatus {
+            exit_code: self.exit_code,
+            signal: None,
+        }
+    }
+}
+
+struct Client {
+    policy: Policy,
+    /// Overrides `policy.permission` when a rule file is given.
+    permissions: Option<PermissionEngine>,
+    /// The virtual filesystem, seeded from the policy.
+    files: HashMap<String, String>,
+    terminals: HashMap<String, Terminal>,
+    next_terminal: usize,
+    /// The agent's notifications and requests by method, and by update kind
+    /// for `session/update`.
+    seen: BTreeMap<String, usize>,
+}
+
+impl Client {
+    fn new(policy: Policy) -> Self {
+        Client {
+            files: policy.fs.files.clone(),
+            policy,
+            permissions: None,
+            terminals: HashMap::new(),
+            next_terminal: 0,
+            seen: BTreeMap::new(),
+        }
+    }
+
+    fn capabilities(&self) -> ClientCapabilities {
+        ClientCapabilities {
+            fs: FileSystemCapabilities {
+                read_text_file: self.policy.fs.read != FsAccess::Deny,
+                write_text_file: self.policy.fs.write != FsAccess::Deny,
+            },
+            terminal: self.policy.terminal.mode != TerminalAccess::Deny,
+        }
+    }
+
+    fn count(&mut self, message: &AgentToClientMessage) {
+        let key = match message {
+            AgentToClientMessage::SessionUpdate(n) => {
+                format!("{}:{}", message.method(), n.update.tag())
+            }
+            _ => message.method().to_owned(),
+        };
+        *self.seen.entry(key).or_default() += 1;
+    }
+
+    fn read_text_file(
+        &self,
+        params: ReadTextFileParams,
+    ) -> Result<ReadTextFileResult, JsonRpcError> {
+        let path = &params.path;
+        let content = match self.policy.fs.read {
+            FsAccess::Deny => return Err(denied("fs/read_text_file")),
+            FsAccess::Virtual => self.files.get(path).cloned(),
+            FsAccess::Disk => std::fs::read_to_string(path).ok(),
+        }
+        .ok_or_else(|| rpc_error(RESOURCE_NOT_FOUND, format!("no such file '{path}'")))?;
+
+        // `line` is 1-based; `limit` caps the number of lines returned.
+        let content = match (params.line, params.limit) {
+            (None | Some(1), None) => content,
+            (line, limit) => content
+                .lines()
+                .skip(line.unwrap_or(1).max(1) as usize - 1)
+                .take(limit.map_or(usize::MAX, |l| l as usize))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        };
+        Ok(ReadTextFileResult { content })
+    }
+
+    fn write_text_file(
+        &mut self,
+        params: WriteTextFileParams,
+    ) -> Result<WriteTextFileResult, JsonRpcError> {
+        match self.policy.fs.write {
+            FsAccess::Deny => return Err(denied("fs/write_text_file")),
+            FsAccess::Virtual => {
+                self.files.insert(params.path, params.content);
+            }
+            FsAccess::Disk => {
+                std::fs::write(&params.path, &params.content)
+                    .map_err(|e| rpc_error(DENIED, e.to_string()))?;
+            }
+        }
+        Ok(WriteTextFileResult {})
+    }
+
+    fn request_permission(&mut self, params: RequestPermissionParams) -> RequestPermissionResult {
+        use PermissionOptionKind as K;
+
+        if let Some(engine) = &mut self.permissions {
+            return RequestPermissionResult {
+                outcome: engine.decide(&params),
+            };
+        }
+
+        let wanted = |kind: PermissionOptionKind| match self.policy.permission {
+            PermissionChoice::Allow => matches!(kind, K::AllowOnce | K::AllowAlways),
+            PermissionChoice::Reject => matches!(kind, K::RejectOnce | K::RejectAlways),
+            PermissionChoice::Cancel => false,
+        };
+        let outcome = match params.options.into_iter().find(|o| wanted(o.kind)) {
+            Some(option) => RequestPermissionOutcome::Selected {
+                option_id: option.option_id,
+            },
+            None => RequestPermissionOutcome::Cancelled,
+        };
+        RequestPermissionResult { outcome }
+    }
+
+    fn create_terminal(
+        &mut self,
+        params: CreateTerminalParams,
+    ) -> Result<CreateTerminalResult, JsonRpcError> {
+        let terminal = match self.policy.terminal.mode {
+            TerminalAccess::Deny => return Err(denied("terminal/create")),
+            TerminalAccess::Fake => Terminal {
+                output: self.policy.terminal.output.clone(),
+                truncated: false,
+                exit_code: Some(self.policy.terminal.exit_code),
+            },
+            TerminalAccess::Run => {
+                let mut cmd = Command::new(&params.command);
+                cmd.args(&params.args).stdin(Stdio::null());
+                if let Some(cwd) = &params.cwd {
+                    cmd.current_dir(cwd);
+                }
+                for var in &params.env {
+                    cmd.env(&var.name, &var.value);
+                }
+                let out = cmd.output().map_err(|e| rpc_error(DENIED, e.to_string()))?;
+                let mut output = String::from_utf8_lossy(&out.stdout).into_owned();
+                output.push_str(&String::from_utf8_lossy(&out.stderr));
+
+                // Over the limit, keep the tail, cut at a character boundary.
+                let limit = params.output_byte_limit.map(|l| l as usize);
+                let truncated = limit.is_some_and(|l| output.len() > l);
+                if let (true, Some(limit)) = (truncated, limit) {
+                    let mut start = output.len() - limit;
+                    while !output.is_char_boundary(start) {
+                        start += 1;
+                    }
+                    output.drain(..start);
+                }
+                Terminal {
+                    output,
+                    truncated,
+                    exit_code: out.status.code(),
+                }
+            }
+        };
+
+        self.next_terminal += 1;
+        let terminal_id = format!("term-{}", self.next_terminal);
+        self.terminals.insert(terminal_id.clone(), terminal);
+        Ok(CreateTerminalResult { terminal_id })
+    }
+
+    fn terminal(&self, id: &str) -> Result<&Terminal, JsonRpcError> {
+        self.terminals.get(id).ok_or_else(|| unknown_terminal(id))
+    }
+}
+
+impl Peer for Client {
+    fn answer(
+        &mut self,
+        request: AgentToClientMessage,
+    ) -> Result<ClientToAgentMessage, JsonRpcError> {
+        use AgentToClientMessage as A;
+        use ClientToAgentMessage as C;
+
+        self.count(&request);
+        Ok(match request {
+            A::FsReadTextFileRequest(p) => C::FsReadTextFileResult(self.read_text_file(p)?),
+            A::FsWriteTextFileRequest(p) => C::FsWriteTextFileResult(self.write_text_file(p)?),
+            A::SessionRequestPermissionRequest(p) => {
+                C::SessionRequestPermissionResult(self.request_permission(p))
+            }
+            A::TerminalCreateRequest(p) => C::TerminalCreateResult(self.create_terminal(p)?),
+            A::TerminalOutputRequest(p) => {
+                let t = self.terminal(&p.terminal_id)?;
+                C::TerminalOutputResult(TerminalOutputResult {
+                    output: t.output.clone(),
+                    truncated: t.truncated,
+                    exit_status: Some(t.exit_status()),
+                })
+            }
+            A::TerminalWaitForExitRequest(p) => {
+                C::TerminalWaitForExitResult(self.terminal(&p.terminal_id)?.exit_status())
+            }
+            // Terminals finish on creation, so there is nothing to kill.
+            A::TerminalKillRequest(p) => {
+                self.terminal(&p.terminal_id)?;
+                C::TerminalKillResult(KillTerminalCommandResult {})
+            }
+            A::TerminalReleaseRequest(p) => {
+                self.terminals
+                    .remove(&p.terminal_id)
+                    .ok_or_else(|| unknown_terminal(&p.terminal_id))?;
+                C::TerminalReleaseResult(ReleaseTerminalResult {})
+            }
+            other => return Err(acp_benchmark::connection::method_not_found(other.method())),
+        })
+    }
+
+    fn notified(&mut self, notification: &AgentToClientMessage) {
+        self.count(notification);
+    }
+}
+
+// -------------
+// Run
+// -------------
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum FramingArg {
+    Ndjson,
+    ContentLength,
+}
+
+#[derive(Parser, Debug)]
+#[command(name = "acp-mock-client")]
+#[command(about = "Mock ACP client: drive an agent over stdio with scripted prompts")]
+struct Args {
+    /// JSON policy for answering agent requests; defaults allow everything virtually
+    #[arg(long)]
+    policy: Option<PathBuf>,
+
+    /// JSON rule file deciding `session/request_permission` (overrides the
+    /// policy's `permission`)
+    #[arg(long)]
+    permissions: Option<PathBuf>,
+
+    /// Write every rule-file permission decision here as JSONL
+    #[arg(long, requires = "permissions")]
+    audit: Option<PathBuf>,
+
+    /// Prompt text; repeat for several turns
+    #[arg(long)]
+    prompt: Vec<String>,
+
+    /// File with one prompt per line, run after any --prompt
+    #[arg(long)]
+    prompts: Option<PathBuf>,
+
+    /// Working directory sent in session/new; defaults to the current one
+    #[arg(long)]
+    cwd: Option<PathBuf>,
+
+    /// Record the connection as a JSONL trace (`{ts, direction, json}` frames)
+    #[arg(long)]
+    trace: Option<PathBuf>,
+
+    /// Message framing to the agent
+    #[arg(long, value_enum, default_value = "ndjson")]
+    framing: FramingArg,
+
+    /// Seconds to wait for each response, prompt turns included
+    #[arg(long, default_value_t = 300)]
+    timeout: u64,
+
+    /// Agent command to spawn (after `--`)
+    #[arg(last = true, required = true)]
+    agent: Vec<String>,
+}
+
+/// One prompt turn's outcome in [`Report`].
+#[derive(Debug, Serialize)]
+struct TurnReport {
+    #[serde(rename = "stopReason", skip_serializing_if = "Option::is_none")]
+    stop_reason: Option<StopReason>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<JsonRpcError>,
+    elapsed_ms: u128,
+}
+
+#[derive(Debug, Serialize)]
+struct Report<'a> {
+    status: &'static str,
+    agent: String,
+    framing: String,
+    session_id: SessionId,
+    turns: Vec<TurnReport>,
+    agent_messages: &'a BTreeMap<String, usize>,
+    agent_exit_code: Option<i32>,
+    elapsed_ms: u128,
+}
+
+fn fail(error: impl std::fmt::Display) -> ! {
+    println!("{}", json!({"status": "error", "error": error.to_string()}));
+    std::process::exit(1);
+}
+
+fn write_audit(path: &PathBuf, entries: &[AuditEntry]) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    for entry in entries {
+        serde_json::to_writer(&mut out, entry)?;
+        writeln!(out)?;
+    }
+    out.flush()
+}
+
+fn load_policy(path: &PathBuf) -> Result<Policy, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    serde_json::from_str(&text).map_err(|e| e.to_string())
+}
+
+/// The error an agent answered a request with, if it did.
+fn error_of(response: &Message) -> Option<&JsonRpcError> {
+    use AgentToClientMessage as A;
+    match response {
+        Message::FromAgent(
+            A::InitializeError(e) | A::SessionNewError(e) | A::SessionPromptError(_, e),
+        ) => Some(e),
+        _ => None,
+    }
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let policy = match &args.policy {
+        Some(path) => {
+            load_policy(path).unwrap_or_else(|e| fail(format!("policy {}: {e}", path.display())))
+        }
+        None => Policy::default(),
+    };
+    let permissions = args.permissions.as_ref().map(|path| {
+        PermissionRules::load(path)
+            .map(PermissionEngine::new)
+            .unwrap_or_else(|e| fail(format!("permissions {}: {e}", path.display())))
+    });
+    let mut prompts = args.prompt.clone();
+    if let Some(path) = &args.prompts {
+        let text = std::fs::read_to_string(path)
+            .unwrap_or_else(|e| fail(format!("prompts {}: {e}", path.display())));
+        prompts.extend(
+            text.lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty())
+                .map(str::to_owned),
+        );
+    }
+    if prompts.is_empty() {
+        prompts.push("Hello".to_owned());
+    }
+    let cwd = match &args.cwd {
+        Some(cwd) => cwd.clone(),
+        None => std::env::current_dir().unwrap_or_else(|e| fail(e)),
+    };
+    let framing = match args.framing {
+        FramingArg::Ndjson => Framing::Ndjson,
+        FramingArg::ContentLength => Framing::ContentLength,
+    };
+
+    let mut agent = AgentProcess::spawn(&args.agent, framing)
+        .unwrap_or_else(|e| fail(format!("spawn {}: {e}", args.agent[0])))
+        .with_timeout(Duration::from_secs(args.timeout));
+    if let Some(path) = &args.trace {
+        let trace = File::create(path).unwrap_or_else(|e| fail(e));
+        agent = agent.with_trace(BufWriter::new(trace));
+    }
+    let mut client = Client::new(policy);
+    client.permissions = permissions;
+    let start = Instant::now();
+
+    let init = ClientToAgentMessage::Initialize(InitializeParams {
+        protocol_version: 1,
+        client_capabilities: client.capabilities(),
+        client_info: Some(ImplementationInfo {
+            name: "acp-mock-client".to_owned(),
+            title: None,
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+        }),
+    });
+    let response = agent
+        .call(init, &mut client)
+        .unwrap_or_else(|e| fail(e))
+        .response;
+    if let Some(e) = error_of(&response) {
+        fail(format!("initialize failed: {}", e.message));
+    }
+
+    let new_session = ClientToAgentMessage::SessionNew(NewSessionParams {
+        cwd: cwd.to_string_lossy().into_owned(),
+        mcp_servers: Vec::new(),
+    });
+    let session_id = match agent
+        .call(new_session, &mut client)
+        .unwrap_or_else(|e| fail(e))
+        .response
+    {
+        Message::FromAgent(AgentToClientMessage::SessionNewResult(r)) => r.session_id,
+        other => fail(match error_of(&other) {
+            Some(e) => format!("session/new failed: {}", e.message),
+            None => "session/new failed".to_owned(),
+        }),
+    };
+
+    let mut turns = Vec::with_capacity(prompts.len());
+    for text in &prompts {
+        let prompt = ClientToAgentMessage::SessionPrompt(SessionPromptParams {
+            session_id: session_id.clone(),
+            prompt: vec![ContentBlock::Text(TextContent {
+                text: text.clone(),
+                annotations: None,
+            })],
+            meta: None,
+        });
+        let exchange = agent.call(prompt, &mut client).unwrap_or_else(|e| fail(e));
+        let stop_reason = match &exchange.response {
+            Message::FromAgent(AgentToClientMessage::SessionPromptResult(r)) => Some(r.stop_reason),
+            _ => None,
+        };
+        turns.push(TurnReport {
+            stop_reason,
+            error: error_of(&exchange.response).cloned(),
+            elapsed_ms: exchange.elapsed.as_millis(),
+        });
+    }
+
+    let status = agent.finish().unwrap_or_else(|e| fail(e));
+    if let (Some(path), Some(engine)) = (&args.audit, &client.permissions) {
+        write_audit(path, engine.audit())
+            .unwrap_or_else(|e| fail(format!("audit {}: {e}", path.display())));
+    }
+
+    let report = Report {
+        status: "ok",
+        agent: args.agent.join(" "),
+        framing: framing.to_string(),
+        session_id,
+        turns,
+        agent_messages: &client.seen,
+        agent_exit_code: status.code(),
+        elapsed_ms: start.elapsed().as_millis(),
+    };
+    println!("{}", serde_json::to_string(&report).unwrap());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use acp_benchmark::codec::{self, CodecState, Direction};
+    use serde_json::Value;
+
+    fn client(policy: &str) -> Client {
+        Client::new(serde_json::from_str(policy).unwrap())
+    }
+
+    /// Decode `method` with `params` as an agent request, answer it, and
+    /// return the encoded result or the error code.
+    fn ask(client: &mut Client, method: &str, mut params: Value) -> Result<Value, i32> {
+        params["sessionId"] = json!("s1");
+        let id = RequestId::Number(1);
+        let line = json!({"jsonrpc": "2.0", "method": method, "params": params, "id": id});
+        let mut state = CodecState::default();
+        let decoded = codec::decode(Direction::FromAgent, &mut state, &line.to_string()).unwrap();
+        let Message::FromAgent(request) = decoded.message else {
+            unreachable!("decoded from the agent direction")
+        };
+        let reply = client.answer(request).map_err(|e| e.code)?;
+        let json = codec::encode(Some(&id), &Message::FromClient(reply)).unwrap();
+        codec::decode(Direction::FromClient, &mut state, &json).unwrap();
+        Ok(serde_json::from_str::<Value>(&json).unwrap()["result"].take())
+    }
+
+    #[test]
+    fn virtual_fs_reads_writes_and_slices_lines() {
+        let mut c = client(r#"{"fs":{"files":{"/a.txt":"one\ntwo\nthree"}}}"#);
+        let read = ask(
+            &mut c,
+            "fs/read_text_file",
+            json!({"path": "/a.txt", "line": 2, "limit": 1}),
+        )
+        .unwrap();
+        assert_eq!(read["content"], "two");
+
+        ask(
+            &mut c,
+            "fs/write_text_file",
+            json!({"path": "/b.txt", "content": "new"}),
+        )
+        .unwrap();
+        let read = ask(&mut c, "fs/read_text_file", json!({"path": "/b.txt"})).unwrap();
+        assert_eq!(read["content"], "new");
+
+        let missing = ask(&mut c, "fs/read_text_file", json!({"path": "/nope"}));
+        assert_eq!(missing.unwrap_err(), RESOURCE_NOT_FOUND);
+        assert_eq!(c.seen["fs/read_text_file"], 3);
+    }
+
+    #[test]
+    fn policy_denies_and_picks_permission_options() {
+        let mut c =
+            client(r#"{"fs":{"write":"deny"},"terminal":{"mode":"deny"},"permission":"reject"}"#);
+        assert!(!c.capabilities().fs.write_text_file);
+        let err = ask(
+            &mut c,
+            "fs/write_text_file",
+            json!({"path": "/x", "content": ""}),
+        );
+        assert_eq!(err.unwrap_err(), DENIED);
+        assert_eq!(
+            ask(&mut c, "terminal/create", json!({"command": "ls"})).unwrap_err(),
+            DENIED
+        );
+
+        let request = json!({
+            "toolCall": {"toolCallId": "t1"},
+            "options": [
+                {"optionId": "yes", "name": "Yes", "kind": "allow_once"},
+                {"optionId": "no", "name": "No", "kind": "reject_once"}
+            ]
+        });
+        let outcome = ask(&mut c, "session/request_permission", request).unwrap();
+        assert_eq!(outcome["outcome"]["optionId"], "no");
+    }
+
+    #[test]
+    fn fake_terminal_lifecycle() {
+        let mut c = client(r#"{"terminal":{"output":"built\n","exitCode":2}}"#);
+        let created = ask(&mut c, "terminal/create", json!({"command": "make"})).unwrap();
+        let id = json!({"terminalId": created["terminalId"]});
+
+        let output = ask(&mut c, "terminal/output", id.clone()).unwrap();
+        assert_eq!(output["output"], "built\n");
+        assert_eq!(output["exitStatus"]["exitCode"], 2);
+        assert_eq!(
+            ask(&mut c, "terminal/wait_for_exit", id.clone()).unwrap()["exitCode"],
+            2
+        );
+
+        ask(&mut c, "terminal/release", id.clone()).unwrap();
+        assert_eq!(
+            ask(&mut c, "terminal/output", id).unwrap_err(),
+            RESOURCE_NOT_FOUND
+        );
+    }
+}
@@ -0,0 +1,217 @@
+//! Zero-copy decoding for the hot path: text chunks streamed in
+//! `session/update`. Their text and session id borrow from the input line
+//! (`Cow` only allocates when the JSON string has escapes), and the envelope
+//! skips over `params` as a `RawValue` instead of building a `Value` tree.
+//! Every other message goes through the owned [`codec::decode`].
+
+use crate::codec::{self, CodecState, DecodeError, Decoded, Direction};
+use crate::domain::{
+    AgentToClientMessage, ContentBlock, ContentChunk, Message, SessionId, SessionUpdate,
+    SessionUpdateNotification, TextContent,
+};
+use serde::Deserialize;
+use serde_json::value::RawValue;
+use std::borrow::Cow;
+
+/// Which chunk variant of `session/update` a [`TextChunk`] came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkKind {
+    UserMessage,
+    AgentMessage,
+    AgentThought,
+}
+
+impl ChunkKind {
+    fn from_tag(tag: &str) -> Option<ChunkKind> {
+        match tag {
+            "user_message_chunk" => Some(ChunkKind::UserMessage),
+            "agent_message_chunk" => Some(ChunkKind::AgentMessage),
+            "agent_thought_chunk" => Some(ChunkKind::AgentThought),
+            _ => None,
+        }
+    }
+}
+
+/// A text chunk borrowed from the input line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextChunk<'a> {
+    pub session_id: Cow<'a, str>,
+    pub kind: ChunkKind,
+    pub text: Cow<'a, str>,
+}
+
+impl TextChunk<'_> {
+    /// The owned message [`codec::decode`] produces for the same line.
+    pub fn into_message(self) -> Message {
+        let chunk = ContentChunk {
+            content: ContentBlock::Text(TextContent {
+                text: self.text.into_owned(),
+                annotations: None,
+            }),
+        };
+        let update = match self.kind {
+            ChunkKind::UserMessage => SessionUpdate::UserMessageChunk(chunk),
+            ChunkKind::AgentMessage => SessionUpdate::AgentMessageChunk(chunk),
+            ChunkKind::AgentThought => SessionUpdate::AgentThoughtChunk(chunk),
+        };
+        Message::FromAgent(AgentToClientMessage::SessionUpdate(
+            SessionUpdateNotification {
+                session_id: SessionId(self.session_id.into_owned()),
+                update,
+                meta: None,
+            },
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub enum BorrowedDecoded<'a> {
+    Chunk(TextChunk<'a>),
+    /// Anything that is not a text chunk, decoded by the owned codec.
+    Owned(Box<Decoded>),
+}
+
+// -------------
+// Wire shapes
+// -------------
+
+#[derive(Deserialize)]
+struct Envelope<'a> {
+    // Plain `&str`: escaped (unusual) values fail here and fall back.
+    #[serde(default, borrow)]
+    jsonrpc: Option<&'a str>,
+    #[serde(default, borrow)]
+    method: Option<&'a str>,
+    #[serde(default, borrow)]
+    params: Option<&'a RawValue>,
+    #[serde(default, borrow)]
+    id: Option<&'a RawValue>,
+}
+
+/// Unknown fields here and below (`_meta`, `annotations`) fail to parse and
+/// fall back, so the owned codec keeps them.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct UpdateParams<'a> {
+    #[serde(borrow)]
+    session_id: Cow<'a, str>,
+    #[serde(borrow)]
+    update: ChunkUpdate<'a>,
+}
+
+/// Fields are required rather than `Option`: serde only borrows a `Cow` that
+/// is not wrapped. Updates without text content fail to parse and fall back.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct ChunkUpdate<'a> {
+    #[serde(borrow)]
+    session_update: Cow<'a, str>,
+    #[serde(borrow)]
+    content: ChunkContent<'a>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ChunkContent<'a> {
+    #[serde(rename = "type", borrow)]
+    kind: Cow<'a, str>,
+    #[serde(borrow)]
+    text: Cow<'a, str>,
+}
+
+/// The borrowed chunk in `json`, or `None` when it needs the owned codec
+/// (including anything malformed, so errors match the owned path).
+fn text_chunk(json: &str) -> Option<TextChunk<'_>> {
+    let envelope: Envelope = serde_json::from_str(json).ok()?;
+    if envelope.jsonrpc != Some("2.0")
+        || envelope.method != Some("session/update")
+        || envelope.id.is_some()
+    {
+        return None;
+    }
+
+    let params: UpdateParams = serde_json::from_str(envelope.params?.get()).ok()?;
+    let kind = ChunkKind::from_tag(&params.update.session_update)?;
+    if params.update.content.kind != "text" {
+        return None;
+    }
+    Some(TextChunk {
+        session_id: params.session_id,
+        kind,
+        text: params.update.content.text,
+    })
+}
+
+/// Decode one message, borrowing text chunks sent `FromAgent` from `json`.
+/// Chunks are notifications, so they leave `state` untouched.
+pub fn decode<'a>(
+    direction: Direction,
+    state: &mut CodecState,
+    json: &'a str,
+) -> Result<BorrowedDecoded<'a>, DecodeError> {
+    if direction == Direction::FromAgent {
+        if let Some(chunk) = text_chunk(json) {
+            return Ok(BorrowedDecoded::Chunk(chunk));
+        }
+    }
+    codec::decode(direction, state, json).map(|d| BorrowedDecoded::Owned(Box::new(d)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_fresh(json: &str) -> BorrowedDecoded<'_> {
+        decode(Direction::FromAgent, &mut CodecState::default(), json).unwrap()
+    }
+
+    #[test]
+    fn borrows_chunk_text_and_matches_owned_decode() {
+        let json = r#"{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s-1","update":{"sessionUpdate":"agent_message_chunk","content":{"type":"text","text":"hello there"}}}}"#;
+        let BorrowedDecoded::Chunk(chunk) = decode_fresh(json) else {
+            panic!("expected a borrowed chunk");
+        };
+        assert!(matches!(chunk.text, Cow::Borrowed("hello there")));
+        assert_eq!(chunk.kind, ChunkKind::AgentMessage);
+
+        let owned = codec::decode(Direction::FromAgent, &mut CodecState::default(), json).unwrap();
+        assert_eq!(chunk.into_message(), owned.message.clone());
+
+        // Escapes force a copy but decode the same text.
+        let escaped = json.replace("hello there", r#"say \"hi\""#);
+        let BorrowedDecoded::Chunk(chunk) = decode_fresh(&escaped) else {
+            panic!("expected a borrowed chunk");
+        };
+        assert!(matches!(chunk.text, Cow::Owned(ref t) if t == r#"say "hi""#));
+
+        // Annotations and `_meta` go to the owned codec, which keeps them.
+        for extra in [
+            json.replace(
+                r#""text":"hello"#,
+                r#""annotations":{"priority":1},"text":"hello"#,
+            ),
+            json.replace(r#""sessionId""#, r#""_meta":{"trace":"t"},"sessionId""#),
+        ] {
+            let BorrowedDecoded::Owned(decoded) = decode_fresh(&extra) else {
+                panic!("expected the owned codec for {extra}");
+            };
+            assert_ne!(decoded.message, owned.message);
+        }
+    }
+
+    #[test]
+    fn falls_back_to_owned_codec() {
+        let plan = r#"{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s-1","update":{"sessionUpdate":"plan","entries":[]}}}"#;
+        assert!(matches!(decode_fresh(plan), BorrowedDecoded::Owned(_)));
+
+        let image = r#"{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s-1","update":{"sessionUpdate":"agent_message_chunk","content":{"type":"image","data":"AA==","mimeType":"image/png"}}}}"#;
+        assert!(matches!(decode_fresh(image), BorrowedDecoded::Owned(_)));
+
+        // Malformed chunks report the owned codec's error.
+        let bad = r#"{"jsonrpc":"2.0","method":"session/update","params":{"update":{"sessionUpdate":"agent_message_chunk"}}}"#;
+        let borrowed = decode(Direction::FromAgent, &mut CodecState::default(), bad).unwrap_err();
+        let owned =
+            codec::decode(Direction::FromAgent, &mut CodecState::default(), bad).unwrap_err();
+        assert_eq!(borrowed, owned);
+    }
+}
@@ -0,0 +1,1137 @@
+//! JSON-RPC 2.0 codec + ACP method routing.
+//! Mirrors `runtime/src/Acp.Codec.fs` and `Acp.Codec.Types.fs`:
+//! - decodes raw JSON-RPC objects into typed ACP domain messages
+//! - correlates JSON-RPC responses to requests via `id`
+//! - reattaches context that is absent from some wire responses (e.g. sessionId)
+//! - encodes typed messages back to JSON-RPC, dropping that context again
+
+use crate::domain::*;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::error::Category;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+// -------------
+// Codec types
+// -------------
+
+/// Direction of message flow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    FromClient,
+    FromAgent,
+}
+
+impl std::fmt::Display for Direction {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Direction::FromClient => f.write_str("fromClient"),
+            Direction::FromAgent => f.write_str("fromAgent"),
+        }
+    }
+}
+
+/// Errors that can occur during decoding.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodeError {
+    InvalidJson(String),
+    InvalidJsonRpc(String),
+    /// The method exists but belongs to the other side of the connection.
+    DirectionMismatch {
+        method: String,
+        expected: Direction,
+    },
+    /// A request reused the id of one still awaiting its response.
+    DuplicateRequestId(RequestId),
+    /// A response arrived for an id with no pending request.
+    UnknownRequestId(RequestId),
+    InvalidParams {
+        method: String,
+        details: String,
+    },
+    InvalidResult {
+        method: String,
+        details: String,
+    },
+    InvalidError(String),
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DecodeError::InvalidJson(d) => write!(f, "invalid JSON: {d}"),
+            DecodeError::InvalidJsonRpc(d) => write!(f, "invalid JSON-RPC: {d}"),
+            DecodeError::DirectionMismatch { method, expected } => {
+                write!(f, "'{method}' is only valid {expected}")
+            }
+            DecodeError::DuplicateRequestId(id) => write!(f, "duplicate request id {id}"),
+            DecodeError::UnknownRequestId(id) => write!(f, "unknown request id {id}"),
+            DecodeError::InvalidParams { method, details } => {
+                write!(f, "invalid params for '{method}': {details}")
+            }
+            DecodeError::InvalidResult { method, details } => {
+                write!(f, "invalid result for '{method}': {details}")
+            }
+            DecodeError::InvalidError(d) => write!(f, "invalid error object: {d}"),
+        }
+    }
+}
+
+/// Errors that can occur during encoding.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EncodeError {
+    /// Requests and responses need an id.
+    MissingRequestId,
+    /// Notifications must not carry one.
+    UnexpectedRequestId,
+    UnsupportedMessage(String),
+}
+
+impl std::fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            EncodeError::MissingRequestId => f.write_str("message needs a request id"),
+            EncodeError::UnexpectedRequestId => f.write_str("notifications take no request id"),
+            EncodeError::UnsupportedMessage(d) => write!(f, "cannot encode message: {d}"),
+        }
+    }
+}
+
+/// Pending client request - tracks what we're waiting for from the agent.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PendingClientRequest {
+    Initialize,
+    ProxyInitialize,
+    Authenticate,
+    SessionNew,
+    SessionLoad(LoadSessionParams),
+    SessionPrompt(SessionPromptParams),
+    SessionSetMode(SetSessionModeParams),
+    ProxySuccessor(String),
+    ExtRequest(String),
+}
+
+impl PendingClientRequest {
+    pub fn method(&self) -> &str {
+        match self {
+            PendingClientRequest::Initialize => "initialize",
+            PendingClientRequest::ProxyInitialize => "proxy/initialize",
+            PendingClientRequest::Authenticate => "authenticate",
+            PendingClientRequest::SessionNew => "session/new",
+            PendingClientRequest::SessionLoad(_) => "session/load",
+            PendingClientRequest::SessionPrompt(_) => "session/prompt",
+            PendingClientRequest::SessionSetMode(_) => "session/set_mode",
+            PendingClientRequest::ProxySuccessor(_) => "proxy/successor",
+            PendingClientRequest::ExtRequest(m) => m,
+        }
+    }
+}
+
+/// Pending agent request - tracks what we're waiting for from the client.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PendingAgentRequest {
+    FsReadTextFile(ReadTextFileParams),
+    FsWriteTextFile(WriteTextFileParams),
+    SessionRequestPermission(RequestPermissionParams),
+    TerminalCreate(CreateTerminalParams),
+    TerminalOutput(TerminalOutputParams),
+    TerminalWaitForExit(WaitForTerminalExitParams),
+    TerminalKill(KillTerminalCommandParams),
+    TerminalRelease(ReleaseTerminalParams),
+    ProxySuccessor(String),
+    ExtRequest(String),
+}
+
+impl PendingAgentRequest {
+    pub fn method(&self) -> &str {
+        match self {
+            PendingAgentRequest::FsReadTextFile(_) => "fs/read_text_file",
+            PendingAgentRequest::FsWriteTextFile(_) => "fs/write_text_file",
+            PendingAgentRequest::SessionRequestPermission(_) => "session/request_permission",
+            PendingAgentRequest::TerminalCreate(_) => "terminal/create",
+            PendingAgentRequest::TerminalOutput(_) => "terminal/output",
+            PendingAgentRequest::TerminalWaitForExit(_) => "terminal/wait_for_exit",
+            PendingAgentRequest::TerminalKill(_) => "terminal/kill",
+            PendingAgentRequest::TerminalRelease(_) => "terminal/release",
+            PendingAgentRequest::ProxySuccessor(_) => "proxy/successor",
+            PendingAgentRequest::ExtRequest(m) => m,
+        }
+    }
+}
+
+/// Codec state - tracks pending requests for correlation.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CodecState {
+    pub pending_client_requests: HashMap<RequestId, PendingClientRequest>,
+    pub pending_agent_requests: HashMap<RequestId, PendingAgentRequest>,
+}
+
+/// A decoded ACP message together with its JSON-RPC id (absent for notifications).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decoded {
+    pub id: Option<RequestId>,
+    pub message: Message,
+}
+
+// -------------
+// Envelope
+// -------------
+
+/// Raw JSON-RPC 2.0 object before method routing.
+#[derive(Deserialize)]
+struct Envelope {
+    #[serde(default)]
+    jsonrpc: Option<String>,
+    #[serde(default)]
+    method: Option<String>,
+    #[serde(default)]
+    params: Option<Value>,
+    #[serde(default)]
+    id: Option<RequestId>,
+    #[serde(default, deserialize_with = "present")]
+    result: Option<Value>,
+    #[serde(default, deserialize_with = "present")]
+    error: Option<Value>,
+}
+
+/// Treat an explicit `null` as present (`Some(Value::Null)`) rather than absent.
+fn present<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Value>, D::Error> {
+    Value::deserialize(d).map(Some)
+}
+
+fn invalid_json_rpc(details: &str) -> DecodeError {
+    DecodeError::InvalidJsonRpc(details.to_owned())
+}
+
+/// Methods whose requests and notifications originate at the agent.
+const AGENT_METHODS: [&str; 9] = [
+    "session/update",
+    "session/request_permission",
+    "fs/read_text_file",
+    "fs/write_text_file",
+    "terminal/create",
+    "terminal/output",
+    "terminal/wait_for_exit",
+    "terminal/kill",
+    "terminal/release",
+];
+
+/// Best-guess direction for a raw message that carries none (e.g. an NDJSON
+/// scenario line): agent methods and bare responses are `FromAgent`, the rest
+/// `FromClient`.
+pub fn infer_direction(json: &str) -> Direction {
+    #[derive(Deserialize)]
+    struct MethodOnly {
+        #[serde(default)]
+        method: Option<String>,
+    }
+
+    match serde_json::from_str::<MethodOnly>(json) {
+        Ok(MethodOnly { method: None }) => Direction::FromAgent,
+        Ok(MethodOnly { method: Some(m) }) if AGENT_METHODS.contains(&m.as_str()) => {
+            Direction::FromAgent
+        }
+        _ => Direction::FromClient,
+    }
+}
+
+/// The id of a raw message that may not decode, if it parses that far: enough
+/// to answer a malformed request.
+pub fn peek_id(json: &str) -> Option<RequestId> {
+    #[derive(Deserialize)]
+    struct IdOnly {
+        #[serde(default)]
+        id: Option<RequestId>,
+    }
+
+    serde_json::from_str::<IdOnly>(json).ok()?.id
+}
+
+// -------------
+// Decoding
+// -------------
+
+/// Decode one JSON-RPC message sent in `direction`, updating `state` with the
+/// requests it opens or the responses it settles. On error `state` is untouched.
+pub fn decode(
+    direction: Direction,
+    state: &mut CodecState,
+    json: &str,
+) -> Result<Decoded, DecodeError> {
+    let envelope: Envelope = serde_json::from_str(json).map_err(|e| match e.classify() {
+        Category::Data => DecodeError::InvalidJsonRpc(e.to_string()),
+        _ => DecodeError::InvalidJson(e.to_string()),
+    })?;
+
+    match envelope.jsonrpc.as_deref() {
+        None => return Err(invalid_json_rpc("missing 'jsonrpc'")),
+        Some("2.0") => {}
+        Some(_) => return Err(invalid_json_rpc("jsonrpc must be '2.0'")),
+    }
+
+    let id = envelope.id;
+
+    if let Some(method) = envelope.method {
+        if method.trim().is_empty() {
+            return Err(invalid_json_rpc("method must be a non-empty string"));
+        }
+
+        let params = envelope.params;
+        let message = match (&id, direction) {
+            (None, Direction::FromClient) => {
+                Message::FromClient(decode_client_notification(&method, params)?)
+            }
+            (None, Direction::FromAgent) => {
+                Message::FromAgent(decode_agent_notification(&method, params)?)
+            }
+            (Some(id), Direction::FromClient) => {
+                let (msg, pending) = decode_client_request(&method, params)?;
+                if state.pending_client_requests.contains_key(id) {
+                    return Err(DecodeError::DuplicateRequestId(id.clone()));
+                }
+                state.pending_client_requests.insert(id.clone(), pending);
+                Message::FromClient(msg)
+            }
+            (Some(id), Direction::FromAgent) => {
+                let (msg, pending) = decode_agent_request(&method, params)?;
+                if state.pending_agent_requests.contains_key(id) {
+                    return Err(DecodeError::DuplicateRequestId(id.clone()));
+                }
+                state.pending_agent_requests.insert(id.clone(), pending);
+                Message::FromAgent(msg)
+            }
+        };
+
+        Ok(Decoded { id, message })
+    } else if envelope.result.is_some() || envelope.error.is_some() {
+        let Some(id) = id else {
+            return Err(invalid_json_rpc("response must include 'id'"));
+        };
+
+        // `"result": null` carries no payload, same as an absent result.
+        let result = envelope.result.filter(|r| !r.is_null());
+
+        let message = match direction {
+            Direction::FromAgent => {
+                let pending = state
+                    .pending_client_requests
+                    .get(&id)
+                    .ok_or_else(|| DecodeError::UnknownRequestId(id.clone()))?;
+                let msg = match envelope.error {
+                    Some(e) => decode_agent_error(pending, decode_rpc_error(e)?),
+                    None => decode_agent_result(pending, result)?,
+                };
+                state.pending_client_requests.remove(&id);
+                Message::FromAgent(msg)
+            }
+            Direction::FromClient => {
+                let pending = state
+                    .pending_agent_requests
+                    .get(&id)
+                    .ok_or_else(|| DecodeError::UnknownRequestId(id.clone()))?;
+                let msg = match envelope.error {
+                    Some(e) => decode_client_error(pending, decode_rpc_error(e)?),
+                    None => decode_client_result(pending, result)?,
+                };
+                state.pending_agent_requests.remove(&id);
+                Message::FromClient(msg)
+            }
+        };
+
+        Ok(Decoded {
+            id: Some(id),
+            message,
+        })
+    } else {
+        Err(invalid_json_rpc(
+            "message must be a request, notification, or response",
+        ))
+    }
+}
+
+fn missing_params(method: &str) -> DecodeError {
+    DecodeError::InvalidParams {
+        method: method.to_owned(),
+        details: "missing params".to_owned(),
+    }
+}
+
+fn typed<T: DeserializeOwned>(method: &str, params: Option<Value>) -> Result<T, DecodeError> {
+    match params {
+        None => Err(missing_params(method)),
+        Some(p) => serde_json::from_value(p).map_err(|e| DecodeError::InvalidParams {
+            method: method.to_owned(),
+            details: e.to_string(),
+        }),
+    }
+}
+
+fn typed_result<T: DeserializeOwned>(
+    method: &str,
+    result: Option<Value>,
+) -> Result<T, DecodeError> {
+    let invalid = |details: String| DecodeError::InvalidResult {
+        method: method.to_owned(),
+        details,
+    };
+
+    match result {
+        None => Err(invalid("missing result".to_owned())),
+        Some(r) => serde_json::from_value(r).map_err(|e| invalid(e.to_string())),
+    }
+}
+
+fn decode_rpc_error(error: Value) -> Result<JsonRpcError, DecodeError> {
+    serde_json::from_value(error).map_err(|e| DecodeError::InvalidError(e.to_string()))
+}
+
+fn decode_client_request(
+    method: &str,
+    params: Option<Value>,
+) -> Result<(ClientToAgentMessage, PendingClientRequest), DecodeError> {
+    use ClientToAgentMessage as C;
+    use PendingClientRequest as P;
+
+    let decoded = match method {
+        "initialize" => (C::Initialize(typed(method, params)?), P::Initialize),
+        "proxy/initialize" => (
+            C::ProxyInitialize(typed(method, params)?),
+            P::ProxyInitialize,
+        ),
+        "authenticate" => (C::Authenticate(typed(method, params)?), P::Authenticate),
+        "session/new" => (C::SessionNew(typed(method, params)?), P::SessionNew),
+        "session/load" => {
+            let p: LoadSessionParams = typed(method, params)?;
+            (C::SessionLoad(p.clone()), P::SessionLoad(p))
+        }
+        "session/prompt" => {
+            let p: SessionPromptParams = typed(method, params)?;
+            (C::SessionPrompt(p.clone()), P::SessionPrompt(p))
+        }
+        "session/set_mode" => {
+            let p: SetSessionModeParams = typed(method, params)?;
+            (C::SessionSetMode(p.clone()), P::SessionSetMode(p))
+        }
+        "proxy/successor" => {
+            let p: ProxySuccessorParams = typed(method, params)?;
+            let pending = P::ProxySuccessor(p.method.clone());
+            (C::ProxySuccessorRequest(p), pending)
+        }
+        m if m == "session/cancel" || AGENT_METHODS.contains(&m) => {
+            return Err(direction_mismatch(method, Direction::FromAgent))
+        }
+
+        // Extension request (opaque params allowed).
+        other => (
+            C::ExtRequest {
+                method: other.to_owned(),
+                params,
+            },
+            P::ExtRequest(other.to_owned()),
+        ),
+    };
+
+    Ok(decoded)
+}
+
+fn decode_client_notification(
+    method: &str,
+    params: Option<Value>,
+) -> Result<ClientToAgentMessage, DecodeError> {
+    use ClientToAgentMessage as C;
+
+    let message = match method {
+        "session/cancel" => C::SessionCancel(typed(method, params)?),
+        "proxy/successor" => C::ProxySuccessorNotification(typed(method, params)?),
+        other => C::ExtNotification {
+            method: other.to_owned(),
+            params,
+        },
+    };
+
+    Ok(message)
+}
+
+fn decode_agent_request(
+    method: &str,
+    params: Option<Value>,
+) -> Result<(AgentToClientMessage, PendingAgentRequest), DecodeError> {
+    use AgentToClientMessage as A;
+    use PendingAgentRequest as P;
+
+    let decoded = match method {
+        "proxy/successor" => {
+            let p: ProxySuccessorParams = typed(method, params)?;
+            let pending = P::ProxySuccessor(p.method.clone());
+            (A::ProxySuccessorRequest(p), pending)
+        }
+        "fs/read_text_file" => {
+            let p: ReadTextFileParams = typed(method, params)?;
+            (A::FsReadTextFileRequest(p.clone()), P::FsReadTextFile(p))
+        }
+        "fs/write_text_file" => {
+            let p: WriteTextFileParams = typed(method, params)?;
+            (A::FsWriteTextFileRequest(p.clone()), P::FsWriteTextFile(p))
+        }
+        "session/request_permission" => {
+            let p: RequestPermissionParams = typed(method, params)?;
+            (
+                A::SessionRequestPermissionRequest(p.clone()),
+                P::SessionRequestPermission(p),
+            )
+        }
+        "terminal/create" => {
+            let p: CreateTerminalParams = typed(method, params)?;
+            (A::TerminalCreateRequest(p.clone()), P::TerminalCreate(p))
+        }
+        "terminal/output" => {
+            let p: TerminalOutputParams = typed(method, params)?;
+            (A::TerminalOutputRequest(p.clone()), P::TerminalOutput(p))
+        }
+        "terminal/wait_for_exit" => {
+            let p: WaitForTerminalExitParams = typed(method, params)?;
+            (
+                A::TerminalWaitForExitRequest(p.clone()),
+                P::TerminalWaitForExit(p),
+            )
+        }
+        "terminal/kill" => {
+            let p: KillTerminalCommandParams = typed(method, params)?;
+            (A::TerminalKillRequest(p.clone()), P::TerminalKill(p))
+        }
+        "terminal/release" => {
+            let p: ReleaseTerminalParams = typed(method, params)?;
+            (A::TerminalReleaseRequest(p.clone()), P::TerminalRelease(p))
+        }
+        "initialize" | "authenticate" | "session/new" | "session/load" | "session/prompt"
+        | "session/set_mode" | "session/cancel" | "session/update" => {
+            return Err(direction_mismatch(method, Direction::FromClient))
+        }
+        other => (
+            A::ExtRequest {
+                method: other.to_owned(),
+                params,
+            },
+            P::ExtRequest(other.to_owned()),
+        ),
+    };
+
+    Ok(decoded)
+}
+
+fn decode_agent_notification(
+    method: &str,
+    params: Option<Value>,
+) -> Result<AgentToClientMessage, DecodeError> {
+    use AgentToClientMessage as A;
+
+    let message = match method {
+        "proxy/successor" => A::ProxySuccessorNotification(typed(method, params)?),
+        "session/update" => A::SessionUpdate(typed(method, params)?),
+        other => A::ExtNotification {
+            method: other.to_owned(),
+            params,
+        },
+    };
+
+    Ok(message)
+}
+
+fn direction_mismatch(method: &str, expected: Direction) -> DecodeError {
+    DecodeError::DirectionMismatch {
+        method: method.to_owned(),
+        expected,
+    }
+}
+
+// ---- Responses ----
+
+/// Wire shape of the session/load result; the session id comes from the request.
+#[derive(Deserialize)]
+struct LoadSessionResultWire {
+    #[serde(default)]
+    modes: Option<SessionModeState>,
+}
+
+/// Wire shape of the session/prompt result; the session id comes from the request.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SessionPromptResultWire {
+    stop_reason: StopReason,
+    #[serde(default)]
+    usage: Option<Map<String, Value>>,
+    #[serde(rename = "_meta", default)]
+    meta: Option<Map<String, Value>>,
+}
+
+fn decode_agent_result(
+    pending: &PendingClientRequest,
+    result: Option<Value>,
+) -> Result<AgentToClientMessage, DecodeError> {
+    use AgentToClientMessage as A;
+    use PendingClientRequest as P;
+
+    let method = pending.method();
+
+    let message = match pending {
+        P::Initialize => A::InitializeResult(typed_result(method, result)?),
+        P::ProxyInitialize => A::ProxyInitializeResult(typed_result(method, result)?),
+        P::Authenticate => A::AuthenticateResult(AuthenticateResult {}),
+        P::SessionNew => A::SessionNewResult(typed_result(method, result)?),
+        P::SessionLoad(req) => {
+            let modes = match result {
+                None => None,
+                Some(r) => typed_result::<LoadSessionResultWire>(method, Some(r))?.modes,
+            };
+            A::SessionLoadResult(LoadSessionResult {
+                session_id: req.session_id.clone(),
+                modes,
+            })
+        }
+        P::SessionPrompt(req) => {
+            let wire: SessionPromptResultWire = typed_result(method, result)?;
+            A::SessionPromptResult(SessionPromptResult {
+                session_id: req.session_id.clone(),
+                stop_reason: wire.stop_reason,
+                usage: wire.usage,
+                meta: wire.meta,
+            })
+        }
+        P::SessionSetMode(req) => A::SessionSetModeResult(SetSessionModeResult {
+            session_id: req.session_id.clone(),
+            mode_id: req.mode_id.clone(),
+        }),
+        P::ProxySuccessor(m) => A::ProxySuccessorResponse {
+            method: m.clone(),
+            result,
+        },
+        P::ExtRequest(m) => A::ExtResponse {
+            method: m.clone(),
+            result,
+        },
+    };
+
+    Ok(message)
+}
+
+fn decode_agent_error(pending: &PendingClientRequest, error: JsonRpcError) -> AgentToClientMessage {
+    use AgentToClientMessage as A;
+    use PendingClientRequest as P;
+
+    match pending {
+        P::Initialize => A::InitializeError(error),
+        P::ProxyInitialize => A::ProxyInitializeError(error),
+        P::Authenticate => A::AuthenticateError(error),
+        P::SessionNew => A::SessionNewError(error),
+        P::SessionLoad(req) => A::SessionLoadError(req.clone(), error),
+        P::SessionPrompt(req) => A::SessionPromptError(req.clone(), error),
+        P::SessionSetMode(req) => A::SessionSetModeError(req.clone(), error),
+        P::ProxySuccessor(m) => A::ProxySuccessorError {
+            method: m.clone(),
+            error,
+        },
+        P::ExtRequest(m) => A::ExtError {
+            method: m.clone(),
+            error,
+        },
+    }
+}
+
+fn decode_client_result(
+    pending: &PendingAgentRequest,
+    result: Option<Value>,
+) -> Result<ClientToAgentMessage, DecodeError> {
+    use ClientToAgentMessage as C;
+    use PendingAgentRequest as P;
+
+    let method = pending.method();
+
+    let message = match pending {
+        P::ProxySuccessor(m) => C::ProxySuccessorResponse {
+            method: m.clone(),
+            result,
+        },
+        P::FsReadTextFile(_) => C::FsReadTextFileResult(typed_result(method, result)?),
+        P::FsWriteTextFile(_) => C::FsWriteTextFileResult(WriteTextFileResult {}),
+        P::SessionRequestPermission(_) => {
+            C::SessionRequestPermissionResult(typed_result(method, result)?)
+        }
+        P::TerminalCreate(_) => C::TerminalCreateResult(typed_result(method, result)?),
+        P::TerminalOutput(_) => C::TerminalOutputResult(typed_result(method, result)?),
+        P::TerminalWaitForExit(_) => C::TerminalWaitForExitResult(typed_result(method, result)?),
+        P::TerminalKill(_) => C::TerminalKillResult(KillTerminalCommandResult {}),
+        P::TerminalRelease(_) => C::TerminalReleaseResult(ReleaseTerminalResult {}),
+        P::ExtRequest(m) => C::ExtResponse {
+            method: m.clone(),
+            result,
+        },
+    };
+
+    Ok(message)
+}
+
+fn decode_client_error(pending: &PendingAgentRequest, error: JsonRpcError) -> ClientToAgentMessage {
+    use ClientToAgentMessage as C;
+    use PendingAgentRequest as P;
+
+    match pending {
+        P::ProxySuccessor(m) => C::ProxySuccessorError {
+            method: m.clone(),
+            error,
+        },
+        P::FsReadTextFile(req) => C::FsReadTextFileError(req.clone(), error),
+        P::FsWriteTextFile(req) => C::FsWriteTextFileError(req.clone(), error),
+        P::SessionRequestPermission(req) => C::SessionRequestPermissionError(req.clone(), error),
+        P::TerminalCreate(req) => C::TerminalCreateError(req.clone(), error),
+        P::TerminalOutput(req) => C::TerminalOutputError(req.clone(), error),
+        P::TerminalWaitForExit(req) => C::TerminalWaitForExitError(req.clone(), error),
+        P::TerminalKill(req) => C::TerminalKillError(req.clone(), error),
+        P::TerminalRelease(req) => C::TerminalReleaseError(req.clone(), error),
+        P::ExtRequest(m) => C::ExtError {
+            method: m.clone(),
+            error,
+        },
+    }
+}
+
+/// The error response, sent in `direction`, to the request `id` still pending
+/// in `state`: how a connection answers a request its handler failed.
+pub fn error_reply(
+    direction: Direction,
+    state: &CodecState,
+    id: &RequestId,
+    error: JsonRpcError,
+) -> Result<Message, DecodeError> {
+    let unknown = || DecodeError::UnknownRequestId(id.clone());
+    match direction {
+        Direction::FromAgent => state
+            .pending_client_requests
+            .get(id)
+            .map(|pending| Message::FromAgent(decode_agent_error(pending, error)))
+            .ok_or_else(unknown),
+        Direction::FromClient => state
+            .pending_agent_requests
+            .get(id)
+            .map(|pending| Message::FromClient(decode_client_error(pending, error)))
+            .ok_or_else(unknown),
+    }
+}
+
+// -------------
+// Encoding
+// -------------
+
+#[derive(Serialize)]
+struct OutgoingRequest<'a, P> {
+    jsonrpc: &'static str,
+    method: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    params: Option<&'a P>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<&'a RequestId>,
+}
+
+#[derive(Serialize)]
+struct OutgoingResult<'a, R> {
+    jsonrpc: &'static str,
+    /// `None` encodes as `"result": null`.
+    result: Option<&'a R>,
+    id: &'a RequestId,
+}
+
+#[derive(Serialize)]
+struct OutgoingError<'a> {
+    jsonrpc: &'static str,
+    error: &'a JsonRpcError,
+    id: &'a RequestId,
+}
+
+/// Wire result for responses whose domain result is only reattached context.
+#[derive(Serialize)]
+struct EmptyResult {}
+
+/// Wire shape of the session/load result, without the reattached session id.
+#[derive(Serialize)]
+struct LoadSessionResultOut<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    modes: Option<&'a SessionModeState>,
+}
+
+/// Wire shape of the session/prompt result, without the reattached session id.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SessionPromptResultOut<'a> {
+    stop_reason: StopReason,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    usage: Option<&'a Map<String, Value>>,
+    #[serde(rename = "_meta", skip_serializing_if = "Option::is_none")]
+    meta: Option<&'a Map<String, Value>>,
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<String, EncodeError> {
+    serde_json::to_string(value).map_err(|e| EncodeError::UnsupportedMessage(e.to_string()))
+}
+
+fn request<P: Serialize>(
+    id: Option<&RequestId>,
+    method: &str,
+    params: Option<&P>,
+) -> Result<String, EncodeError> {
+    let id = id.ok_or(EncodeError::MissingRequestId)?;
+    to_json(&OutgoingRequest {
+        jsonrpc: "2.0",
+        method,
+        params,
+        id: Some(id),
+    })
+}
+
+fn notification<P: Serialize>(
+    id: Option<&RequestId>,
+    method: &str,
+    params: Option<&P>,
+) -> Result<String, EncodeError> {
+    if id.is_some() {
+        return Err(EncodeError::UnexpectedRequestId);
+    }
+    to_json(&OutgoingRequest {
+        jsonrpc: "2.0",
+        method,
+        params,
+        id: None,
+    })
+}
+
+fn result<R: Serialize>(id: Option<&RequestId>, result: Option<&R>) -> Result<String, EncodeError> {
+    let id = id.ok_or(EncodeError::MissingRequestId)?;
+    to_json(&OutgoingResult {
+        jsonrpc: "2.0",
+        result,
+        id,
+    })
+}
+
+fn error(id: Option<&RequestId>, error: &JsonRpcError) -> Result<String, EncodeError> {
+    let id = id.ok_or(EncodeError::MissingRequestId)?;
+    to_json(&OutgoingError {
+        jsonrpc: "2.0",
+        error,
+        id,
+    })
+}
+
+/// Encode a typed message as one JSON-RPC object. `id` is required for
+/// requests and responses and rejected for notifications.
+pub fn encode(id: Option<&RequestId>, msg: &Message) -> Result<String, EncodeError> {
+    match msg {
+        Message::FromClient(c) => encode_client_message(id, c),
+        Message::FromAgent(a) => encode_agent_message(id, a),
+    }
+}
+
+/// The error response to a request that failed to decode, which has no typed
+/// counterpart since no pending entry records its method.
+pub fn encode_error(id: &RequestId, error: &JsonRpcError) -> Result<String, EncodeError> {
+    self::error(Some(id), error)
+}
+
+fn encode_client_message(
+    id: Option<&RequestId>,
+    msg: &ClientToAgentMessage,
+) -> Result<String, EncodeError> {
+    use ClientToAgentMessage as C;
+
+    let method = msg.method();
+    match msg {
+        // Requests
+        C::Initialize(p) | C::ProxyInitialize(p) => request(id, method, Some(p)),
+        C::Authenticate(p) => request(id, method, Some(p)),
+        C::SessionNew(p) => request(id, method, Some(p)),
+        C::SessionLoad(p) => request(id, method, Some(p)),
+        C::SessionPrompt(p) => request(id, method, Some(p)),
+        C::SessionSetMode(p) => request(id, method, Some(p)),
+        C::ProxySuccessorRequest(p) => request(id, method, Some(p)),
+        C::ExtRequest { params, .. } => request(id, method, params.as_ref()),
+
+        // Notifications
+        C::SessionCancel(p) => notification(id, method, Some(p)),
+        C::ProxySuccessorNotification(p) => notification(id, method, Some(p)),
+        C::ExtNotification { params, .. } => notification(id, method, params.as_ref()),
+
+        // Responses
+        C::FsReadTextFileResult(r) => result(id, Some(r)),
+        C::FsWriteTextFileResult(r) => result(id, Some(r)),
+        C::SessionRequestPermissionResult(r) => result(id, Some(r)),
+        C::TerminalCreateResult(r) => result(id, Some(r)),
+        C::TerminalOutputResult(r) => result(id, Some(r)),
+        C::TerminalWaitForExitResult(r) => result(id, Some(r)),
+        C::TerminalKillResult(r) => result(id, Some(r)),
+        C::TerminalReleaseResult(r) => result(id, Some(r)),
+        C::ExtResponse { result: r, .. } | C::ProxySuccessorResponse { result: r, .. } => {
+            result(id, r.as_ref())
+        }
+
+        // Errors
+        C::FsReadTextFileError(_, e)
+        | C::FsWriteTextFileError(_, e)
+        | C::SessionRequestPermissionError(_, e)
+        | C::TerminalCreateError(_, e)
+        | C::TerminalOutputError(_, e)
+        | C::TerminalWaitForExitError(_, e)
+        | C::TerminalKillError(_, e)
+        | C::TerminalReleaseError(_, e)
+        | C::ExtError { error: e, .. }
+        | C::ProxySuccessorError { error: e, .. } => error(id, e),
+    }
+}
+
+fn encode_agent_message(
+    id: Option<&RequestId>,
+    msg: &AgentToClientMessage,
+) -> Result<String, EncodeError> {
+    use AgentToClientMessage as A;
+
+    let method = msg.method();
+    match msg {
+        // Responses
+        A::InitializeResult(r) | A::ProxyInitializeResult(r) => result(id, Some(r)),
+        A::AuthenticateResult(r) => result(id, Some(r)),
+        A::SessionNewResult(r) => result(id, Some(r)),
+        A::SessionLoadResult(r) => result(
+            id,
+            Some(&LoadSessionResultOut {
+                modes: r.modes.as_ref(),
+            }),
+        ),
+        A::SessionPromptResult(r) => result(
+            id,
+            Some(&SessionPromptResultOut {
+                stop_reason: r.stop_reason,
+                usage: r.usage.as_ref(),
+                meta: r.meta.as_ref(),
+            }),
+        ),
+        A::SessionSetModeResult(_) => result(id, Some(&EmptyResult {})),
+        A::ExtResponse { result: r, .. } | A::ProxySuccessorResponse { result: r, .. } => {
+            result(id, r.as_ref())
+        }
+
+        // Errors
+        A::InitializeError(e)
+        | A::ProxyInitializeError(e)
+        | A::AuthenticateError(e)
+        | A::SessionNewError(e)
+        | A::SessionLoadError(_, e)
+        | A::SessionPromptError(_, e)
+        | A::SessionSetModeError(_, e)
+        | A::ExtError { error: e, .. }
+        | A::ProxySuccessorError { error: e, .. } => error(id, e),
+
+        // Notifications
+        A::SessionUpdate(n) => notification(id, method, Some(n)),
+        A::ProxySuccessorNotification(p) => notification(id, method, Some(p)),
+        A::ExtNotification { params, .. } => notification(id, method, params.as_ref()),
+
+        // Requests
+        A::FsReadTextFileRequest(p) => request(id, method, Some(p)),
+        A::FsWriteTextFileRequest(p) => request(id, method, Some(p)),
+        A::SessionRequestPermissionRequest(p) => request(id, method, Some(p)),
+        A::TerminalCreateRequest(p) => request(id, method, Some(p)),
+        A::TerminalOutputRequest(p) => request(id, method, Some(p)),
+        A::TerminalWaitForExitRequest(p) => request(id, method, Some(p)),
+        A::TerminalKillRequest(p) => request(id, method, Some(p)),
+        A::TerminalReleaseRequest(p) => request(id, method, Some(p)),
+        A::ProxySuccessorRequest(p) => request(id, method, Some(p)),
+        A::ExtRequest { params, .. } => request(id, method, params.as_ref()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_fresh(direction: Direction, json: &str) -> Result<Decoded, DecodeError> {
+        decode(direction, &mut CodecState::default(), json)
+    }
+
+    #[test]
+    fn decodes_session_update_chunk() {
+        let json = r#"{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s1","update":{"sessionUpdate":"agent_message_chunk","content":{"type":"text","text":"hi"}}}}"#;
+        let decoded = decode_fresh(Direction::FromAgent, json).unwrap();
+        assert_eq!(decoded.id, None);
+        match decoded.message {
+            Message::FromAgent(AgentToClientMessage::SessionUpdate(n)) => match n.update {
+                SessionUpdate::AgentMessageChunk(c) => {
+                    assert!(matches!(c.content, ContentBlock::Text(ref t) if t.text == "hi"))
+                }
+                other => panic!("unexpected update {other:?}"),
+            },
+            other => panic!("unexpected message {other:?}"),
+        }
+    }
+
+    #[test]
+    fn preserves_unknown_session_update_as_ext() {
+        let json = r#"{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s1","update":{"sessionUpdate":"future_thing","x":1}}}"#;
+        match decode_fresh(Direction::FromAgent, json).unwrap().message {
+            Message::FromAgent(AgentToClientMessage::SessionUpdate(n)) => {
+                assert!(
+                    matches!(n.update, SessionUpdate::Ext { ref tag, .. } if tag == "future_thing")
+                )
+            }
+            other => panic!("unexpected message {other:?}"),
+        }
+    }
+
+    #[test]
+    fn rejects_bad_envelopes() {
+        let client = Direction::FromClient;
+        assert!(matches!(
+            decode_fresh(client, "{"),
+            Err(DecodeError::InvalidJson(_))
+        ));
+        assert!(matches!(
+            decode_fresh(client, r#"{"jsonrpc":"1.0","method":"initialize","id":1}"#),
+            Err(DecodeError::InvalidJsonRpc(_))
+        ));
+        assert!(matches!(
+            decode_fresh(
+                client,
+                r#"{"jsonrpc":"2.0","method":"session/new","params":{},"id":1}"#
+            ),
+            Err(DecodeError::InvalidParams { .. })
+        ));
+        assert_eq!(
+            decode_fresh(
+                Direction::FromAgent,
+                r#"{"jsonrpc":"2.0","result":{},"id":7}"#
+            ),
+            Err(DecodeError::UnknownRequestId(RequestId::Number(7)))
+        );
+    }
+
+    #[test]
+    fn ext_requests_may_omit_params() {
+        for direction in [Direction::FromClient, Direction::FromAgent] {
+            let json = r#"{"jsonrpc":"2.0","method":"_acme/ping","id":1}"#;
+            let decoded = decode_fresh(direction, json).unwrap();
+            let (Message::FromClient(ClientToAgentMessage::ExtRequest { params, .. })
+            | Message::FromAgent(AgentToClientMessage::ExtRequest { params, .. })) =
+                &decoded.message
+            else {
+                panic!("expected an extension request, got {:?}", decoded.message);
+            };
+            assert_eq!(*params, None);
+            assert_eq!(encode(decoded.id.as_ref(), &decoded.message).unwrap(), json);
+        }
+        // Known methods still need theirs.
+        assert!(matches!(
+            decode_fresh(
+                Direction::FromClient,
+                r#"{"jsonrpc":"2.0","method":"session/new","id":1}"#
+            ),
+            Err(DecodeError::InvalidParams { .. })
+        ));
+    }
+
+    #[test]
+    fn correlates_prompt_result_and_reattaches_session_id() {
+        let mut state = CodecState::default();
+        let prompt = r#"{"jsonrpc":"2.0","method":"session/prompt","params":{"sessionId":"s1","prompt":[]},"id":2}"#;
+        decode(Direction::FromClient, &mut state, prompt).unwrap();
+        assert_eq!(state.pending_client_requests.len(), 1);
+
+        let response = r#"{"jsonrpc":"2.0","result":{"stopReason":"end_turn"},"id":2}"#;
+        let decoded = decode(Direction::FromAgent, &mut state, response).unwrap();
+        assert!(state.pending_client_requests.is_empty());
+        match decoded.message {
+            Message::FromAgent(AgentToClientMessage::SessionPromptResult(r)) => {
+                assert_eq!(r.session_id, SessionId("s1".into()));
+                assert_eq!(r.stop_reason, StopReason::EndTurn);
+            }
+            other => panic!("unexpected message {other:?}"),
+        }
+    }
+
+    #[test]
+    fn rejects_duplicate_ids_and_wrong_direction() {
+        let mut state = CodecState::default();
+        let new = r#"{"jsonrpc":"2.0","method":"session/new","params":{"cwd":"/","mcpServers":[]},"id":1}"#;
+        decode(Direction::FromClient, &mut state, new).unwrap();
+        assert_eq!(
+            decode(Direction::FromClient, &mut state, new),
+            Err(DecodeError::DuplicateRequestId(RequestId::Number(1)))
+        );
+        assert!(matches!(
+            decode(Direction::FromAgent, &mut state, new),
+            Err(DecodeError::DirectionMismatch {
+                expected: Direction::FromClient,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn maps_error_response_to_pending_request() {
+        let mut state = CodecState::default();
+        let read = r#"{"jsonrpc":"2.0","method":"fs/read_text_file","params":{"sessionId":"s1","path":"/a"},"id":"r1"}"#;
+        decode(Direction::FromAgent, &mut state, read).unwrap();
+
+        let error = r#"{"jsonrpc":"2.0","error":{"code":-32002,"message":"not found"},"id":"r1"}"#;
+        match decode(Direction::FromClient, &mut state, error)
+            .unwrap()
+            .message
+        {
+            Message::FromClient(ClientToAgentMessage::FsReadTextFileError(req, e)) => {
+                assert_eq!(req.path, "/a");
+                assert_eq!(e.code, -32002);
+            }
+            other => panic!("unexpected message {other:?}"),
+        }
+    }
+
+    #[test]
+    fn encode_round_trips_the_sample_session() {
+        // Re-decoding each encoding with fresh threaded state yields the same
+        // typed message, so nothing the codec reattached leaks onto the wire.
+        let mut state = CodecState::default();
+        for (decoded, &(direction, line)) in crate::samples::encode_mix()
+            .iter()
+            .zip(&crate::samples::TOOL_SESSION)
+        {
+            let json = encode(decoded.id.as_ref(), &decoded.message).unwrap();
+            let again = decode(direction, &mut state, &json)
+                .unwrap_or_else(|e| panic!("{e}\n{line}\n{json}"));
+            assert_eq!(&again, decoded, "{line}");
+        }
+    }
+
+    #[test]
+    fn encodes_results_without_request_context() {
+        let mut state = CodecState::default();
+        let prompt = r#"{"jsonrpc":"2.0","method":"session/prompt","params":{"sessionId":"s1","prompt":[]},"id":2}"#;
+        decode(Direction::FromClient, &mut state, prompt).unwrap();
+        let response = r#"{"jsonrpc":"2.0","result":{"stopReason":"end_turn"},"id":2}"#;
+        let decoded = decode(Direction::FromAgent, &mut state, response).unwrap();
+        assert_eq!(
+            encode(decoded.id.as_ref(), &decoded.message).unwrap(),
+            response
+        );
+
+        assert_eq!(
+            encode(None, &decoded.message),
+            Err(EncodeError::MissingRequestId)
+        );
+    }
+
+    #[test]
+    fn infers_direction_from_method() {
+        let update = r#"{"jsonrpc":"2.0","method":"session/update","params":{}}"#;
+        let result = r#"{"jsonrpc":"2.0","result":{},"id":1}"#;
+        let prompt = r#"{"jsonrpc":"2.0","method":"session/prompt","params":{},"id":1}"#;
+        assert_eq!(infer_direction(update), Direction::FromAgent);
+        assert_eq!(infer_direction(result), Direction::FromAgent);
+        assert_eq!(infer_direction(prompt), Direction::FromClient);
+    }
+}
@@ -0,0 +1,254 @@
+//! Round-trip fidelity: decode a message into the typed model, encode it
+//! back, and compare the two JSON documents semantically (key order is
+//! ignored). Differences are located by JSON pointer and classified so that
+//! fields the model drops, renames or re-represents stand out.
+
+use crate::codec::{self, CodecState, DecodeError, Direction, EncodeError};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// One way the re-encoded message differs from the original.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Difference {
+    /// Present in the original, missing after the round trip.
+    Dropped { pointer: String, original: Value },
+    /// Missing from the original, present after the round trip.
+    Added { pointer: String, encoded: Value },
+    /// The same value moved to a sibling key.
+    Renamed { from: String, to: String },
+    /// Numerically equal but written differently, e.g. `1.0` vs `1`.
+    NumberRepresentation {
+        pointer: String,
+        original: Value,
+        encoded: Value,
+    },
+    Changed {
+        pointer: String,
+        original: Value,
+        encoded: Value,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RoundTripError {
+    Decode(DecodeError),
+    Encode(EncodeError),
+    /// The encoder produced something that is not JSON (a bug).
+    InvalidEncoding(String),
+}
+
+impl fmt::Display for RoundTripError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RoundTripError::Decode(e) => write!(f, "decode: {e}"),
+            RoundTripError::Encode(e) => write!(f, "encode: {e}"),
+            RoundTripError::InvalidEncoding(e) => write!(f, "encoded invalid JSON: {e}"),
+        }
+    }
+}
+
+/// The decoded method and its round-trip differences (empty when faithful).
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoundTrip {
+    pub method: String,
+    pub differences: Vec<Difference>,
+}
+
+/// Decode `json` with threaded `state`, re-encode it and compare.
+pub fn round_trip(
+    direction: Direction,
+    state: &mut CodecState,
+    json: &str,
+) -> Result<RoundTrip, RoundTripError> {
+    let decoded = codec::decode(direction, state, json).map_err(RoundTripError::Decode)?;
+    let encoded =
+        codec::encode(decoded.id.as_ref(), &decoded.message).map_err(RoundTripError::Encode)?;
+
+    // The original already parsed during decode.
+    let original: Value = serde_json::from_str(json)
+        .map_err(|e| RoundTripError::Decode(DecodeError::InvalidJson(e.to_string())))?;
+    let encoded: Value = serde_json::from_str(&encoded)
+        .map_err(|e| RoundTripError::InvalidEncoding(e.to_string()))?;
+
+    Ok(RoundTrip {
+        method: decoded.message.method().to_owned(),
+        differences: compare(&original, &encoded),
+    })
+}
+
+// -------------
+// Comparison
+// -------------
+
+/// Semantic differences between `original` and `encoded`, in document order.
+pub fn compare(original: &Value, encoded: &Value) -> Vec<Difference> {
+    let mut differences = Vec::new();
+    compare_at("", original, encoded, &mut differences);
+    differences
+}
+
+fn compare_at(pointer: &str, original: &Value, encoded: &Value, out: &mut Vec<Difference>) {
+    match (original, encoded) {
+        (Value::Object(o), Value::Object(e)) => compare_objects(pointer, o, e, out),
+        (Value::Array(o), Value::Array(e)) => {
+            for (i, (o, e)) in o.iter().zip(e).enumerate() {
+                compare_at(&format!("{pointer}/{i}"), o, e, out);
+            }
+            for (i, o) in o.iter().enumerate().skip(e.len()) {
+                out.push(Difference::Dropped {
+                    pointer: format!("{pointer}/{i}"),
+                    original: o.clone(),
+                });
+            }
+            for (i, e) in e.iter().enumerate().skip(o.len()) {
+                out.push(Difference::Added {
+                    pointer: format!("{pointer}/{i}"),
+                    encoded: e.clone(),
+                });
+            }
+        }
+        (Value::Number(o), Value::Number(e)) if o != e && o.as_f64() == e.as_f64() => {
+            out.push(Difference::NumberRepresentation {
+                pointer: pointer.to_owned(),
+                original: original.clone(),
+                encoded: encoded.clone(),
+            })
+        }
+        _ if original != encoded => out.push(Difference::Changed {
+            pointer: pointer.to_owned(),
+            original: original.clone(),
+            encoded: encoded.clone(),
+        }),
+        _ => {}
+    }
+}
+
+/// A key missing on one side whose value turns up under a new key on the
+/// other is a rename; anything left over was dropped or added.
+fn compare_objects(
+    pointer: &str,
+    original: &Map<String, Value>,
+    encoded: &Map<String, Value>,
+    out: &mut Vec<Difference>,
+) {
+    let mut added: Vec<(&String, &Value)> = encoded
+        .iter()
+        .filter(|(k, _)| !original.contains_key(*k))
+        .collect();
+
+    for (key, value) in original {
+        let here = child(pointer, key);
+        if let Some(e) = encoded.get(key) {
+            compare_at(&here, value, e, out);
+        } else if let Some(i) = added.iter().position(|(_, e)| *e == value) {
+            let (to, _) = added.remove(i);
+            out.push(Difference::Renamed {
+                from: here,
+                to: child(pointer, to),
+            });
+        } else {
+            out.push(Difference::Dropped {
+                pointer: here,
+                original: value.clone(),
+            });
+        }
+    }
+    for (key, value) in added {
+        out.push(Difference::Added {
+            pointer: child(pointer, key),
+            encoded: value.clone(),
+        });
+    }
+}
+
+/// `pointer` extended by one object key, escaped per RFC 6901.
+fn child(pointer: &str, key: &str) -> String {
+    format!("{pointer}/{}", key.replace('~', "~0").replace('/', "~1"))
+}
+
+// -------------
+// Corpus
+// -------------
+
+/// The repository's message corpus: `cli/benchmarks/scenarios/*.json` and
+/// `sentinel/tests/traces/*.jsonl` under `root`, sorted by path.
+pub fn corpus(root: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for (dir, extension) in [
+        ("cli/benchmarks/scenarios", "json"),
+        ("sentinel/tests/traces", "jsonl"),
+    ] {
+        for entry in std::fs::read_dir(root.join(dir))? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|e| e == extension) {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// The nearest ancestor of `start` holding the corpus directories.
+pub fn find_root(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .find(|dir| dir.join("sentinel/tests/traces").is_dir())
+        .map(Path::to_path_buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn classifies_differences_by_pointer() {
+        let original = json!({
+            "id": 1,
+            "params": {"a/b": 1.0, "old": "x", "gone": null, "list": [1, 2]},
+        });
+        let encoded = json!({
+            "params": {"list": [1], "new": "x", "a/b": 1, "extra": true},
+            "id": 1,
+        });
+        assert_eq!(
+            compare(&original, &encoded),
+            vec![
+                Difference::NumberRepresentation {
+                    pointer: "/params/a~1b".into(),
+                    original: json!(1.0),
+                    encoded: json!(1),
+                },
+                Difference::Dropped {
+                    pointer: "/params/gone".into(),
+                    original: Value::Null,
+                },
+                Difference::Dropped {
+                    pointer: "/params/list/1".into(),
+                    original: json!(2),
+                },
+                Difference::Renamed {
+                    from: "/params/old".into(),
+                    to: "/params/new".into(),
+                },
+                Difference::Added {
+                    pointer: "/params/extra".into(),
+                    encoded: json!(true),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn round_trip_ignores_key_order() {
+        let json = r#"{"id":2,"params":{"mcpServers":[],"cwd":"/tmp"},"method":"session/new","jsonrpc":"2.0"}"#;
+        let trip = round_trip(Direction::FromClient, &mut CodecState::default(), json).unwrap();
+        assert_eq!(trip.method, "session/new");
+        assert_eq!(trip.differences, vec![]);
+    }
+}
@@ -0,0 +1,1121 @@
+//! JSON-RPC connections over a [`Transport`]. Mirrors
+//! `runtime/src/Acp.Connection.fs`: a [`ClientConnection`] or
+//! [`AgentConnection`] numbers its outgoing requests and correlates their
+//! responses, answers incoming requests through a [`Client`] or [`Agent`]
+//! handler, and hands incoming notifications to a [`Notifications`] stream.
+//!
+//! A connection only makes progress while `run` is polled, so requests are
+//! made alongside it:
+//!
+//! ```ignore
+//! let (client, _updates) = ClientConnection::new(transport);
+//! tokio::join!(client.run(&handler), async {
+//!     let init = client.initialize(params).await;
+//!     client.close().await
+//! });
+//! ```
+
+use crate::codec::{self, CodecState, DecodeError, Direction, EncodeError};
+use crate::domain::*;
+use crate::transport::{frame_finding, lock, Transport};
+use futures_util::stream::{FuturesUnordered, Stream, StreamExt};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Mutex;
+use std::task::{Context, Poll};
+use tokio::sync::{mpsc, oneshot};
+
+// -------------
+// Errors
+// -------------
+
+/// Why a request or notification failed.
+#[derive(Debug)]
+pub enum ConnectionError {
+    /// The transport closed, or `run` returned, before a response arrived.
+    TransportClosed,
+    Transport(io::Error),
+    Encode(EncodeError),
+    Decode(DecodeError),
+    /// The peer answered with a JSON-RPC error.
+    Rpc(JsonRpcError),
+    /// The peer answered with a result for a different method.
+    UnexpectedResponse(Box<Message>),
+}
+
+impl fmt::Display for ConnectionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConnectionError::TransportClosed => f.write_str("transport closed"),
+            ConnectionError::Transport(e) => e.fmt(f),
+            ConnectionError::Encode(e) => e.fmt(f),
+            ConnectionError::Decode(e) => e.fmt(f),
+            ConnectionError::Rpc(e) => write!(f, "{} ({})", e.message, e.code),
+            ConnectionError::UnexpectedResponse(m) => {
+                write!(f, "unexpected '{}' response", m.method())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConnectionError {}
+
+impl From<io::Error> for ConnectionError {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::BrokenPipe => ConnectionError::TransportClosed,
+            _ => ConnectionError::Transport(e),
+        }
+    }
+}
+
+/// `-32601`: what handlers answer for methods they do not implement.
+pub fn method_not_found(method: &str) -> JsonRpcError {
+    JsonRpcError {
+        code: -32601,
+        message: format!("method not found: {method}"),
+        data: None,
+    }
+}
+
+/// `-32603`: a handler failed for reasons of its own.
+pub fn internal_error(message: impl fmt::Display) -> JsonRpcError {
+    JsonRpcError {
+        code: -32603,
+        message: message.to_string(),
+        data: None,
+    }
+}
+
+// -------------
+// Handlers
+// -------------
+
+/// Answers the requests a client sends. Notifications (`session/cancel`)
+/// arrive on the connection's [`Notifications`] instead.
+pub trait Agent: Sync {
+    fn initialize(
+        &self,
+        params: InitializeParams,
+    ) -> impl Future<Output = Result<InitializeResult, JsonRpcError>> + Send;
+
+    fn authenticate(
+        &self,
+        _params: AuthenticateParams,
+    ) -> impl Future<Output = Result<AuthenticateResult, JsonRpcError>> + Send {
+        async { Err(method_not_found("authenticate")) }
+    }
+
+    fn new_session(
+        &self,
+        params: NewSessionParams,
+    ) -> impl Future<Output = Result<NewSessionResult, JsonRpcError>> + Send;
+
+    fn load_session(
+        &self,
+        _params: LoadSessionParams,
+    ) -> impl Future<Output = Result<LoadSessionResult, JsonRpcError>> + Send {
+        async { Err(method_not_found("session/load")) }
+    }
+
+    fn prompt(
+        &self,
+        params: SessionPromptParams,
+    ) -> impl Future<Output = Result<SessionPromptResult, JsonRpcError>> + Send;
+
+    fn set_mode(
+        &self,
+        _params: SetSessionModeParams,
+    ) -> impl Future<Output = Result<SetSessionModeResult, JsonRpcError>> + Send {
+        async { Err(method_not_found("session/set_mode")) }
+    }
+
+    /// Extension (`_`-prefixed) methods.
+    fn ext_method(
+        &self,
+        method: &str,
+        _params: Option<Value>,
+    ) -> impl Future<Output = Result<Option<Value>, JsonRpcError>> + Send {
+        let error = method_not_found(method);
+        async { Err(error) }
+    }
+}
+
+/// Answers the requests an agent sends. Every method defaults to
+/// method-not-found, so a client implements what it advertises in
+/// `clientCapabilities`. Notifications (`session/update`) arrive on the
+/// connection's [`Notifications`] instead.
+pub trait Client: Sync {
+    fn request_permission(
+        &self,
+        _params: RequestPermissionParams,
+    ) -> impl Future<Output = Result<RequestPermissionResult, JsonRpcError>> + Send {
+        async { Err(method_not_found("session/request_permission")) }
+    }
+
+    fn read_text_file(
+        &self,
+        _params: ReadTextFileParams,
+    ) -> impl Future<Output = Result<ReadTextFileResult, JsonRpcError>> + Send {
+        async { Err(method_not_found("fs/read_text_file")) }
+    }
+
+    fn write_text_file(
+        &self,
+        _params: WriteTextFileParams,
+    ) -> impl Future<Output = Result<WriteTextFileResult, JsonRpcError>> + Send {
+        async { Err(method_not_found("fs/write_text_file")) }
+    }
+
+    fn create_terminal(
+        &self,
+        _params: CreateTerminalParams,
+    ) -> impl Future<Output = Result<CreateTerminalResult, JsonRpcError>> + Send {
+        async { Err(method_not_found("terminal/create")) }
+    }
+
+    fn terminal_output(
+        &self,
+        _params: TerminalOutputParams,
+    ) -> impl Future<Output = Result<TerminalOutputResult, JsonRpcError>> + Send {
+        async { Err(method_not_found("terminal/output")) }
+    }
+
+    fn wait_for_terminal_exit(
+        &self,
+        _params: WaitForTerminalExitParams,
+    ) -> impl Future<Output = Result<TerminalExitStatus, JsonRpcError>> + Send {
+        async { Err(method_not_found("terminal/wait_for_exit")) }
+    }
+
+    fn kill_terminal(
+        &self,
+        _params: KillTerminalCommandParams,
+    ) -> impl Future<Output = Result<KillTerminalCommandResult, JsonRpcError>> + Send {
+        async { Err(method_not_found("terminal/kill")) }
+    }
+
+    fn release_terminal(
+        &self,
+        _params: ReleaseTerminalParams,
+    ) -> impl Future<Output = Result<ReleaseTerminalResult, JsonRpcError>> + Send {
+        async { Err(method_not_found("terminal/release")) }
+    }
+
+    /// Extension (`_`-prefixed) methods.
+    fn ext_method(
+        &self,
+        method: &str,
+        _params: Option<Value>,
+    ) -> impl Future<Output = Result<Option<Value>, JsonRpcError>> + Send {
+        let error = method_not_found(method);
+        async { Err(error) }
+    }
+}
+
+/// Run `agent`'s handler for a client request. Errors are turned into the
+/// matching error response by the caller.
+async fn answer_client(agent: &impl Agent, request: Message) -> Result<Message, JsonRpcError> {
+    use AgentToClientMessage as A;
+    use ClientToAgentMessage as C;
+
+    let Message::FromClient(request) = request else {
+        unreachable!("agents only receive client messages")
+    };
+    let response = match request {
+        C::Initialize(p) => A::InitializeResult(agent.initialize(p).await?),
+        C::Authenticate(p) => A::AuthenticateResult(agent.authenticate(p).await?),
+        C::SessionNew(p) => A::SessionNewResult(agent.new_session(p).await?),
+        C::SessionLoad(p) => A::SessionLoadResult(agent.load_session(p).await?),
+        C::SessionPrompt(p) => A::SessionPromptResult(agent.prompt(p).await?),
+        C::SessionSetMode(p) => A::SessionSetModeResult(agent.set_mode(p).await?),
+        C::ExtRequest { method, params } => {
+            let result = agent.ext_method(&method, params).await?;
+            A::ExtResponse { method, result }
+        }
+        other => return Err(method_not_found(other.method())),
+    };
+    Ok(Message::FromAgent(response))
+}
+
+/// See [`answer_client`].
+async fn answer_agent(client: &impl Client, request: Message) -> Result<Message, JsonRpcError> {
+    use AgentToClientMessage as A;
+    use ClientToAgentMessage as C;
+
+    let Message::FromAgent(request) = request else {
+        unreachable!("clients only receive agent messages")
+    };
+    let response = match request {
+        A::SessionRequestPermissionRequest(p) => {
+            C::SessionRequestPermissionResult(client.request_permission(p).await?)
+        }
+        A::FsReadTextFileRequest(p) => C::FsReadTextFileResult(client.read_text_file(p).await?),
+        A::FsWriteTextFileRequest(p) => C::FsWriteTextFileResult(client.write_text_file(p).await?),
+        A::TerminalCreateRequest(p) => C::TerminalCreateResult(client.create_terminal(p).await?),
+        A::TerminalOutputRequest(p) => C::TerminalOutputResult(client.terminal_output(p).await?),
+        A::TerminalWaitForExitRequest(p) => {
+            C::TerminalWaitForExitResult(client.wait_for_terminal_exit(p).await?)
+        }
+        A::TerminalKillRequest(p) => C::TerminalKillResult(client.kill_terminal(p).await?),
+        A::TerminalReleaseRequest(p) => C::TerminalReleaseResult(client.release_terminal(p).await?),
+        A::ExtRequest { method, params } => {
+            let result = client.ext_method(&method, params).await?;
+            C::ExtResponse { method, result }
+        }
+        other => return Err(method_not_found(other.method())),
+    };
+    Ok(Message::FromClient(response))
+}
+
+// -------------
+// Message classes
+// -------------
+
+/// Whether `message` opens a request, as opposed to answering one. Only
+/// called for messages that carry an id, so notifications never reach it.
+fn is_request(message: &Message) -> bool {
+    use AgentToClientMessage as A;
+    use ClientToAgentMessage as C;
+
+    match message {
+        Message::FromClient(c) => matches!(
+            c,
+            C::Initialize(_)
+                | C::ProxyInitialize(_)
+                | C::Authenticate(_)
+                | C::SessionNew(_)
+                | C::SessionLoad(_)
+                | C::SessionPrompt(_)
+                | C::SessionSetMode(_)
+                | C::ProxySuccessorRequest(_)
+                | C::ExtRequest { .. }
+        ),
+        Message::FromAgent(a) => matches!(
+            a,
+            A::FsReadTextFileRequest(_)
+                | A::FsWriteTextFileRequest(_)
+                | A::SessionRequestPermissionRequest(_)
+                | A::TerminalCreateRequest(_)
+                | A::TerminalOutputRequest(_)
+                | A::TerminalWaitForExitRequest(_)
+                | A::TerminalKillRequest(_)
+                | A::TerminalReleaseRequest(_)
+                | A::ProxySuccessorRequest(_)
+                | A::ExtRequest { .. }
+        ),
+    }
+}
+
+/// The error carried by an error response, or [`ConnectionError::UnexpectedResponse`]
+/// for a result of the wrong kind.
+fn rejected(response: Message) -> ConnectionError {
+    use AgentToClientMessage as A;
+    use ClientToAgentMessage as C;
+
+    match response {
+        Message::FromAgent(
+            A::InitializeError(e)
+            | A::ProxyInitializeError(e)
+            | A::AuthenticateError(e)
+            | A::SessionNewError(e)
+            | A::SessionLoadError(_, e)
+            | A::SessionPromptError(_, e)
+            | A::SessionSetModeError(_, e)
+            | A::ExtError { error: e, .. }
+            | A::ProxySuccessorError { error: e, .. },
+        )
+        | Message::FromClient(
+            C::FsReadTextFileError(_, e)
+            | C::FsWriteTextFileError(_, e)
+            | C::SessionRequestPermissionError(_, e)
+            | C::TerminalCreateError(_, e)
+            | C::TerminalOutputError(_, e)
+            | C::TerminalWaitForExitError(_, e)
+            | C::TerminalKillError(_, e)
+            | C::TerminalReleaseError(_, e)
+            | C::ExtError { error: e, .. }
+            | C::ProxySuccessorError { error: e, .. },
+        ) => ConnectionError::Rpc(e),
+        other => ConnectionError::UnexpectedResponse(Box::new(other)),
+    }
+}
+
+// -------------
+// Notifications
+// -------------
+
+/// Notifications received by a connection, in arrival order. Ends once the
+/// connection's `run` returns.
+pub struct Notifications {
+    receiver: mpsc::UnboundedReceiver<Message>,
+}
+
+impl Notifications {
+    pub async fn recv(&mut self) -> Option<Message> {
+        self.receiver.recv().await
+    }
+}
+
+impl Stream for Notifications {
+    type Item = Message;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Message>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+// -------------
+// Connection core
+// -------------
+
+type Waiters = HashMap<RequestId, oneshot::Sender<Result<Message, ConnectionError>>>;
+
+/// An incoming request for a handler, or one that failed to decode and is
+/// answered with invalid params.
+enum Inbound {
+    Request(RequestId, Box<Message>),
+    Malformed(RequestId, DecodeError),
+}
+
+/// What both connection roles share: the transport, the codec state for both
+/// directions, and the requests awaiting a response.
+struct Core<T> {
+    transport: T,
+    outbound: Direction,
+    inbound: Direction,
+    state: Mutex<CodecState>,
+    next_id: AtomicI64,
+    /// `None` once `run` has returned: new requests fail straight away.
+    waiting: Mutex<Option<Waiters>>,
+    notifications: Mutex<Option<mpsc::UnboundedSender<Message>>>,
+}
+
+impl<T: Transport> Core<T> {
+    fn new(transport: T, outbound: Direction) -> (Self, Notifications) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let inbound = match outbound {
+            Direction::FromClient => Direction::FromAgent,
+            Direction::FromAgent => Direction::FromClient,
+        };
+        let core = Core {
+            transport,
+            outbound,
+            inbound,
+            state: Mutex::new(CodecState::default()),
+            next_id: AtomicI64::new(1),
+            waiting: Mutex::new(Some(HashMap::new())),
+            notifications: Mutex::new(Some(sender)),
+        };
+        (core, Notifications { receiver })
+    }
+
+    /// Encode `message` and step the codec state with it, as the peer will.
+    fn encode(&self, id: Option<&RequestId>, message: &Message) -> Result<String, ConnectionError> {
+        let json = codec::encode(id, message).map_err(ConnectionError::Encode)?;
+        codec::decode(self.outbound, &mut lock(&self.state), &json)
+            .map_err(ConnectionError::Decode)?;
+        Ok(json)
+    }
+
+    /// Send a request and wait for its response, which `run` delivers.
+    async fn request(&self, message: Message) -> Result<Message, ConnectionError> {
+        let id = RequestId::Number(self.next_id.fetch_add(1, Ordering::Relaxed));
+        // Register the waiter first: once `run` has returned, fail before the
+        // codec state records a request that will never be sent.
+        let (sender, receiver) = oneshot::channel();
+        lock(&self.waiting)
+            .as_mut()
+            .ok_or(ConnectionError::TransportClosed)?
+            .insert(id.clone(), sender);
+
+        let sent = match self.encode(Some(&id), &message) {
+            Ok(json) => self.transport.send(json).await.map_err(|e| {
+                self.forget(&id);
+                e.into()
+            }),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = sent {
+            if let Some(waiting) = lock(&self.waiting).as_mut() {
+                waiting.remove(&id);
+            }
+            return Err(e);
+        }
+        receiver
+            .await
+            .map_err(|_| ConnectionError::TransportClosed)?
+    }
+
+    /// Undo [`Core::encode`] for a request the peer never received.
+    fn forget(&self, id: &RequestId) {
+        let mut state = lock(&self.state);
+        match self.outbound {
+            Direction::FromClient => {
+                state.pending_client_requests.remove(id);
+            }
+            Direction::FromAgent => {
+                state.pending_agent_requests.remove(id);
+            }
+        }
+    }
+
+    async fn notify(&self, message: Message) -> Result<(), ConnectionError> {
+        let json = self.encode(None, &message)?;
+        Ok(self.transport.send(json).await?)
+    }
+
+    /// Send the handler's answer to request `id`, or the error response.
+    async fn respond(
+        &self,
+        id: RequestId,
+        answer: Result<Message, JsonRpcError>,
+    ) -> Result<(), ConnectionError> {
+        let response = match answer {
+            Ok(response) => response,
+            Err(error) => codec::error_reply(self.outbound, &lock(&self.state), &id, error)
+                .map_err(ConnectionError::Decode)?,
+        };
+        let json = self.encode(Some(&id), &response)?;
+        Ok(self.transport.send(json).await?)
+    }
+
+    /// Answer a request that failed to decode with invalid params (-32602).
+    /// Nothing was pending for it, so the codec state is left alone.
+    async fn reject(&self, id: RequestId, error: DecodeError) -> Result<(), ConnectionError> {
+        let error = JsonRpcError {
+            code: -32602,
+            message: error.to_string(),
+            data: None,
+        };
+        let json = codec::encode_error(&id, &error).map_err(ConnectionError::Encode)?;
+        Ok(self.transport.send(json).await?)
+    }
+
+    /// Decode an incoming message and deliver responses and notifications.
+    /// Returns requests, which need a handler or, if malformed, an error
+    /// reply. A response that fails to decode fails its waiter, as
+    /// `DecodeFailed` does in the F# connection; anything else undecodable
+    /// is dropped.
+    fn route(&self, json: &str) -> Option<Inbound> {
+        // Decoded apart from the match, so the state lock is released before
+        // `forget` takes it again.
+        let decoded = codec::decode(self.inbound, &mut lock(&self.state), json);
+        let decoded = match decoded {
+            Ok(decoded) => decoded,
+            Err(e) => {
+                let id = codec::peek_id(json)?;
+                return match e {
+                    DecodeError::InvalidParams { .. } | DecodeError::DirectionMismatch { .. } => {
+                        Some(Inbound::Malformed(id, e))
+                    }
+                    DecodeError::InvalidResult { .. } | DecodeError::InvalidError(_) => {
+                        let waiter = lock(&self.waiting).as_mut().and_then(|w| w.remove(&id));
+                        if let Some(waiter) = waiter {
+                            self.forget(&id);
+                            let _ = waiter.send(Err(ConnectionError::Decode(e)));
+                        }
+                        None
+                    }
+                    _ => None,
+                };
+            }
+        };
+        match decoded.id {
+            None => {
+                if let Some(sender) = lock(&self.notifications).as_ref() {
+                    // Nobody listening is fine.
+                    let _ = sender.send(decoded.message);
+                }
+                None
+            }
+            Some(id) if is_request(&decoded.message) => {
+                Some(Inbound::Request(id, Box::new(decoded.message)))
+            }
+            Some(id) => {
+                let waiter = lock(&self.waiting).as_mut().and_then(|w| w.remove(&id));
+                if let Some(waiter) = waiter {
+                    // The requester may have given up waiting.
+                    let _ = waiter.send(Ok(decoded.message));
+                }
+                None
+            }
+        }
+    }
+
+    /// Receive until the transport closes, answering requests with `answer`.
+    /// Handlers run concurrently with receiving, so one may itself make a
+    /// request of the peer. Outstanding requests fail once this returns.
+    async fn run<F, Fut>(&self, answer: F) -> Result<(), ConnectionError>
+    where
+        F: Fn(Message) -> Fut,
+        Fut: Future<Output = Result<Message, JsonRpcError>>,
+    {
+        let result = self.serve(&answer).await;
+        lock(&self.waiting).take();
+        lock(&self.notifications).take();
+        result
+    }
+
+    async fn serve<F, Fut>(&self, answer: &F) -> Result<(), ConnectionError>
+    where
+        F: Fn(Message) -> Fut,
+        Fut: Future<Output = Result<Message, JsonRpcError>>,
+    {
+        let mut handling = FuturesUnordered::new();
+        // Kept across iterations: a transport's receive need not be cancel-safe.
+        let receive = self.transport.receive();
+        tokio::pin!(receive);
+
+        loop {
+            tokio::select! {
+                Some((id, result)) = handling.next(), if !handling.is_empty() => {
+                    self.respond(id, result).await?;
+                }
+                received = &mut receive => {
+                    receive.set(self.transport.receive());
+                    let json = match received {
+                        Ok(Some(json)) => json,
+                        Ok(None) => return Ok(()),
+                        // An oversized or non-UTF-8 frame; the stream goes on.
+                        Err(e) if frame_finding(&e).is_some() => continue,
+                        Err(e) => return Err(e.into()),
+                    };
+                    match self.route(&json) {
+                        Some(Inbound::Request(id, request)) => {
+                            handling.push(async move { (id, answer(*request).await) });
+                        }
+                        Some(Inbound::Malformed(id, error)) => self.reject(id, error).await?,
+                        None => {}
+                    }
+                }
+            }
+        }
+    }
+}
+
+// -------------
+// ClientConnection
+// -------------
+
+/// The client's end: requests to an agent, answers through a [`Client`].
+pub struct ClientConnection<T> {
+    core: Core<T>,
+}
+
+impl<T: Transport> ClientConnection<T> {
+    /// The connection and the agent's notifications (`session/update`).
+    pub fn new(transport: T) -> (Self, Notifications) {
+        let (core, notifications) = Core::new(transport, Direction::FromClient);
+        (ClientConnection { core }, notifications)
+    }
+
+    /// Receive until the transport closes, answering the agent's requests
+    /// with `client`.
+    pub async fn run(&self, client: &impl Client) -> Result<(), ConnectionError> {
+        self.core.run(|request| answer_agent(client, request)).await
+    }
+
+    async fn request(&self, request: ClientToAgentMessage) -> Result<Message, ConnectionError> {
+        self.core.request(Message::FromClient(request)).await
+    }
+
+    pub async fn initialize(
+        &self,
+        params: InitializeParams,
+    ) -> Result<InitializeResult, ConnectionError> {
+        match self
+            .request(ClientToAgentMessage::Initialize(params))
+            .await?
+        {
+            Message::FromAgent(AgentToClientMessage::InitializeResult(r)) => Ok(r),
+            other => Err(rejected(other)),
+        }
+    }
+
+    pub async fn authenticate(
+        &self,
+        params: AuthenticateParams,
+    ) -> Result<AuthenticateResult, ConnectionError> {
+        match self
+            .request(ClientToAgentMessage::Authenticate(params))
+            .await?
+        {
+            Message::FromAgent(AgentToClientMessage::AuthenticateResult(r)) => Ok(r),
+            other => Err(rejected(other)),
+        }
+    }
+
+    pub async fn new_session(
+        &self,
+        params: NewSessionParams,
+    ) -> Result<NewSessionResult, ConnectionError> {
+        match self
+            .request(ClientToAgentMessage::SessionNew(params))
+            .await?
+        {
+            Message::FromAgent(AgentToClientMessage::SessionNewResult(r)) => Ok(r),
+            other => Err(rejected(other)),
+        }
+    }
+
+    pub async fn load_session(
+        &self,
+        params: LoadSessionParams,
+    ) -> Result<LoadSessionResult, ConnectionError> {
+        match self
+            .request(ClientToAgentMessage::SessionLoad(params))
+            .await?
+        {
+            Message::FromAgent(AgentToClientMessage::SessionLoadResult(r)) => Ok(r),
+            other => Err(rejected(other)),
+        }
+    }
+
+    pub async fn prompt(
+        &self,
+        params: SessionPromptParams,
+    ) -> Result<SessionPromptResult, ConnectionError> {
+        match self
+            .request(ClientToAgentMessage::SessionPrompt(params))
+            .await?
+        {
+            Message::FromAgent(AgentToClientMessage::SessionPromptResult(r)) => Ok(r),
+            other => Err(rejected(other)),
+        }
+    }
+
+    pub async fn set_mode(
+        &self,
+        params: SetSessionModeParams,
+    ) -> Result<SetSessionModeResult, ConnectionError> {
+        match self
+            .request(ClientToAgentMessage::SessionSetMode(params))
+            .await?
+        {
+            Message::FromAgent(AgentToClientMessage::SessionSetModeResult(r)) => Ok(r),
+            other => Err(rejected(other)),
+        }
+    }
+
+    pub async fn ext_method(
+        &self,
+        method: &str,
+        params: Option<Value>,
+    ) -> Result<Option<Value>, ConnectionError> {
+        let request = ClientToAgentMessage::ExtRequest {
+            method: method.to_owned(),
+            params,
+        };
+        match self.request(request).await? {
+            Message::FromAgent(AgentToClientMessage::ExtResponse { result, .. }) => Ok(result),
+            other => Err(rejected(other)),
+        }
+    }
+
+    /// `session/cancel` is a notification: it has no response to wait for.
+    pub async fn cancel(&self, params: SessionCancelParams) -> Result<(), ConnectionError> {
+        let cancel = ClientToAgentMessage::SessionCancel(params);
+        self.core.notify(Message::FromClient(cancel)).await
+    }
+
+    /// Close the transport, which ends `run`.
+    pub async fn close(&self) -> Result<(), ConnectionError> {
+        Ok(self.core.transport.close().await?)
+    }
+}
+
+// -------------
+// AgentConnection
+// -------------
+
+/// The agent's end: answers through an [`Agent`], requests to the client.
+pub struct AgentConnection<T> {
+    core: Core<T>,
+}
+
+impl<T: Transport> AgentConnection<T> {
+    /// The connection and the client's notifications (`session/cancel`).
+    pub fn new(transport: T) -> (Self, Notifications) {
+        let (core, notifications) = Core::new(transport, Direction::FromAgent);
+        (AgentConnection { core }, notifications)
+    }
+
+    /// Receive until the transport closes, answering the client's requests
+    /// with `agent`.
+    pub async fn run(&self, agent: &impl Agent) -> Result<(), ConnectionError> {
+        self.core.run(|request| answer_client(agent, request)).await
+    }
+
+    async fn request(&self, request: AgentToClientMessage) -> Result<Message, ConnectionError> {
+        self.core.request(Message::FromAgent(request)).await
+    }
+
+    pub async fn session_update(
+        &self,
+        params: SessionUpdateNotification,
+    ) -> Result<(), ConnectionError> {
+        let update = AgentToClientMessage::SessionUpdate(params);
+        self.core.notify(Message::FromAgent(update)).await
+    }
+
+    pub async fn request_permission(
+        &self,
+        params: RequestPermissionParams,
+    ) -> Result<RequestPermissionResult, ConnectionError> {
+        let request = AgentToClientMessage::SessionRequestPermissionRequest(params);
+        match self.request(request).await? {
+            Message::FromClient(ClientToAgentMessage::SessionRequestPermissionResult(r)) => Ok(r),
+            other => Err(rejected(other)),
+        }
+    }
+
+    pub async fn read_text_file(
+        &self,
+        params: ReadTextFileParams,
+    ) -> Result<ReadTextFileResult, ConnectionError> {
+        match self
+            .request(AgentToClientMessage::FsReadTextFileRequest(params))
+            .await?
+        {
+            Message::FromClient(ClientToAgentMessage::FsReadTextFileResult(r)) => Ok(r),
+            other => Err(rejected(other)),
+        }
+    }
+
+    pub async fn write_text_file(
+        &self,
+        params: WriteTextFileParams,
+    ) -> Result<WriteTextFileResult, ConnectionError> {
+        match self
+            .request(AgentToClientMessage::FsWriteTextFileRequest(params))
+            .await?
+        {
+            Message::FromClient(ClientToAgentMessage::FsWriteTextFileResult(r)) => Ok(r),
+            other => Err(rejected(other)),
+        }
+    }
+
+    pub async fn create_terminal(
+        &self,
+        params: CreateTerminalParams,
+    ) -> Result<CreateTerminalResult, ConnectionError> {
+        match self
+            .request(AgentToClientMessage::TerminalCreateRequest(params))
+            .await?
+        {
+            Message::FromClient(ClientToAgentMessage::TerminalCreateResult(r)) => Ok(r),
+            other => Err(rejected(other)),
+        }
+    }
+
+    pub async fn terminal_output(
+        &self,
+        params: TerminalOutputParams,
+    ) -> Result<TerminalOutputResult, ConnectionError> {
+        match self
+            .request(AgentToClientMessage::TerminalOutputRequest(params))
+            .await?
+        {
+            Message::FromClient(ClientToAgentMessage::TerminalOutputResult(r)) => Ok(r),
+            other => Err(rejected(other)),
+        }
+    }
+
+    pub async fn wait_for_terminal_exit(
+        &self,
+        params: WaitForTerminalExitParams,
+    ) -> Result<TerminalExitStatus, ConnectionError> {
+        let request = AgentToClientMessage::TerminalWaitForExitRequest(params);
+        match self.request(request).await? {
+            Message::FromClient(ClientToAgentMessage::TerminalWaitForExitResult(r)) => Ok(r),
+            other => Err(rejected(other)),
+        }
+    }
+
+    pub async fn kill_terminal(
+        &self,
+        params: KillTerminalCommandParams,
+    ) -> Result<KillTerminalCommandResult, ConnectionError> {
+        match self
+            .request(AgentToClientMessage::TerminalKillRequest(params))
+            .await?
+        {
+            Message::FromClient(ClientToAgentMessage::TerminalKillResult(r)) => Ok(r),
+            other => Err(rejected(other)),
+        }
+    }
+
+    pub async fn release_terminal(
+        &self,
+        params: ReleaseTerminalParams,
+    ) -> Result<ReleaseTerminalResult, ConnectionError> {
+        match self
+            .request(AgentToClientMessage::TerminalReleaseRequest(params))
+            .await?
+        {
+            Message::FromClient(ClientToAgentMessage::TerminalReleaseResult(r)) => Ok(r),
+            other => Err(rejected(other)),
+        }
+    }
+
+    pub async fn ext_method(
+        &self,
+        method: &str,
+        params: Option<Value>,
+    ) -> Result<Option<Value>, ConnectionError> {
+        let request = AgentToClientMessage::ExtRequest {
+            method: method.to_owned(),
+            params,
+        };
+        match self.request(request).await? {
+            Message::FromClient(ClientToAgentMessage::ExtResponse { result, .. }) => Ok(result),
+            other => Err(rejected(other)),
+        }
+    }
+
+    /// Close the transport, which ends `run`.
+    pub async fn close(&self) -> Result<(), ConnectionError> {
+        Ok(self.core.transport.close().await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::{block_on, DuplexTransport};
+
+    fn session() -> SessionId {
+        SessionId("s1".to_owned())
+    }
+
+    /// Reads a file from the client before finishing each prompt turn.
+    struct Reader<'a> {
+        conn: &'a AgentConnection<DuplexTransport>,
+    }
+
+    impl Agent for Reader<'_> {
+        async fn initialize(
+            &self,
+            params: InitializeParams,
+        ) -> Result<InitializeResult, JsonRpcError> {
+            Ok(InitializeResult {
+                protocol_version: params.protocol_version,
+                agent_capabilities: AgentCapabilities::default(),
+                agent_info: None,
+                auth_methods: Vec::new(),
+            })
+        }
+
+        async fn new_session(&self, _: NewSessionParams) -> Result<NewSessionResult, JsonRpcError> {
+            Ok(NewSessionResult {
+                session_id: session(),
+                modes: None,
+            })
+        }
+
+        async fn prompt(
+            &self,
+            params: SessionPromptParams,
+        ) -> Result<SessionPromptResult, JsonRpcError> {
+            let read = ReadTextFileParams {
+                session_id: params.session_id.clone(),
+                path: "notes.txt".to_owned(),
+                line: None,
+                limit: None,
+            };
+            let file = self
+                .conn
+                .read_text_file(read)
+                .await
+                .map_err(internal_error)?;
+            let update = SessionUpdateNotification {
+                session_id: params.session_id.clone(),
+                update: SessionUpdate::AgentMessageChunk(ContentChunk {
+                    content: ContentBlock::Text(TextContent {
+                        text: file.content,
+                        annotations: None,
+                    }),
+                }),
+                meta: None,
+            };
+            self.conn
+                .session_update(update)
+                .await
+                .map_err(internal_error)?;
+            Ok(SessionPromptResult {
+                session_id: params.session_id,
+                stop_reason: StopReason::EndTurn,
+                usage: None,
+                meta: None,
+            })
+        }
+    }
+
+    struct Files;
+
+    impl Client for Files {
+        async fn read_text_file(
+            &self,
+            params: ReadTextFileParams,
+        ) -> Result<ReadTextFileResult, JsonRpcError> {
+            Ok(ReadTextFileResult {
+                content: format!("contents of {}", params.path),
+            })
+        }
+    }
+
+    #[test]
+    fn prompt_turn_calls_back_into_the_client() {
+        let (near, far) = DuplexTransport::pair();
+        let (client, mut updates) = ClientConnection::new(near);
+        let (agent, _) = AgentConnection::new(far);
+        let handler = Reader { conn: &agent };
+
+        let (client_run, agent_run, turn) = block_on(async {
+            tokio::join!(client.run(&Files), agent.run(&handler), async {
+                let init = InitializeParams {
+                    protocol_version: 1,
+                    client_capabilities: ClientCapabilities::default(),
+                    client_info: None,
+                };
+                client.initialize(init).await?;
+                let new = NewSessionParams {
+                    cwd: "/tmp".to_owned(),
+                    mcp_servers: Vec::new(),
+                };
+                let session_id = client.new_session(new).await?.session_id;
+                let prompt = SessionPromptParams {
+                    session_id,
+                    prompt: Vec::new(),
+                    meta: None,
+                };
+                let turn = client.prompt(prompt).await;
+                client.close().await?;
+                turn
+            })
+        });
+        client_run.unwrap();
+        agent_run.unwrap();
+        assert_eq!(turn.unwrap().stop_reason, StopReason::EndTurn);
+
+        let update = block_on(updates.recv()).expect("one session/update");
+        let Message::FromAgent(AgentToClientMessage::SessionUpdate(update)) = update else {
+            panic!("expected a session/update, got {update:?}");
+        };
+        let SessionUpdate::AgentMessageChunk(chunk) = update.update else {
+            panic!("expected a message chunk");
+        };
+        assert_eq!(
+            chunk.content,
+            ContentBlock::Text(TextContent {
+                text: "contents of notes.txt".to_owned(),
+                annotations: None
+            })
+        );
+        assert!(block_on(updates.recv()).is_none(), "stream ends with run");
+    }
+
+    #[test]
+    fn unimplemented_methods_answer_method_not_found() {
+        let (near, far) = DuplexTransport::pair();
+        let (client, _) = ClientConnection::new(near);
+        let (agent, _) = AgentConnection::new(far);
+        let handler = Reader { conn: &agent };
+
+        let (_, _, (set_mode, ext)) = block_on(async {
+            tokio::join!(client.run(&Files), agent.run(&handler), async {
+                let set_mode = SetSessionModeParams {
+                    session_id: session(),
+                    mode_id: SessionModeId("plan".to_owned()),
+                };
+                let set_mode = client.set_mode(set_mode).await;
+                let ext = client
+                    .ext_method("_acme/ping", Some(serde_json::json!({})))
+                    .await;
+                client.close().await.unwrap();
+                (set_mode, ext)
+            })
+        });
+        for result in [set_mode.map(|_| ()), ext.map(|_| ())] {
+            match result {
+                Err(ConnectionError::Rpc(e)) => assert_eq!(e.code, -32601),
+                other => panic!("expected method not found, got {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn malformed_messages_fail_their_waiter_or_get_invalid_params() {
+        let (near, far) = DuplexTransport::pair();
+        let (client, _) = ClientConnection::new(near);
+
+        let (run, (turn, reply)) = block_on(async {
+            tokio::join!(client.run(&Files), async {
+                let prompt = SessionPromptParams {
+                    session_id: session(),
+                    prompt: Vec::new(),
+                    meta: None,
+                };
+                let peer = async {
+                    far.receive().await.unwrap().expect("the prompt request");
+                    // No stopReason: the result does not decode.
+                    let result = r#"{"jsonrpc":"2.0","result":{},"id":1}"#;
+                    far.send(result.to_owned()).await.unwrap();
+                    let request =
+                        r#"{"jsonrpc":"2.0","method":"fs/read_text_file","params":{},"id":7}"#;
+                    far.send(request.to_owned()).await.unwrap();
+                    let reply = far.receive().await.unwrap().expect("an error reply");
+                    far.close().await.unwrap();
+                    reply
+                };
+                tokio::join!(client.prompt(prompt), peer)
+            })
+        });
+        run.unwrap();
+        assert!(matches!(
+            turn,
+            Err(ConnectionError::Decode(DecodeError::InvalidResult { .. }))
+        ));
+        assert!(lock(&client.core.state).pending_client_requests.is_empty());
+        let reply: Value = serde_json::from_str(&reply).unwrap();
+        assert_eq!(reply["id"], 7);
+        assert_eq!(reply["error"]["code"], -32602);
+    }
+
+    #[test]
+    fn pending_requests_fail_when_the_transport_closes() {
+        let (near, far) = DuplexTransport::pair();
+        let (client, _) = ClientConnection::new(near);
+
+        let (run, init) = block_on(async {
+            tokio::join!(client.run(&Files), async {
+                let init = InitializeParams {
+                    protocol_version: 1,
+                    client_capabilities: ClientCapabilities::default(),
+                    client_info: None,
+                };
+                // Nobody answers on `far`; closing it ends the wait.
+                let (init, _) = tokio::join!(client.initialize(init), far.close());
+                init
+            })
+        });
+        run.unwrap();
+        assert!(matches!(init, Err(ConnectionError::TransportClosed)));
+        assert!(matches!(
+            block_on(client.new_session(NewSessionParams {
+                cwd: "/tmp".to_owned(),
+                mcp_servers: Vec::new(),
+            })),
+            Err(ConnectionError::TransportClosed)
+        ));
+        // The refused request never entered the codec state.
+        let pending = &lock(&client.core.state).pending_client_requests;
+        assert!(!pending.contains_key(&RequestId::Number(2)));
+    }
+}
@@ -0,0 +1,1235 @@
+//! Typed ACP domain model (schema 0.10.5).
+//! Mirrors `protocol/src/Acp.Domain.fs`: the transport-agnostic meaning of ACP
+//! once JSON-RPC framing has been decoded.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Value};
+
+// -------------
+// Primitives
+// -------------
+
+/// MAJOR protocol version negotiated during initialization (uint16 in schema).
+pub type ProtocolVersion = u16;
+
+/// Opaque session identifier.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SessionId(pub String);
+
+/// Implementation metadata for clients and agents.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImplementationInfo {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    pub version: String,
+}
+
+// -------------
+// Capabilities
+// -------------
+
+/// File system capabilities supported by the client.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FileSystemCapabilities {
+    pub read_text_file: bool,
+    pub write_text_file: bool,
+}
+
+/// Capabilities advertised by the client in initialize.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClientCapabilities {
+    pub fs: FileSystemCapabilities,
+    pub terminal: bool,
+}
+
+/// MCP transport capabilities supported by the agent.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct McpCapabilities {
+    pub http: bool,
+    pub sse: bool,
+}
+
+/// Prompt content types the agent can process in `session/prompt`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PromptCapabilities {
+    pub audio: bool,
+    pub image: bool,
+    pub embedded_context: bool,
+}
+
+/// Session capabilities supported by the agent. Currently an empty object in schema.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SessionCapabilities {}
+
+/// Capabilities advertised by the agent during initialize.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AgentCapabilities {
+    pub load_session: bool,
+    pub mcp_capabilities: McpCapabilities,
+    pub prompt_capabilities: PromptCapabilities,
+    pub session_capabilities: SessionCapabilities,
+}
+
+// -------------
+// Authentication
+// -------------
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthenticateParams {
+    pub method_id: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuthMethod {
+    pub id: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+/// Empty result object in schema.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AuthenticateResult {}
+
+// -------------
+// Initialization
+// -------------
+
+/// Params for initialize (client -> agent).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InitializeParams {
+    pub protocol_version: ProtocolVersion,
+    #[serde(default)]
+    pub client_capabilities: ClientCapabilities,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_info: Option<ImplementationInfo>,
+}
+
+/// Result for initialize (agent -> client).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InitializeResult {
+    pub protocol_version: ProtocolVersion,
+    #[serde(default)]
+    pub agent_capabilities: AgentCapabilities,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent_info: Option<ImplementationInfo>,
+    #[serde(default)]
+    pub auth_methods: Vec<AuthMethod>,
+}
+
+// -------------
+// Session modes
+// -------------
+
+/// Unique identifier for a session mode.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SessionModeId(pub String);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionMode {
+    pub id: SessionModeId,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+/// The set of modes and the one currently active.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionModeState {
+    pub current_mode_id: SessionModeId,
+    pub available_modes: Vec<SessionMode>,
+}
+
+/// Params for session/set_mode (client -> agent).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetSessionModeParams {
+    pub session_id: SessionId,
+    pub mode_id: SessionModeId,
+}
+
+/// Domain-level result for session/set_mode (agent -> client).
+/// Wire result is structurally empty; we reattach sessionId + modeId from the request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetSessionModeResult {
+    pub session_id: SessionId,
+    pub mode_id: SessionModeId,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CurrentModeUpdate {
+    pub current_mode_id: SessionModeId,
+}
+
+// -------------
+// Session setup
+// -------------
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EnvVariable {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HttpHeader {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct McpServerHttp {
+    pub name: String,
+    pub url: String,
+    pub headers: Vec<HttpHeader>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct McpServerSse {
+    pub name: String,
+    pub url: String,
+    pub headers: Vec<HttpHeader>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct McpServerStdio {
+    pub name: String,
+    pub command: String,
+    pub args: Vec<String>,
+    pub env: Vec<EnvVariable>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct McpServerAcp {
+    pub name: String,
+    pub uuid: String,
+}
+
+/// MCP server configuration. Stdio has no discriminator in schema, so the wire
+/// shape is resolved through [`McpServerWire`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "McpServerWire", into = "McpServerWire")]
+pub enum McpServer {
+    Http(McpServerHttp),
+    Sse(McpServerSse),
+    Stdio(McpServerStdio),
+    Acp(McpServerAcp),
+}
+
+/// Flat wire shape shared by every MCP server variant.
+#[derive(Serialize, Deserialize)]
+struct McpServerWire {
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    kind: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    transport: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    headers: Option<Vec<HttpHeader>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    command: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    args: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    env: Option<Vec<EnvVariable>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    uuid: Option<String>,
+}
+
+impl TryFrom<McpServerWire> for McpServer {
+    type Error = String;
+
+    fn try_from(w: McpServerWire) -> Result<Self, Self::Error> {
+        fn required<T>(field: Option<T>, name: &str) -> Result<T, String> {
+            field.ok_or_else(|| format!("missing field `{name}`"))
+        }
+
+        match w.transport.as_deref().or(w.kind.as_deref()) {
+            Some("http") => Ok(McpServer::Http(McpServerHttp {
+                name: required(w.name, "name")?,
+                url: required(w.url, "url")?,
+                headers: required(w.headers, "headers")?,
+            })),
+            Some("sse") => Ok(McpServer::Sse(McpServerSse {
+                name: required(w.name, "name")?,
+                url: required(w.url, "url")?,
+                headers: required(w.headers, "headers")?,
+            })),
+            Some("acp") => {
+                let uuid = required(w.uuid, "uuid")?;
+                Ok(McpServer::Acp(McpServerAcp {
+                    name: w.name.unwrap_or_else(|| uuid.clone()),
+                    uuid,
+                }))
+            }
+            _ => Ok(McpServer::Stdio(McpServerStdio {
+                name: required(w.name, "name")?,
+                command: required(w.command, "command")?,
+                args: required(w.args, "args")?,
+                env: required(w.env, "env")?,
+            })),
+        }
+    }
+}
+
+impl From<McpServer> for McpServerWire {
+    fn from(s: McpServer) -> Self {
+        let empty = McpServerWire {
+            kind: None,
+            transport: None,
+            name: None,
+            url: None,
+            headers: None,
+            command: None,
+            args: None,
+            env: None,
+            uuid: None,
+        };
+
+        match s {
+            McpServer::Http(v) => McpServerWire {
+                kind: Some("http".into()),
+                name: Some(v.name),
+                url: Some(v.url),
+                headers: Some(v.headers),
+                ..empty
+            },
+            McpServer::Sse(v) => McpServerWire {
+                kind: Some("sse".into()),
+                name: Some(v.name),
+                url: Some(v.url),
+                headers: Some(v.headers),
+                ..empty
+            },
+            McpServer::Stdio(v) => McpServerWire {
+                name: Some(v.name),
+                command: Some(v.command),
+                args: Some(v.args),
+                env: Some(v.env),
+                ..empty
+            },
+            McpServer::Acp(v) => McpServerWire {
+                transport: Some("acp".into()),
+                name: Some(v.name),
+                uuid: Some(v.uuid),
+                ..empty
+            },
+        }
+    }
+}
+
+/// Params for session/new (client -> agent).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewSessionParams {
+    pub cwd: String,
+    pub mcp_servers: Vec<McpServer>,
+}
+
+/// Params for session/load (client -> agent).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoadSessionParams {
+    pub session_id: SessionId,
+    pub cwd: String,
+    pub mcp_servers: Vec<McpServer>,
+}
+
+/// Result for session/new (agent -> client).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewSessionResult {
+    pub session_id: SessionId,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modes: Option<SessionModeState>,
+}
+
+/// Domain-level result for session/load (agent -> client).
+/// Wire result does not include a session id; we reattach it from the request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoadSessionResult {
+    pub session_id: SessionId,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modes: Option<SessionModeState>,
+}
+
+// -------------
+// JSON-RPC (wire errors + ids)
+// -------------
+
+/// JSON-RPC request id. A `null` id is treated as absent (notification).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RequestId {
+    Number(i64),
+    String(String),
+}
+
+impl Serialize for RequestId {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        match self {
+            RequestId::Number(n) => s.serialize_i64(*n),
+            RequestId::String(v) => s.serialize_str(v),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for RequestId {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl serde::de::Visitor<'_> for Visitor {
+            type Value = RequestId;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("id must be string, number, or null")
+            }
+
+            fn visit_i64<E: serde::de::Error>(self, v: i64) -> Result<RequestId, E> {
+                Ok(RequestId::Number(v))
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, v: u64) -> Result<RequestId, E> {
+                i64::try_from(v)
+                    .map(RequestId::Number)
+                    .map_err(|_| E::custom("id out of int64 range"))
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<RequestId, E> {
+                Ok(RequestId::String(v.to_owned()))
+            }
+
+            fn visit_string<E: serde::de::Error>(self, v: String) -> Result<RequestId, E> {
+                Ok(RequestId::String(v))
+            }
+        }
+
+        d.deserialize_any(Visitor)
+    }
+}
+
+impl std::fmt::Display for RequestId {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RequestId::Number(n) => write!(f, "{n}"),
+            RequestId::String(s) => write!(f, "\"{s}\""),
+        }
+    }
+}
+
+/// JSON-RPC error object carried by a failed response.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonRpcError {
+    pub code: i32,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+// -------------
+// Content
+// -------------
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Annotations {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audience: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TextContent {
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub annotations: Option<Annotations>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageContent {
+    pub data: String,
+    pub mime_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uri: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub annotations: Option<Annotations>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioContent {
+    pub data: String,
+    pub mime_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub annotations: Option<Annotations>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceLink {
+    pub name: String,
+    pub uri: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub annotations: Option<Annotations>,
+}
+
+/// Embedded resource contents, distinguished by field name (`text` vs `blob`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum EmbeddedResourceResource {
+    Text {
+        uri: String,
+        text: String,
+        #[serde(rename = "mimeType", default, skip_serializing_if = "Option::is_none")]
+        mime_type: Option<String>,
+    },
+    Blob {
+        uri: String,
+        blob: String,
+        #[serde(rename = "mimeType", default, skip_serializing_if = "Option::is_none")]
+        mime_type: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EmbeddedResource {
+    pub resource: EmbeddedResourceResource,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub annotations: Option<Annotations>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentBlock {
+    Text(TextContent),
+    Image(ImageContent),
+    Audio(AudioContent),
+    ResourceLink(ResourceLink),
+    Resource(EmbeddedResource),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContentChunk {
+    pub content: ContentBlock,
+}
+
+// -------------
+// Prompt turn
+// -------------
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionPromptParams {
+    pub session_id: SessionId,
+    pub prompt: Vec<ContentBlock>,
+    /// Draft RFD: `_meta` for W3C trace context propagation.
+    #[serde(rename = "_meta", default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Map<String, Value>>,
+}
+
+/// Params for session/cancel (client -> agent). Notification.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionCancelParams {
+    pub session_id: SessionId,
+}
+
+/// Reasons why a prompt turn stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StopReason {
+    EndTurn,
+    MaxTokens,
+    MaxTurnRequests,
+    Refusal,
+    Cancelled,
+}
+
+/// Domain-level result for session/prompt (agent -> client).
+/// Wire result does not include a session id; we reattach it from the request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionPromptResult {
+    pub session_id: SessionId,
+    pub stop_reason: StopReason,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Map<String, Value>>,
+    /// Draft RFD: `_meta` for W3C trace context propagation.
+    #[serde(rename = "_meta", default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Map<String, Value>>,
+}
+
+// ---- Plan ----
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlanEntryPriority {
+    High,
+    Medium,
+    Low,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlanEntryStatus {
+    Pending,
+    InProgress,
+    Completed,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlanEntry {
+    pub content: String,
+    pub priority: PlanEntryPriority,
+    pub status: PlanEntryStatus,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Plan {
+    pub entries: Vec<PlanEntry>,
+}
+
+// ---- Commands ----
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UnstructuredCommandInput {
+    pub hint: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum AvailableCommandInput {
+    Unstructured(UnstructuredCommandInput),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AvailableCommand {
+    pub name: String,
+    pub description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input: Option<AvailableCommandInput>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AvailableCommandsUpdate {
+    pub available_commands: Vec<AvailableCommand>,
+}
+
+// ---- Tool calls ----
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Diff {
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub old_text: Option<String>,
+    pub new_text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TerminalRef {
+    pub terminal_id: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ToolCallContent {
+    Content { content: ContentBlock },
+    Diff(Diff),
+    Terminal(TerminalRef),
+}
+
+/// Tool category; unknown kinds decode as `Other`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolKind {
+    Read,
+    Edit,
+    Delete,
+    Move,
+    Search,
+    Execute,
+    Think,
+    Fetch,
+    SwitchMode,
+    #[default]
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolCallStatus {
+    #[default]
+    Pending,
+    InProgress,
+    Completed,
+    Failed,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCallLocation {
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolCall {
+    pub tool_call_id: String,
+    pub title: String,
+    #[serde(default)]
+    pub kind: ToolKind,
+    #[serde(default)]
+    pub status: ToolCallStatus,
+    #[serde(default)]
+    pub content: Vec<ToolCallContent>,
+    #[serde(default)]
+    pub locations: Vec<ToolCallLocation>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_input: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_output: Option<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolCallUpdate {
+    pub tool_call_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<ToolKind>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<ToolCallStatus>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<Vec<ToolCallContent>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locations: Option<Vec<ToolCallLocation>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_input: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_output: Option<Value>,
+}
+
+// ---- Permission requests ----
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PermissionOptionKind {
+    AllowOnce,
+    AllowAlways,
+    RejectOnce,
+    RejectAlways,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PermissionOption {
+    pub option_id: String,
+    pub name: String,
+    pub kind: PermissionOptionKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestPermissionParams {
+    pub session_id: SessionId,
+    pub tool_call: ToolCallUpdate,
+    pub options: Vec<PermissionOption>,
+}
+
+/// Outcome object, discriminated by `outcome`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum RequestPermissionOutcome {
+    Cancelled,
+    Selected {
+        #[serde(rename = "optionId")]
+        option_id: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RequestPermissionResult {
+    pub outcome: RequestPermissionOutcome,
+}
+
+// ---- Session updates ----
+
+/// A `session/update` payload, discriminated by `sessionUpdate`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionUpdate {
+    UserMessageChunk(ContentChunk),
+    AgentMessageChunk(ContentChunk),
+    AgentThoughtChunk(ContentChunk),
+    ToolCall(ToolCall),
+    ToolCallUpdate(ToolCallUpdate),
+    Plan(Plan),
+    AvailableCommandsUpdate(AvailableCommandsUpdate),
+    CurrentModeUpdate(CurrentModeUpdate),
+    /// Unknown update payload preserved for forward compatibility.
+    Ext {
+        tag: String,
+        payload: Map<String, Value>,
+    },
+}
+
+impl SessionUpdate {
+    /// The `sessionUpdate` discriminator, e.g. `agent_message_chunk`.
+    pub fn tag(&self) -> &str {
+        match self {
+            SessionUpdate::UserMessageChunk(_) => "user_message_chunk",
+            SessionUpdate::AgentMessageChunk(_) => "agent_message_chunk",
+            SessionUpdate::AgentThoughtChunk(_) => "agent_thought_chunk",
+            SessionUpdate::ToolCall(_) => "tool_call",
+            SessionUpdate::ToolCallUpdate(_) => "tool_call_update",
+            SessionUpdate::Plan(_) => "plan",
+            SessionUpdate::AvailableCommandsUpdate(_) => "available_commands_update",
+            SessionUpdate::CurrentModeUpdate(_) => "current_mode_update",
+            SessionUpdate::Ext { tag, .. } => tag,
+        }
+    }
+}
+
+/// Borrowed mirror of the known `SessionUpdate` variants, used for serialization.
+#[derive(Serialize)]
+#[serde(tag = "sessionUpdate", rename_all = "snake_case")]
+enum KnownSessionUpdate<'a> {
+    UserMessageChunk(&'a ContentChunk),
+    AgentMessageChunk(&'a ContentChunk),
+    AgentThoughtChunk(&'a ContentChunk),
+    ToolCall(&'a ToolCall),
+    ToolCallUpdate(&'a ToolCallUpdate),
+    Plan(&'a Plan),
+    AvailableCommandsUpdate(&'a AvailableCommandsUpdate),
+    CurrentModeUpdate(&'a CurrentModeUpdate),
+}
+
+impl Serialize for SessionUpdate {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        let known = match self {
+            SessionUpdate::UserMessageChunk(c) => KnownSessionUpdate::UserMessageChunk(c),
+            SessionUpdate::AgentMessageChunk(c) => KnownSessionUpdate::AgentMessageChunk(c),
+            SessionUpdate::AgentThoughtChunk(c) => KnownSessionUpdate::AgentThoughtChunk(c),
+            SessionUpdate::ToolCall(c) => KnownSessionUpdate::ToolCall(c),
+            SessionUpdate::ToolCallUpdate(u) => KnownSessionUpdate::ToolCallUpdate(u),
+            SessionUpdate::Plan(p) => KnownSessionUpdate::Plan(p),
+            SessionUpdate::AvailableCommandsUpdate(u) => {
+                KnownSessionUpdate::AvailableCommandsUpdate(u)
+            }
+            SessionUpdate::CurrentModeUpdate(u) => KnownSessionUpdate::CurrentModeUpdate(u),
+            SessionUpdate::Ext { tag, payload } => {
+                use serde::ser::SerializeMap;
+                let mut map = s.serialize_map(Some(payload.len() + 1))?;
+                map.serialize_entry("sessionUpdate", tag)?;
+                for (k, v) in payload.iter().filter(|(k, _)| *k != "sessionUpdate") {
+                    map.serialize_entry(k, v)?;
+                }
+                return map.end();
+            }
+        };
+        known.serialize(s)
+    }
+}
+
+impl<'de> Deserialize<'de> for SessionUpdate {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        let payload = Map::<String, Value>::deserialize(d)?;
+        let tag = match payload.get("sessionUpdate") {
+            Some(Value::String(t)) => t.clone(),
+            Some(_) => return Err(D::Error::custom("sessionUpdate must be a string")),
+            None => return Err(D::Error::missing_field("sessionUpdate")),
+        };
+
+        fn typed<T: serde::de::DeserializeOwned, E: Error>(p: Map<String, Value>) -> Result<T, E> {
+            serde_json::from_value(Value::Object(p)).map_err(E::custom)
+        }
+
+        Ok(match tag.as_str() {
+            "user_message_chunk" => SessionUpdate::UserMessageChunk(typed(payload)?),
+            "agent_message_chunk" => SessionUpdate::AgentMessageChunk(typed(payload)?),
+            "agent_thought_chunk" => SessionUpdate::AgentThoughtChunk(typed(payload)?),
+            "tool_call" => SessionUpdate::ToolCall(typed(payload)?),
+            "tool_call_update" => SessionUpdate::ToolCallUpdate(typed(payload)?),
+            "plan" => SessionUpdate::Plan(typed(payload)?),
+            "available_commands_update" => SessionUpdate::AvailableCommandsUpdate(typed(payload)?),
+            "current_mode_update" => SessionUpdate::CurrentModeUpdate(typed(payload)?),
+            _ => SessionUpdate::Ext { tag, payload },
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionUpdateNotification {
+    pub session_id: SessionId,
+    pub update: SessionUpdate,
+    /// Draft RFD: `_meta` for W3C trace context propagation.
+    #[serde(rename = "_meta", default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Map<String, Value>>,
+}
+
+// ---- File system + terminal tool surface (agent -> client requests) ----
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadTextFileParams {
+    pub session_id: SessionId,
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReadTextFileResult {
+    pub content: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WriteTextFileParams {
+    pub session_id: SessionId,
+    pub path: String,
+    pub content: String,
+}
+
+/// Empty result object in schema.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct WriteTextFileResult {}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateTerminalParams {
+    pub session_id: SessionId,
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    #[serde(default)]
+    pub env: Vec<EnvVariable>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_byte_limit: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateTerminalResult {
+    pub terminal_id: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TerminalExitStatus {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signal: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TerminalOutputParams {
+    pub session_id: SessionId,
+    pub terminal_id: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TerminalOutputResult {
+    pub output: String,
+    #[serde(default)]
+    pub truncated: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_status: Option<TerminalExitStatus>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WaitForTerminalExitParams {
+    pub session_id: SessionId,
+    pub terminal_id: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KillTerminalCommandParams {
+    pub session_id: SessionId,
+    pub terminal_id: String,
+}
+
+/// Empty result object in schema.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct KillTerminalCommandResult {}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReleaseTerminalParams {
+    pub session_id: SessionId,
+    pub terminal_id: String,
+}
+
+/// Empty result object in schema.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReleaseTerminalResult {}
+
+// -------------
+// Proxy chains (draft)
+// -------------
+
+/// Proxy chain wrapper for forwarding ACP messages to successors.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProxySuccessorParams {
+    pub method: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub params: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Map<String, Value>>,
+}
+
+// -------------
+// Message envelopes
+// -------------
+
+/// Methods, notifications and responses originating at the client.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClientToAgentMessage {
+    // Requests (client -> agent)
+    Initialize(InitializeParams),
+    ProxyInitialize(InitializeParams),
+    Authenticate(AuthenticateParams),
+    SessionNew(NewSessionParams),
+    SessionLoad(LoadSessionParams),
+    SessionPrompt(SessionPromptParams),
+    SessionSetMode(SetSessionModeParams),
+    ProxySuccessorRequest(ProxySuccessorParams),
+    ExtRequest {
+        method: String,
+        params: Option<Value>,
+    },
+    // Notifications (client -> agent)
+    SessionCancel(SessionCancelParams),
+    ProxySuccessorNotification(ProxySuccessorParams),
+    ExtNotification {
+        method: String,
+        params: Option<Value>,
+    },
+    // Responses (client -> agent) to agent->client requests
+    FsReadTextFileResult(ReadTextFileResult),
+    FsWriteTextFileResult(WriteTextFileResult),
+    SessionRequestPermissionResult(RequestPermissionResult),
+    TerminalCreateResult(CreateTerminalResult),
+    TerminalOutputResult(TerminalOutputResult),
+    TerminalWaitForExitResult(TerminalExitStatus),
+    TerminalKillResult(KillTerminalCommandResult),
+    TerminalReleaseResult(ReleaseTerminalResult),
+    FsReadTextFileError(ReadTextFileParams, JsonRpcError),
+    FsWriteTextFileError(WriteTextFileParams, JsonRpcError),
+    SessionRequestPermissionError(RequestPermissionParams, JsonRpcError),
+    TerminalCreateError(CreateTerminalParams, JsonRpcError),
+    TerminalOutputError(TerminalOutputParams, JsonRpcError),
+    TerminalWaitForExitError(WaitForTerminalExitParams, JsonRpcError),
+    TerminalKillError(KillTerminalCommandParams, JsonRpcError),
+    TerminalReleaseError(ReleaseTerminalParams, JsonRpcError),
+    ExtError {
+        method: String,
+        error: JsonRpcError,
+    },
+    ExtResponse {
+        method: String,
+        result: Option<Value>,
+    },
+    ProxySuccessorError {
+        method: String,
+        error: JsonRpcError,
+    },
+    ProxySuccessorResponse {
+        method: String,
+        result: Option<Value>,
+    },
+}
+
+/// Methods, notifications and requests originating at the agent.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AgentToClientMessage {
+    // Responses (agent -> client) to client->agent requests
+    InitializeResult(InitializeResult),
+    ProxyInitializeResult(InitializeResult),
+    AuthenticateResult(AuthenticateResult),
+    SessionNewResult(NewSessionResult),
+    SessionLoadResult(LoadSessionResult),
+    SessionPromptResult(SessionPromptResult),
+    SessionSetModeResult(SetSessionModeResult),
+    ExtResponse {
+        method: String,
+        result: Option<Value>,
+    },
+    ProxySuccessorResponse {
+        method: String,
+        result: Option<Value>,
+    },
+    InitializeError(JsonRpcError),
+    ProxyInitializeError(JsonRpcError),
+    AuthenticateError(JsonRpcError),
+    SessionNewError(JsonRpcError),
+    SessionLoadError(LoadSessionParams, JsonRpcError),
+    SessionPromptError(SessionPromptParams, JsonRpcError),
+    SessionSetModeError(SetSessionModeParams, JsonRpcError),
+    ExtError {
+        method: String,
+        error: JsonRpcError,
+    },
+    ProxySuccessorError {
+        method: String,
+        error: JsonRpcError,
+    },
+    // Notifications (agent -> client)
+    SessionUpdate(SessionUpdateNotification),
+    ProxySuccessorNotification(ProxySuccessorParams),
+    ExtNotification {
+        method: String,
+        params: Option<Value>,
+    },
+    // Requests (agent -> client)
+    FsReadTextFileRequest(ReadTextFileParams),
+    FsWriteTextFileRequest(WriteTextFileParams),
+    SessionRequestPermissionRequest(RequestPermissionParams),
+    TerminalCreateRequest(CreateTerminalParams),
+    TerminalOutputRequest(TerminalOutputParams),
+    TerminalWaitForExitRequest(WaitForTerminalExitParams),
+    TerminalKillRequest(KillTerminalCommandParams),
+    TerminalReleaseRequest(ReleaseTerminalParams),
+    ProxySuccessorRequest(ProxySuccessorParams),
+    ExtRequest {
+        method: String,
+        params: Option<Value>,
+    },
+}
+
+/// Direction-tagged domain message stream for a single connection.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    FromClient(ClientToAgentMessage),
+    FromAgent(AgentToClientMessage),
+}
+
+impl ClientToAgentMessage {
+    /// The JSON-RPC method this message is, or answers (responses are labelled
+    /// with the method of the request they settle).
+    pub fn method(&self) -> &str {
+        use ClientToAgentMessage as C;
+
+        match self {
+            C::Initialize(_) => "initialize",
+            C::ProxyInitialize(_) => "proxy/initialize",
+            C::Authenticate(_) => "authenticate",
+            C::SessionNew(_) => "session/new",
+            C::SessionLoad(_) => "session/load",
+            C::SessionPrompt(_) => "session/prompt",
+            C::SessionSetMode(_) => "session/set_mode",
+            C::SessionCancel(_) => "session/cancel",
+            C::ProxySuccessorRequest(_)
+            | C::ProxySuccessorNotification(_)
+            | C::ProxySuccessorError { .. }
+            | C::ProxySuccessorResponse { .. } => "proxy/successor",
+            C::ExtRequest { method, .. }
+            | C::ExtNotification { method, .. }
+            | C::ExtError { method, .. }
+            | C::ExtResponse { method, .. } => method,
+            C::FsReadTextFileResult(_) | C::FsReadTextFileError(..) => "fs/read_text_file",
+            C::FsWriteTextFileResult(_) | C::FsWriteTextFileError(..) => "fs/write_text_file",
+            C::SessionRequestPermissionResult(_) | C::SessionRequestPermissionError(..) => {
+                "session/request_permission"
+            }
+            C::TerminalCreateResult(_) | C::TerminalCreateError(..) => "terminal/create",
+            C::TerminalOutputResult(_) | C::TerminalOutputError(..) => "terminal/output",
+            C::TerminalWaitForExitResult(_) | C::TerminalWaitForExitError(..) => {
+                "terminal/wait_for_exit"
+            }
+            C::TerminalKillResult(_) | C::TerminalKillError(..) => "terminal/kill",
+            C::TerminalReleaseResult(_) | C::TerminalReleaseError(..) => "terminal/release",
+        }
+    }
+}
+
+impl AgentToClientMessage {
+    /// See [`ClientToAgentMessage::method`].
+    pub fn method(&self) -> &str {
+        use AgentToClientMessage as A;
+
+        match self {
+            A::InitializeResult(_) | A::InitializeError(_) => "initialize",
+            A::ProxyInitializeResult(_) | A::ProxyInitializeError(_) => "proxy/initialize",
+            A::AuthenticateResult(_) | A::AuthenticateError(_) => "authenticate",
+            A::SessionNewResult(_) | A::SessionNewError(_) => "session/new",
+            A::SessionLoadResult(_) | A::SessionLoadError(..) => "session/load",
+            A::SessionPromptResult(_) | A::SessionPromptError(..) => "session/prompt",
+            A::SessionSetModeResult(_) | A::SessionSetModeError(..) => "session/set_mode",
+            A::SessionUpdate(_) => "session/update",
+            A::ProxySuccessorResponse { .. }
+            | A::ProxySuccessorError { .. }
+            | A::ProxySuccessorNotification(_)
+            | A::ProxySuccessorRequest(_) => "proxy/successor",
+            A::ExtResponse { method, .. }
+            | A::ExtError { method, .. }
+            | A::ExtNotification { method, .. }
+            | A::ExtRequest { method, .. } => method,
+            A::FsReadTextFileRequest(_) => "fs/read_text_file",
+            A::FsWriteTextFileRequest(_) => "fs/write_text_file",
+            A::SessionRequestPermissionRequest(_) => "session/request_permission",
+            A::TerminalCreateRequest(_) => "terminal/create",
+            A::TerminalOutputRequest(_) => "terminal/output",
+            A::TerminalWaitForExitRequest(_) => "terminal/wait_for_exit",
+            A::TerminalKillRequest(_) => "terminal/kill",
+            A::TerminalReleaseRequest(_) => "terminal/release",
+        }
+    }
+}
+
+impl Message {
+    /// The JSON-RPC method this message is, or answers (responses are labelled
+    /// with the method of the request they settle).
+    pub fn method(&self) -> &str {
+        match self {
+            Message::FromClient(c) => c.method(),
+            Message::FromAgent(a) => a.method(),
+        }
+    }
+}
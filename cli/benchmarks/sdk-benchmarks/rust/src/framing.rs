@@ -0,0 +1,523 @@
+//! Message framing over `BufRead`: newline-delimited JSON, as in
+//! `StdioTransport` in `runtime/src/Acp.Transport.fs`, or LSP-style
+//! `Content-Length` headers used by some bridges. Every frame goes through
+//! `Validation.Transport.validateSize`; bad frames become Transport-lane
+//! findings rather than panics.
+
+use crate::validation::{self, ValidationFinding};
+use std::fmt;
+use std::io::{self, BufRead, Read, Write};
+
+/// Frame size limit when none is configured.
+pub const DEFAULT_MAX_FRAME_BYTES: usize = 16 * 1024 * 1024;
+
+/// Header lines are short; anything longer is not a header.
+const MAX_HEADER_LINE_BYTES: usize = 1024;
+
+// -------------
+// Framing
+// -------------
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Framing {
+    Ndjson,
+    ContentLength,
+}
+
+impl fmt::Display for Framing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Framing::Ndjson => f.write_str("ndjson"),
+            Framing::ContentLength => f.write_str("content-length"),
+        }
+    }
+}
+
+impl Framing {
+    /// Guess the framing from the first buffered bytes without consuming
+    /// them: a leading `Content-Length` header (any case) selects header
+    /// framing, anything else NDJSON. Blocks until input is available.
+    pub fn detect(input: &mut impl BufRead) -> io::Result<Framing> {
+        const HEADER: &[u8] = b"content-length";
+        let head = loop {
+            match input.fill_buf() {
+                Ok(head) => break head,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        };
+        let start = head.iter().position(|b| !b.is_ascii_whitespace());
+        let is_header = start.is_some_and(|start| {
+            let head = &head[start..];
+            let n = head.len().min(HEADER.len());
+            n > 0 && head[..n].eq_ignore_ascii_case(&HEADER[..n])
+        });
+        Ok(if is_header {
+            Framing::ContentLength
+        } else {
+            Framing::Ndjson
+        })
+    }
+
+    /// Write one framed message and flush.
+    pub fn write(self, output: &mut impl Write, json: &str) -> io::Result<()> {
+        match self {
+            Framing::Ndjson => {
+                output.write_all(json.as_bytes())?;
+                output.write_all(b"\n")?;
+            }
+            Framing::ContentLength => {
+                write!(output, "Content-Length: {}\r\n\r\n", json.len())?;
+                output.write_all(json.as_bytes())?;
+            }
+        }
+        output.flush()
+    }
+}
+
+/// One line gathered across buffer fills, keeping at most one byte past
+/// `limit` so an over-limit line never grows the buffer further. Shared by
+/// the blocking readers here and the async `transport::StdioTransport`, which
+/// differ only in how they fill their buffers.
+#[derive(Debug, Default)]
+pub(crate) struct LineScan {
+    len: usize,
+    ends_with_cr: bool,
+    started: bool,
+}
+
+impl LineScan {
+    /// Take `available` up to and including the next `\n` into `buf`.
+    /// Returns how many bytes to consume and whether the line is complete;
+    /// an empty `available` is EOF.
+    pub(crate) fn feed(
+        &mut self,
+        available: &[u8],
+        buf: &mut Vec<u8>,
+        limit: usize,
+    ) -> (usize, bool) {
+        if available.is_empty() {
+            return (0, true);
+        }
+        self.started = true;
+        let (chunk, used, complete) = match available.iter().position(|&b| b == b'\n') {
+            Some(i) => (&available[..i], i + 1, true),
+            None => (available, available.len(), false),
+        };
+        if let Some(&last) = chunk.last() {
+            self.ends_with_cr = last == b'\r';
+        }
+        self.len += chunk.len();
+        let room = (limit + 1).saturating_sub(buf.len());
+        buf.extend_from_slice(&chunk[..chunk.len().min(room)]);
+        (used, complete)
+    }
+
+    /// The line's full length without its ending, or `None` if EOF came
+    /// before any byte. A final line without `\n` still counts.
+    pub(crate) fn finish(&self) -> Option<usize> {
+        self.started
+            .then_some(self.len - self.ends_with_cr as usize)
+    }
+}
+
+/// Read up to the next `\n` (or EOF) into `buf` through a [`LineScan`].
+fn read_line(
+    input: &mut impl BufRead,
+    buf: &mut Vec<u8>,
+    limit: usize,
+    bytes_read: &mut u64,
+) -> io::Result<Option<usize>> {
+    buf.clear();
+    let mut scan = LineScan::default();
+    loop {
+        let available = match input.fill_buf() {
+            Ok(available) => available,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        let (used, complete) = scan.feed(available, buf, limit);
+        input.consume(used);
+        *bytes_read += used as u64;
+        if complete {
+            return Ok(scan.finish());
+        }
+    }
+}
+
+/// Check NDJSON line number `line` of length `len`, as read into `buf`: an
+/// over-limit line is a finding; otherwise `buf` is cut to the line and the
+/// result says whether it has content. JSON whitespace is ASCII, so blank
+/// lines can be skipped before UTF-8 validation.
+pub(crate) fn ndjson_line(
+    buf: &mut Vec<u8>,
+    len: usize,
+    line: usize,
+    max_frame_bytes: usize,
+) -> Result<bool, Box<ValidationFinding>> {
+    if let Some(finding) = validation::validate_size(Some(max_frame_bytes), Some(line), len) {
+        return Err(Box::new(finding));
+    }
+    buf.truncate(len);
+    Ok(!buf.iter().all(u8::is_ascii_whitespace))
+}
+
+fn io_error(e: io::Error) -> ValidationFinding {
+    validation::transport_error("ACP.TRANSPORT.IO_ERROR", format!("Read failed: {e}"), None)
+}
+
+pub(crate) fn utf8_frame(buf: &[u8], line: usize) -> Result<Frame<'_>, Box<ValidationFinding>> {
+    match std::str::from_utf8(buf) {
+        Ok(text) => Ok(Frame { line, text }),
+        Err(e) => Err(Box::new(validation::transport_error(
+            "ACP.TRANSPORT.INVALID_UTF8",
+            format!("Frame is not valid UTF-8 after {} bytes", e.valid_up_to()),
+            Some(line),
+        ))),
+    }
+}
+
+// -------------
+// Readers
+// -------------
+
+/// One frame borrowed from the reader's buffer, without its line ending.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frame<'a> {
+    /// 1-based position in the stream: the line number for NDJSON (blank
+    /// lines included), the frame ordinal for `Content-Length`.
+    pub line: usize,
+    pub text: &'a str,
+}
+
+pub struct NdjsonReader<R> {
+    input: R,
+    max_frame_bytes: usize,
+    buf: Vec<u8>,
+    line: usize,
+    bytes_read: u64,
+    done: bool,
+}
+
+impl<R: BufRead> NdjsonReader<R> {
+    pub fn new(input: R, max_frame_bytes: usize) -> Self {
+        NdjsonReader {
+            input,
+            max_frame_bytes,
+            buf: Vec::new(),
+            line: 0,
+            bytes_read: 0,
+            done: false,
+        }
+    }
+
+    /// Bytes consumed from the input so far, line endings included.
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    /// The next non-blank frame, a Transport-lane finding for a frame that
+    /// is over the limit or not UTF-8, or `None` once the input is exhausted.
+    /// An I/O error is reported once and ends the stream.
+    pub fn next_frame(&mut self) -> Option<Result<Frame<'_>, ValidationFinding>> {
+        loop {
+            if self.done {
+                return None;
+            }
+            let len = match read_line(
+                &mut self.input,
+                &mut self.buf,
+                self.max_frame_bytes,
+                &mut self.bytes_read,
+            ) {
+                Ok(Some(len)) => len,
+                Ok(None) => {
+                    self.done = true;
+                    return None;
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(io_error(e)));
+                }
+            };
+            self.line += 1;
+            let line = self.line;
+
+            match ndjson_line(&mut self.buf, len, line, self.max_frame_bytes) {
+                Err(finding) => return Some(Err(*finding)),
+                Ok(false) => continue,
+                Ok(true) => return Some(utf8_frame(&self.buf, line).map_err(|f| *f)),
+            }
+        }
+    }
+}
+
+/// Reads `Content-Length: N` header blocks, each followed by N body bytes.
+/// Over-limit bodies are skipped without buffering; a missing or malformed
+/// header, or a body cut short by EOF, loses the framing and ends the stream.
+pub struct ContentLengthReader<R> {
+    input: R,
+    max_frame_bytes: usize,
+    buf: Vec<u8>,
+    frame: usize,
+    bytes_read: u64,
+    done: bool,
+}
+
+impl<R: BufRead> ContentLengthReader<R> {
+    pub fn new(input: R, max_frame_bytes: usize) -> Self {
+        ContentLengthReader {
+            input,
+            max_frame_bytes,
+            buf: Vec::new(),
+            frame: 0,
+            bytes_read: 0,
+            done: false,
+        }
+    }
+
+    /// Bytes consumed from the input so far, headers included.
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    /// A finding that ends the stream.
+    fn fatal(&mut self, code: &str, message: String) -> Box<ValidationFinding> {
+        self.done = true;
+        Box::new(validation::transport_error(code, message, Some(self.frame)))
+    }
+
+    /// Parse one header block. `Ok(None)` is a clean EOF before any header.
+    fn read_headers(&mut self) -> Result<Option<usize>, Box<ValidationFinding>> {
+        let mut length = None;
+        let mut in_block = false;
+        loop {
+            let line = read_line(
+                &mut self.input,
+                &mut self.buf,
+                MAX_HEADER_LINE_BYTES,
+                &mut self.bytes_read,
+            )
+            .map_err(|e| {
+                self.done = true;
+                Box::new(io_error(e))
+            })?;
+            let Some(len) = line else {
+                if in_block {
+                    return Err(self.fatal(
+                        "ACP.TRANSPORT.TRUNCATED_FRAME",
+                        "Input ended inside a header block".to_owned(),
+                    ));
+                }
+                return Ok(None);
+            };
+            if self.buf.iter().all(u8::is_ascii_whitespace) {
+                // Blank lines before a block are tolerated; one after ends it.
+                if !in_block {
+                    continue;
+                }
+                return match length {
+                    Some(length) => Ok(Some(length)),
+                    None => Err(self.fatal(
+                        "ACP.TRANSPORT.MISSING_CONTENT_LENGTH",
+                        "Header block has no Content-Length".to_owned(),
+                    )),
+                };
+            }
+            if !in_block {
+                in_block = true;
+                self.frame += 1;
+            }
+
+            let header = (len <= MAX_HEADER_LINE_BYTES)
+                .then(|| std::str::from_utf8(&self.buf[..len]).ok())
+                .flatten()
+                .and_then(|line| line.split_once(':'));
+            let Some((name, value)) = header else {
+                return Err(self.fatal(
+                    "ACP.TRANSPORT.INVALID_HEADER",
+                    "Expected a 'Name: value' header line".to_owned(),
+                ));
+            };
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                match value.trim().parse::<usize>() {
+                    Ok(n) => length = Some(n),
+                    Err(_) => {
+                        let message = format!("Invalid Content-Length '{}'", value.trim());
+                        return Err(self.fatal("ACP.TRANSPORT.INVALID_HEADER", message));
+                    }
+                }
+            }
+        }
+    }
+
+    /// The next frame, a Transport-lane finding, or `None` at the end.
+    pub fn next_frame(&mut self) -> Option<Result<Frame<'_>, ValidationFinding>> {
+        if self.done {
+            return None;
+        }
+        let length = match self.read_headers() {
+            Ok(Some(length)) => length,
+            Ok(None) => {
+                self.done = true;
+                return None;
+            }
+            Err(finding) => return Some(Err(*finding)),
+        };
+        let frame = self.frame;
+
+        if let Some(finding) =
+            validation::validate_size(Some(self.max_frame_bytes), Some(frame), length)
+        {
+            let skipped = io::copy(&mut (&mut self.input).take(length as u64), &mut io::sink());
+            match skipped {
+                Ok(n) => {
+                    self.bytes_read += n;
+                    if n < length as u64 {
+                        self.done = true;
+                    }
+                }
+                Err(_) => self.done = true,
+            }
+            return Some(Err(finding));
+        }
+
+        self.buf.resize(length, 0);
+        if let Err(e) = self.input.read_exact(&mut self.buf) {
+            return Some(Err(if e.kind() == io::ErrorKind::UnexpectedEof {
+                *self.fatal(
+                    "ACP.TRANSPORT.TRUNCATED_FRAME",
+                    format!("Input ended inside a {length}-byte body"),
+                )
+            } else {
+                self.done = true;
+                io_error(e)
+            }));
+        }
+        self.bytes_read += length as u64;
+        Some(utf8_frame(&self.buf, frame).map_err(|f| *f))
+    }
+}
+
+/// A reader for either framing.
+pub enum FrameReader<R> {
+    Ndjson(NdjsonReader<R>),
+    ContentLength(ContentLengthReader<R>),
+}
+
+impl<R: BufRead> FrameReader<R> {
+    pub fn new(framing: Framing, input: R, max_frame_bytes: usize) -> Self {
+        match framing {
+            Framing::Ndjson => FrameReader::Ndjson(NdjsonReader::new(input, max_frame_bytes)),
+            Framing::ContentLength => {
+                FrameReader::ContentLength(ContentLengthReader::new(input, max_frame_bytes))
+            }
+        }
+    }
+
+    /// A reader for the framing [`Framing::detect`] finds in `input`.
+    pub fn detect(mut input: R, max_frame_bytes: usize) -> io::Result<Self> {
+        let framing = Framing::detect(&mut input)?;
+        Ok(FrameReader::new(framing, input, max_frame_bytes))
+    }
+
+    pub fn framing(&self) -> Framing {
+        match self {
+            FrameReader::Ndjson(_) => Framing::Ndjson,
+            FrameReader::ContentLength(_) => Framing::ContentLength,
+        }
+    }
+
+    pub fn bytes_read(&self) -> u64 {
+        match self {
+            FrameReader::Ndjson(r) => r.bytes_read(),
+            FrameReader::ContentLength(r) => r.bytes_read(),
+        }
+    }
+
+    pub fn next_frame(&mut self) -> Option<Result<Frame<'_>, ValidationFinding>> {
+        match self {
+            FrameReader::Ndjson(r) => r.next_frame(),
+            FrameReader::ContentLength(r) => r.next_frame(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufReader;
+
+    /// Frame texts, or the finding code for rejected frames.
+    fn frames(input: &[u8], capacity: usize, max: usize) -> Vec<(usize, String)> {
+        let mut reader = NdjsonReader::new(BufReader::with_capacity(capacity, input), max);
+        let mut out = Vec::new();
+        while let Some(frame) = reader.next_frame() {
+            out.push(match frame {
+                Ok(f) => (f.line, f.text.to_owned()),
+                Err(f) => (f.trace_index.unwrap(), f.failure.unwrap().code),
+            });
+        }
+        assert_eq!(reader.bytes_read(), input.len() as u64);
+        out
+    }
+
+    #[test]
+    fn reassembles_partial_reads_and_strips_line_endings() {
+        let input = b"{\"a\":1}\r\n\n   \r\n{\"b\":2}\n{\"c\":3}";
+        let expected = vec![
+            (1, r#"{"a":1}"#.to_owned()),
+            (4, r#"{"b":2}"#.to_owned()),
+            (5, r#"{"c":3}"#.to_owned()),
+        ];
+        // A 3-byte buffer splits every frame across several reads.
+        assert_eq!(frames(input, 3, 64), expected);
+        assert_eq!(frames(input, 8192, 64), expected);
+    }
+
+    #[test]
+    fn reports_bad_frames_and_recovers() {
+        // Line 2 is exactly at the limit once its CRLF is stripped.
+        let input = b"0123456789\n01234567\r\n\xff\xfe\n{}\n";
+        assert_eq!(
+            frames(input, 4, 8),
+            vec![
+                (1, "ACP.TRANSPORT.MAX_MESSAGE_BYTES_EXCEEDED".to_owned()),
+                (2, "01234567".to_owned()),
+                (3, "ACP.TRANSPORT.INVALID_UTF8".to_owned()),
+                (4, "{}".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_content_length_frames_and_detects_framing() {
+        let mut input = Vec::new();
+        for json in [r#"{"a":1}"#, "0123456789", r#"{"é":2}"#] {
+            Framing::ContentLength.write(&mut input, json).unwrap();
+        }
+        input.extend_from_slice(b"Content-Length: 6\r\n\r\n{}");
+
+        let mut reader = FrameReader::detect(BufReader::with_capacity(5, &input[..]), 8).unwrap();
+        assert_eq!(reader.framing(), Framing::ContentLength);
+        let mut out = Vec::new();
+        while let Some(frame) = reader.next_frame() {
+            out.push(match frame {
+                Ok(f) => (f.line, f.text.to_owned()),
+                Err(f) => (f.trace_index.unwrap(), f.failure.unwrap().code),
+            });
+        }
+        assert_eq!(
+            out,
+            vec![
+                (1, r#"{"a":1}"#.to_owned()),
+                (2, "ACP.TRANSPORT.MAX_MESSAGE_BYTES_EXCEEDED".to_owned()),
+                (3, r#"{"é":2}"#.to_owned()),
+                (4, "ACP.TRANSPORT.TRUNCATED_FRAME".to_owned()),
+            ]
+        );
+
+        let mut ndjson = BufReader::new(&b"  {\"a\":1}\n"[..]);
+        assert_eq!(Framing::detect(&mut ndjson).unwrap(), Framing::Ndjson);
+    }
+}
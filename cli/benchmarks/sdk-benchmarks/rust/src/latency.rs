@@ -0,0 +1,106 @@
+//! Per-message latency recording, one HDR histogram per ACP method, so a run
+//! shows which message shapes dominate decode cost.
+
+use hdrhistogram::Histogram;
+use serde::{Serialize, Serializer};
+use std::collections::BTreeMap;
+
+/// Highest trackable latency; slower samples are clamped to it.
+const MAX_NS: u64 = 60_000_000_000;
+
+/// Key for messages that failed to decode (their method is not known).
+pub const DECODE_ERROR: &str = "(decode error)";
+
+/// Latency distribution of one method, in nanoseconds.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MethodSummary {
+    pub count: u64,
+    pub min_ns: u64,
+    pub mean_ns: f64,
+    pub p50_ns: u64,
+    pub p90_ns: u64,
+    pub p99_ns: u64,
+    pub p999_ns: u64,
+    pub max_ns: u64,
+}
+
+/// Histograms keyed by method. A run sees a handful of methods, so a linear
+/// scan beats hashing and avoids allocating a key per recorded message.
+#[derive(Debug, Default)]
+pub struct MethodLatency {
+    by_method: Vec<(String, Histogram<u64>)>,
+}
+
+impl MethodLatency {
+    pub fn record(&mut self, method: &str, ns: u64) {
+        let histogram = match self.by_method.iter().position(|(m, _)| m == method) {
+            Some(i) => &mut self.by_method[i].1,
+            None => {
+                let histogram =
+                    Histogram::new_with_bounds(1, MAX_NS, 3).expect("valid histogram bounds");
+                self.by_method.push((method.to_owned(), histogram));
+                &mut self.by_method.last_mut().unwrap().1
+            }
+        };
+        histogram.saturating_record(ns.max(1));
+    }
+
+    pub fn summary(&self) -> BTreeMap<&str, MethodSummary> {
+        self.by_method
+            .iter()
+            .map(|(method, h)| {
+                let summary = MethodSummary {
+                    count: h.len(),
+                    min_ns: h.min(),
+                    mean_ns: h.mean(),
+                    p50_ns: h.value_at_quantile(0.5),
+                    p90_ns: h.value_at_quantile(0.9),
+                    p99_ns: h.value_at_quantile(0.99),
+                    p999_ns: h.value_at_quantile(0.999),
+                    max_ns: h.max(),
+                };
+                (method.as_str(), summary)
+            })
+            .collect()
+    }
+}
+
+/// Serializes as the per-method summary.
+impl Serialize for MethodLatency {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.summary().serialize(serializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_samples_by_method() {
+        let mut latency = MethodLatency::default();
+        for ns in 1..=100 {
+            latency.record("session/update", ns * 10);
+        }
+        latency.record("initialize", 5_000_000);
+
+        let summary = latency.summary();
+        assert_eq!(
+            summary.keys().copied().collect::<Vec<_>>(),
+            ["initialize", "session/update"]
+        );
+
+        let update = &summary["session/update"];
+        assert_eq!(
+            (update.count, update.min_ns, update.max_ns),
+            (100, 10, 1000)
+        );
+        assert_eq!(
+            (update.p50_ns, update.p90_ns, update.p99_ns),
+            (500, 900, 990)
+        );
+        let init = &summary["initialize"];
+        assert_eq!(init.count, 1);
+        assert!(init.max_ns.abs_diff(5_000_000) < 5_000);
+    }
+}
@@ -0,0 +1,25 @@
+//! ACP benchmark library: the message model, codec, scenario loading and
+//! measurement harness behind the `acp-benchmark` CLI.
+//! Mirrors the F# benchmark for cross-language comparison
+
+pub mod alloc;
+pub mod borrowed;
+pub mod codec;
+pub mod conformance;
+pub mod connection;
+pub mod domain;
+pub mod framing;
+pub mod latency;
+pub mod modes;
+pub mod permissions;
+pub mod protocol;
+pub mod samples;
+pub mod scenario;
+pub mod schema;
+pub mod session;
+pub mod stats;
+pub mod stdio;
+pub mod stub;
+pub mod trace;
+pub mod transport;
+pub mod validation;
@@ -1,33 +1,20 @@
 //! Rust SDK Benchmark CLI
 //! Mirrors the F# benchmark for cross-language comparison
 
+use acp_benchmark::framing::{self, Framing};
+use acp_benchmark::scenario::{self, Scenario};
+use acp_benchmark::schema::Schema;
+use acp_benchmark::stats::Harness;
+use acp_benchmark::{alloc, conformance, modes, samples, stub};
 use clap::{Parser, ValueEnum};
-use serde_json::{json, Value};
-use std::time::Instant;
+use serde::Serialize;
+use serde_json::json;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
 
-/// Sample ACP messages
-const INITIALIZE_REQUEST: &str = r#"{"jsonrpc":"2.0","method":"initialize","params":{"protocolVersion":1,"clientCapabilities":{"fs":{"readTextFile":true,"writeTextFile":true},"terminal":true},"clientInfo":{"name":"benchmark","version":"1.0.0"}},"id":1}"#;
-
-const SESSION_NEW_REQUEST: &str = r#"{"jsonrpc":"2.0","method":"session/new","params":{"cwd":"/tmp","mcpServers":[]},"id":1}"#;
-
-const SESSION_UPDATE_NOTIFICATION: &str = r#"{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"sess-001","update":{"sessionUpdate":"agent_message_chunk","content":{"type":"text","text":"Hello, this is a test message."}}}}"#;
-
-const PROMPT_REQUEST: &str = r#"{"jsonrpc":"2.0","method":"session/prompt","params":{"sessionId":"sess-001","prompt":[{"type":"text","text":"What is 2+2?"}]},"id":2}"#;
-
-fn make_token_update(token_count: usize) -> String {
-    let text = "word ".repeat(token_count);
-    serde_json::to_string(&json!({
-        "jsonrpc": "2.0",
-        "method": "session/update",
-        "params": {
-            "sessionId": "sess-001",
-            "update": {
-                "sessionUpdate": "agent_message_chunk",
-                "content": {"type": "text", "text": text}
-            }
-        }
-    })).unwrap()
-}
+#[global_allocator]
+static GLOBAL: alloc::CountingAlloc = alloc::CountingAlloc;
 
 #[derive(Debug, Clone, ValueEnum)]
 enum Mode {
@@ -36,6 +23,44 @@ enum Mode {
     Throughput,
     Codec,
     Tokens,
+    /// Owned vs zero-copy decoding of streamed text chunks
+    ZeroCopy,
+    Protocol,
+    /// Decode-only vs decode plus pinned ACP JSON Schema validation
+    Schema,
+    Replay,
+    Validate,
+    Memory,
+    Stdio,
+    /// Decode messages received through the memory, duplex and NDJSON transports
+    Transport,
+    /// Prompt roundtrips between in-process client and stub agent connections
+    Connection,
+    /// Decode, re-encode and compare every scenario and trace message (or --scenario)
+    Conformance,
+    /// Frame and decode NDJSON straight from stdin (or --scenario) in one pass
+    Pipe,
+    /// Serve as the bundled stub agent on stdin/stdout (used by stdio mode)
+    #[value(hide = true)]
+    StubAgent,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum FramingArg {
+    /// Detect from the input (pipe); both (throughput); NDJSON (stdio)
+    Auto,
+    Ndjson,
+    ContentLength,
+}
+
+impl FramingArg {
+    fn framing(self) -> Option<Framing> {
+        match self {
+            FramingArg::Auto => None,
+            FramingArg::Ndjson => Some(Framing::Ndjson),
+            FramingArg::ContentLength => Some(Framing::ContentLength),
+        }
+    }
 }
 
 #[derive(Parser, Debug)]
@@ -50,155 +75,231 @@ struct Args {
 
     #[arg(long, default_value = "100")]
     tokens: usize,
-}
 
-fn run_cold_start() {
-    let start = Instant::now();
+    /// NDJSON scenario file (a JSONL trace in replay mode; either works in
+    /// protocol and validate modes); defaults to stdin when piped, else
+    /// built-in messages
+    #[arg(long)]
+    scenario: Option<PathBuf>,
 
-    // Parse an initialize request
-    let parsed: Value = serde_json::from_str(INITIALIZE_REQUEST).unwrap();
-    let response = json!({"jsonrpc": "2.0", "result": {"protocolVersion": 1}, "id": parsed["id"]});
-    let _encoded = serde_json::to_string(&response).unwrap();
+    /// Untimed runs before measuring
+    #[arg(long, default_value = "0")]
+    warmup: usize,
 
-    let elapsed = start.elapsed();
-    let elapsed_ms = elapsed.as_millis();
+    /// Timed runs summarised in the `stats` field
+    #[arg(long, default_value = "1")]
+    iterations: usize,
 
-    println!(
-        r#"{{"status":"ok","mode":"cold-start","elapsed_ms":{}}}"#,
-        elapsed_ms
-    );
-}
+    /// Tokens mode: also fold each update into per-session state
+    #[arg(long)]
+    track_state: bool,
 
-fn run_roundtrip() {
-    let start = Instant::now();
+    /// Replay mode: wait between frames as recorded by their `ts` fields
+    #[arg(long)]
+    honor_timestamps: bool,
 
-    let parsed: Value = serde_json::from_str(SESSION_NEW_REQUEST).unwrap();
-    let response = json!({"jsonrpc": "2.0", "result": {"sessionId": "sess-benchmark"}, "id": parsed["id"]});
-    let _encoded = serde_json::to_string(&response).unwrap();
+    /// Message framing for the pipe, stdio and throughput modes
+    #[arg(long, value_enum, default_value = "auto")]
+    framing: FramingArg,
 
-    let elapsed = start.elapsed();
-    let elapsed_ms = elapsed.as_millis();
+    /// Pipe mode: frames over this many bytes are rejected
+    #[arg(long, default_value_t = framing::DEFAULT_MAX_FRAME_BYTES)]
+    max_frame_bytes: usize,
 
-    println!(
-        r#"{{"status":"ok","mode":"roundtrip","elapsed_ms":{}}}"#,
-        elapsed_ms
-    );
+    /// Stdio mode: agent command to spawn (after `--`); defaults to the bundled stub
+    #[arg(last = true)]
+    agent: Vec<String>,
 }
 
-fn run_throughput(count: usize) {
-    let messages = [
-        INITIALIZE_REQUEST,
-        SESSION_NEW_REQUEST,
-        SESSION_UPDATE_NOTIFICATION,
-        PROMPT_REQUEST,
-    ];
-
-    let start = Instant::now();
-    let mut decoded = 0usize;
-
-    for i in 0..count {
-        let msg = messages[i % messages.len()];
-        let _: Value = serde_json::from_str(msg).unwrap();
-        decoded += 1;
-    }
-
-    let elapsed = start.elapsed();
-    let elapsed_ms = elapsed.as_millis();
-    let elapsed_sec = elapsed.as_secs_f64();
-    let msgs_per_sec = if elapsed_sec > 0.0 {
-        (decoded as f64 / elapsed_sec) as u64
-    } else {
-        decoded as u64 * 1000
-    };
+/// A result line: the mode's report after `status` and `mode`.
+#[derive(Serialize)]
+struct Line<'a, R> {
+    status: &'static str,
+    mode: &'a str,
+    #[serde(flatten)]
+    report: R,
+}
 
+/// Report a failure on stdout in the same single-line JSON shape as results.
+fn fail(mode: &str, error: impl std::fmt::Display) -> ! {
     println!(
-        r#"{{"status":"ok","mode":"throughput","count":{},"elapsed_ms":{},"msgs_per_sec":{}}}"#,
-        decoded, elapsed_ms, msgs_per_sec
+        "{}",
+        json!({"status": "error", "mode": mode, "error": error.to_string()})
     );
+    std::process::exit(1);
 }
 
-fn run_codec(count: usize) {
-    let messages = [
-        INITIALIZE_REQUEST,
-        SESSION_NEW_REQUEST,
-        SESSION_UPDATE_NOTIFICATION,
-        PROMPT_REQUEST,
-    ];
-
-    let start = Instant::now();
-    let mut ops = 0usize;
-
-    for i in 0..count {
-        let msg = messages[i % messages.len()];
-
-        // Decode
-        let _: Value = serde_json::from_str(msg).unwrap();
-        ops += 1;
-
-        // Encode
-        let response = json!({"jsonrpc": "2.0", "result": {"sessionId": "sess-bench"}, "id": i});
-        let _ = serde_json::to_string(&response).unwrap();
-        ops += 1;
+fn print<R: Serialize>(mode: &str, report: Result<R, modes::BenchError>) {
+    match report {
+        Ok(report) => println!(
+            "{}",
+            serde_json::to_string(&Line {
+                status: "ok",
+                mode,
+                report
+            })
+            .unwrap()
+        ),
+        Err(e) => fail(mode, e),
     }
+}
 
-    let elapsed = start.elapsed();
-    let elapsed_ms = elapsed.as_millis();
-    let elapsed_sec = elapsed.as_secs_f64();
-    let ops_per_sec = if elapsed_sec > 0.0 {
-        (ops as f64 / elapsed_sec) as u64
+/// Spawn the agent (the bundled stub when none is given) and run stdio mode.
+fn run_stdio(count: usize, agent: &[String], framing: Framing) {
+    let report = if agent.is_empty() {
+        let exe = std::env::current_exe().unwrap_or_else(|e| fail("stdio", e));
+        let command = [
+            exe.display().to_string(),
+            "--mode".into(),
+            "stub-agent".into(),
+        ];
+        modes::stdio(&command, count, framing).map(|report| modes::StdioReport {
+            agent: "stub".to_owned(),
+            ..report
+        })
     } else {
-        ops as u64 * 1000
+        modes::stdio(agent, count, framing)
     };
-
-    println!(
-        r#"{{"status":"ok","mode":"codec","ops":{},"elapsed_ms":{},"ops_per_sec":{}}}"#,
-        ops, elapsed_ms, ops_per_sec
-    );
+    print("stdio", report);
 }
 
-fn run_tokens(count: usize, tokens_per_msg: usize) {
-    let message = make_token_update(tokens_per_msg);
-
-    let start = Instant::now();
-    let mut decoded = 0usize;
-    let mut total_tokens = 0usize;
-
-    for _ in 0..count {
-        let _: Value = serde_json::from_str(&message).unwrap();
-        decoded += 1;
-        total_tokens += tokens_per_msg;
-    }
-
-    let elapsed = start.elapsed();
-    let elapsed_ms = elapsed.as_millis();
-    let elapsed_sec = elapsed.as_secs_f64();
-
-    let tokens_per_sec = if elapsed_sec > 0.0 {
-        (total_tokens as f64 / elapsed_sec) as u64
-    } else {
-        total_tokens as u64 * 1000
+/// Stream `path` (stdin when absent) through the framer without loading it first.
+fn run_pipe(path: Option<&Path>, framing: Option<Framing>, max_frame_bytes: usize) {
+    let report = match path {
+        Some(path) => match File::open(path) {
+            Ok(file) => modes::pipe(
+                path.display().to_string(),
+                BufReader::new(file),
+                framing,
+                max_frame_bytes,
+            ),
+            Err(e) => fail("pipe", e),
+        },
+        None => modes::pipe(
+            "stdin".to_owned(),
+            io::stdin().lock(),
+            framing,
+            max_frame_bytes,
+        ),
     };
+    print("pipe", report);
+}
 
-    let msgs_per_sec = if elapsed_sec > 0.0 {
-        (decoded as f64 / elapsed_sec) as u64
-    } else {
-        decoded as u64 * 1000
+/// Round-trip `path`, or the repository's scenarios and sentinel traces.
+fn run_conformance(path: Option<&Path>) {
+    let files = match path {
+        Some(path) => vec![path.to_path_buf()],
+        None => {
+            let cwd = std::env::current_dir().unwrap_or_else(|e| fail("conformance", e));
+            let root = conformance::find_root(&cwd)
+                .or_else(|| conformance::find_root(Path::new(env!("CARGO_MANIFEST_DIR"))))
+                .unwrap_or_else(|| {
+                    fail(
+                        "conformance",
+                        "repository corpus not found; pass --scenario",
+                    )
+                });
+            conformance::corpus(&root).unwrap_or_else(|e| fail("conformance", e))
+        }
     };
-
-    println!(
-        r#"{{"status":"ok","mode":"tokens","messages":{},"tokens_per_msg":{},"total_tokens":{},"elapsed_ms":{},"tokens_per_sec":{},"msgs_per_sec":{}}}"#,
-        decoded, tokens_per_msg, total_tokens, elapsed_ms, tokens_per_sec, msgs_per_sec
-    );
+    print("conformance", modes::conformance(&files));
 }
 
 fn main() {
     let args = Args::parse();
+    let harness = Harness {
+        warmup: args.warmup,
+        iterations: args.iterations,
+    };
+
+    // These modes talk over stdin/stdout or drive their own messages rather
+    // than reading a scenario.
+    match args.mode {
+        Mode::StubAgent => {
+            if let Err(e) = stub::serve(std::io::stdin().lock(), std::io::stdout().lock()) {
+                eprintln!("Stub agent failed: {e}");
+                std::process::exit(1);
+            }
+            return;
+        }
+        Mode::Stdio => {
+            let framing = args.framing.framing().unwrap_or(Framing::Ndjson);
+            return run_stdio(args.count, &args.agent, framing);
+        }
+        Mode::Conformance => return run_conformance(args.scenario.as_deref()),
+        Mode::Connection => return print("connection", modes::connection(args.count, harness)),
+        Mode::Pipe => {
+            return run_pipe(
+                args.scenario.as_deref(),
+                args.framing.framing(),
+                args.max_frame_bytes,
+            )
+        }
+        _ => {}
+    }
 
+    let loaded = match scenario::load(args.scenario.as_deref()) {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("Failed to load scenario: {e}");
+            std::process::exit(1);
+        }
+    };
+    let scenario = loaded.unwrap_or_else(|| {
+        Scenario::built_in(match args.mode {
+            Mode::ColdStart => vec![samples::INITIALIZE_REQUEST.to_owned()],
+            Mode::Roundtrip => vec![samples::SESSION_NEW_REQUEST.to_owned()],
+            Mode::Throughput
+            | Mode::Codec
+            | Mode::Protocol
+            | Mode::Schema
+            | Mode::Validate
+            | Mode::Memory
+            | Mode::Transport => samples::batch(),
+            Mode::Tokens | Mode::ZeroCopy => vec![samples::token_update(args.tokens)],
+            Mode::Replay => samples::trace(),
+            Mode::Stdio | Mode::Pipe | Mode::Conformance | Mode::Connection | Mode::StubAgent => {
+                unreachable!("handled above")
+            }
+        })
+    });
+
+    let mode = args.mode.to_possible_value().unwrap();
+    let mode = mode.get_name();
+    let count = args.count;
     match args.mode {
-        Mode::ColdStart => run_cold_start(),
-        Mode::Roundtrip => run_roundtrip(),
-        Mode::Throughput => run_throughput(args.count),
-        Mode::Codec => run_codec(args.count),
-        Mode::Tokens => run_tokens(args.count, args.tokens),
+        Mode::ColdStart => print(mode, modes::cold_start(&scenario, harness)),
+        Mode::Roundtrip => print(mode, modes::roundtrip(&scenario, harness)),
+        Mode::Throughput => {
+            let framings = match args.framing.framing() {
+                Some(framing) => vec![framing],
+                None => vec![Framing::Ndjson, Framing::ContentLength],
+            };
+            print(
+                mode,
+                modes::throughput(&scenario, count, &framings, harness),
+            )
+        }
+        Mode::Codec => print(mode, modes::codec(&scenario, count, harness)),
+        Mode::Tokens => print(
+            mode,
+            Ok(modes::tokens(&scenario, count, harness, args.track_state)),
+        ),
+        Mode::ZeroCopy => print(mode, Ok(modes::zero_copy(&scenario, count, harness))),
+        Mode::Protocol => print(mode, modes::protocol(&scenario, count, harness)),
+        Mode::Schema => print(
+            mode,
+            Ok(modes::schema(&scenario, count, harness, &Schema::pinned())),
+        ),
+        Mode::Replay => print(
+            mode,
+            Ok(modes::replay(&scenario, args.honor_timestamps, harness)),
+        ),
+        Mode::Validate => print(mode, modes::validate(&scenario, count, harness)),
+        Mode::Memory => print(mode, Ok(modes::memory(&scenario, count))),
+        Mode::Transport => print(mode, modes::transport(&scenario, count, harness)),
+        Mode::Stdio | Mode::Pipe | Mode::Conformance | Mode::Connection | Mode::StubAgent => {
+            unreachable!("handled above")
+        }
     }
 }
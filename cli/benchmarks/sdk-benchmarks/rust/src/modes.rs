@@ -0,0 +1,1650 @@
+//! Benchmark modes. Each runs over a [`Scenario`] and returns a serializable
+//! report; the CLI prints it as one JSON line after `status` and `mode`.
+
+use crate::alloc;
+use crate::borrowed::{self, BorrowedDecoded};
+use crate::codec::{self, CodecState, DecodeError, Decoded, Direction};
+use crate::conformance::{self, Difference};
+use crate::connection::{AgentConnection, Client, ClientConnection, ConnectionError};
+use crate::domain::{
+    AgentToClientMessage, ClientCapabilities, ContentBlock, InitializeParams, Message,
+    NewSessionParams, RequestId, SessionPromptParams, SessionUpdate, TextContent,
+};
+use crate::framing::{FrameReader, Framing, DEFAULT_MAX_FRAME_BYTES};
+use crate::latency::MethodLatency;
+use crate::protocol;
+use crate::samples;
+use crate::scenario::Scenario;
+use crate::schema::{self, Schema};
+use crate::session::SessionTracker;
+use crate::stats::{Harness, Summary};
+use crate::stdio::{AgentProcess, HarnessError};
+use crate::stub::StubAgent;
+use crate::trace;
+use crate::transport::{DuplexTransport, MemoryTransport, StdioTransport, Transport};
+use crate::validation::{self, ValidationFinding};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::hint::black_box;
+use std::io::{self, BufRead};
+use std::path::PathBuf;
+use std::time::Instant;
+
+#[derive(Debug)]
+pub enum BenchError {
+    Decode(DecodeError),
+    NoDecodableMessages,
+    Io(io::Error),
+    Agent(HarnessError),
+    Connection(ConnectionError),
+    /// The agent answered a setup request (initialize, session/new) with an error.
+    Handshake(&'static str),
+    /// Most messages failed to decode, so a rate would time the error path.
+    MostlyErrors {
+        errors: usize,
+        attempts: usize,
+    },
+}
+
+impl fmt::Display for BenchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BenchError::Decode(e) => e.fmt(f),
+            BenchError::NoDecodableMessages => f.write_str("no decodable messages in scenario"),
+            BenchError::Io(e) => e.fmt(f),
+            BenchError::Agent(e) => e.fmt(f),
+            BenchError::Connection(e) => e.fmt(f),
+            BenchError::Handshake(method) => write!(f, "{method} failed"),
+            BenchError::MostlyErrors { errors, attempts } => {
+                write!(f, "{errors} of {attempts} messages failed to decode")
+            }
+        }
+    }
+}
+
+impl From<DecodeError> for BenchError {
+    fn from(e: DecodeError) -> Self {
+        BenchError::Decode(e)
+    }
+}
+
+impl From<io::Error> for BenchError {
+    fn from(e: io::Error) -> Self {
+        BenchError::Io(e)
+    }
+}
+
+impl From<HarnessError> for BenchError {
+    fn from(e: HarnessError) -> Self {
+        BenchError::Agent(e)
+    }
+}
+
+impl From<ConnectionError> for BenchError {
+    fn from(e: ConnectionError) -> Self {
+        BenchError::Connection(e)
+    }
+}
+
+/// Fail a run in which decode errors outnumber successes.
+fn check_errors(errors: usize, attempts: usize) -> Result<(), BenchError> {
+    if errors > attempts / 2 {
+        return Err(BenchError::MostlyErrors { errors, attempts });
+    }
+    Ok(())
+}
+
+/// The scenario index of the `i`th message a loop handles. Each pass over the
+/// scenario replays a fresh connection, so per-connection `state` (pending
+/// requests, protocol phase) starts over with `fresh()` at index 0 instead of
+/// carrying the last pass's requests into ids the next pass reuses.
+fn replay_pass<S>(i: usize, len: usize, state: &mut S, fresh: impl FnOnce() -> S) -> usize {
+    let idx = i % len;
+    if idx == 0 {
+        *state = fresh();
+    }
+    idx
+}
+
+/// Pair each scenario message with the direction it is decoded in.
+fn with_directions(scenario: &Scenario) -> Vec<(&str, Direction)> {
+    scenario
+        .messages
+        .iter()
+        .map(|m| (m.as_str(), codec::infer_direction(m)))
+        .collect()
+}
+
+/// Decode a whole scenario up front, for modes that measure what happens after
+/// the codec. Lines that parse as trace frames use their recorded direction.
+/// Returns the decoded messages and the number that failed to decode.
+pub fn decode_all(scenario: &Scenario) -> Result<(Vec<Message>, usize), BenchError> {
+    let mut codec_state = CodecState::default();
+    let mut decode_errors = 0usize;
+    let messages: Vec<_> = scenario
+        .messages
+        .iter()
+        .filter_map(|line| {
+            let result = match trace::parse_frame(line) {
+                Ok(frame) => codec::decode(frame.direction, &mut codec_state, &frame.json),
+                Err(_) => codec::decode(codec::infer_direction(line), &mut codec_state, line),
+            };
+            match result {
+                Ok(d) => Some(d.message),
+                Err(_) => {
+                    decode_errors += 1;
+                    None
+                }
+            }
+        })
+        .collect();
+    if messages.is_empty() {
+        return Err(BenchError::NoDecodableMessages);
+    }
+    Ok((messages, decode_errors))
+}
+
+// -------------
+// Single-message modes
+// -------------
+
+#[derive(Debug, Serialize)]
+pub struct SingleReport {
+    pub elapsed_ms: u128,
+    pub stats: Summary,
+}
+
+/// Decode the first scenario message as a client request and encode `result`
+/// as its response.
+fn decode_and_respond(
+    scenario: &Scenario,
+    harness: Harness,
+    result: Value,
+) -> Result<SingleReport, BenchError> {
+    let (outcome, stats) = harness.measure(|| {
+        let decoded = codec::decode(
+            Direction::FromClient,
+            &mut CodecState::default(),
+            &scenario.messages[0],
+        )?;
+        let response = json!({"jsonrpc": "2.0", "result": result, "id": decoded.id});
+        black_box(serde_json::to_string(&response).unwrap());
+        Ok::<_, DecodeError>(())
+    });
+    outcome?;
+
+    Ok(SingleReport {
+        elapsed_ms: stats.mean_ms(),
+        stats,
+    })
+}
+
+/// Decode the first scenario message (an initialize request by default).
+pub fn cold_start(scenario: &Scenario, harness: Harness) -> Result<SingleReport, BenchError> {
+    decode_and_respond(scenario, harness, json!({"protocolVersion": 1}))
+}
+
+pub fn roundtrip(scenario: &Scenario, harness: Harness) -> Result<SingleReport, BenchError> {
+    decode_and_respond(scenario, harness, json!({"sessionId": "sess-benchmark"}))
+}
+
+// -------------
+// Codec loops
+// -------------
+
+#[derive(Debug, Serialize)]
+pub struct ThroughputReport {
+    pub scenario: String,
+    /// Messages decoded.
+    pub count: usize,
+    pub errors: usize,
+    pub elapsed_ms: u128,
+    pub msgs_per_sec: u64,
+    pub stats: Summary,
+    /// Per-message decode latency from a separate, untimed pass.
+    pub latency: MethodLatency,
+    /// The same messages framed into one buffer, then read and decoded.
+    pub framed: BTreeMap<String, FramedRun>,
+}
+
+/// Throughput through a framer, keyed by framing in [`ThroughputReport`].
+#[derive(Debug, Serialize)]
+pub struct FramedRun {
+    pub bytes: usize,
+    pub frames: usize,
+    pub errors: usize,
+    pub elapsed_ms: u128,
+    pub msgs_per_sec: u64,
+    pub mb_per_sec: f64,
+    pub stats: Summary,
+}
+
+fn framed_throughput(
+    messages: &[(&str, Direction)],
+    count: usize,
+    framing: Framing,
+    harness: Harness,
+) -> FramedRun {
+    // Scenario messages are single-line JSON, so either framing carries them.
+    let mut stream = Vec::new();
+    for i in 0..count {
+        let (msg, _) = messages[i % messages.len()];
+        framing.write(&mut stream, msg).expect("writing to a Vec");
+    }
+
+    let ((frames, errors), stats) = harness.measure(|| {
+        let mut reader = FrameReader::new(framing, &stream[..], DEFAULT_MAX_FRAME_BYTES);
+        let mut state = CodecState::default();
+        let mut frames = 0usize;
+        let mut errors = 0usize;
+
+        while let Some(frame) = reader.next_frame() {
+            let ok = match frame {
+                Ok(frame) => {
+                    let idx = replay_pass(frames, messages.len(), &mut state, CodecState::default);
+                    let direction = messages[idx].1;
+                    frames += 1;
+                    black_box(codec::decode(direction, &mut state, frame.text)).is_ok()
+                }
+                Err(_) => false,
+            };
+            errors += !ok as usize;
+        }
+        (frames, errors)
+    });
+
+    let secs = (stats.mean_ns / 1e9).max(f64::MIN_POSITIVE);
+    FramedRun {
+        bytes: stream.len(),
+        frames,
+        errors,
+        elapsed_ms: stats.mean_ms(),
+        msgs_per_sec: stats.per_sec(frames),
+        mb_per_sec: stream.len() as f64 / 1_048_576.0 / secs,
+        stats,
+    }
+}
+
+/// Decode `count` scenario messages with threaded state, then repeat the run
+/// through each of `framings`.
+pub fn throughput(
+    scenario: &Scenario,
+    count: usize,
+    framings: &[Framing],
+    harness: Harness,
+) -> Result<ThroughputReport, BenchError> {
+    let messages = with_directions(scenario);
+
+    let mut state = CodecState::default();
+    let mut decode = |i: usize| {
+        let idx = replay_pass(i, messages.len(), &mut state, CodecState::default);
+        let (msg, direction) = messages[idx];
+        codec::decode(direction, &mut state, msg)
+    };
+
+    let ((decoded, errors), stats) = harness.measure(|| {
+        let mut decoded = 0usize;
+        for i in 0..count {
+            decoded += black_box(decode(i)).is_ok() as usize;
+        }
+        (decoded, count - decoded)
+    });
+    check_errors(errors, count)?;
+    let latency = method_latency(count, decode);
+
+    Ok(ThroughputReport {
+        scenario: scenario.source.to_string(),
+        count: decoded,
+        errors,
+        elapsed_ms: stats.mean_ms(),
+        msgs_per_sec: stats.per_sec(decoded),
+        stats,
+        latency,
+        framed: framings
+            .iter()
+            .map(|&framing| {
+                let run = framed_throughput(&messages, count, framing, harness);
+                (framing.to_string(), run)
+            })
+            .collect(),
+    })
+}
+
+#[derive(Debug, Serialize)]
+pub struct CodecReport {
+    pub scenario: String,
+    pub ops: usize,
+    pub errors: usize,
+    pub elapsed_ms: u128,
+    pub ops_per_sec: u64,
+    pub stats: Summary,
+}
+
+pub fn codec(
+    scenario: &Scenario,
+    count: usize,
+    harness: Harness,
+) -> Result<CodecReport, BenchError> {
+    let messages = with_directions(scenario);
+    let mix = samples::encode_mix();
+
+    let ((ops, errors, decode_errors), stats) = harness.measure(|| {
+        let mut state = CodecState::default();
+        let mut ops = 0usize;
+        let mut errors = 0usize;
+        let mut decode_errors = 0usize;
+
+        for i in 0..count {
+            let idx = replay_pass(i, messages.len(), &mut state, CodecState::default);
+            let (msg, direction) = messages[idx];
+
+            // Decode
+            match codec::decode(direction, &mut state, msg) {
+                Ok(m) => {
+                    black_box(m);
+                    ops += 1;
+                }
+                Err(_) => decode_errors += 1,
+            }
+
+            // Encode
+            let decoded = &mix[i % mix.len()];
+            match codec::encode(decoded.id.as_ref(), &decoded.message) {
+                Ok(json) => {
+                    black_box(json);
+                    ops += 1;
+                }
+                Err(_) => errors += 1,
+            }
+        }
+        (ops, errors + decode_errors, decode_errors)
+    });
+    check_errors(decode_errors, count)?;
+
+    Ok(CodecReport {
+        scenario: scenario.source.to_string(),
+        ops,
+        errors,
+        elapsed_ms: stats.mean_ms(),
+        ops_per_sec: stats.per_sec(ops),
+        stats,
+    })
+}
+
+/// Time `count` calls of `decode` one by one, outside any measured run, so
+/// reading the clock per message does not slow the headline rate.
+fn method_latency(
+    count: usize,
+    mut decode: impl FnMut(usize) -> Result<Decoded, DecodeError>,
+) -> MethodLatency {
+    let mut latency = MethodLatency::default();
+    for i in 0..count {
+        let t = Instant::now();
+        let result = decode(i);
+        let ns = t.elapsed().as_nanos() as u64;
+        match result {
+            Ok(m) => latency.record(m.message.method(), ns),
+            Err(_) => latency.record(crate::latency::DECODE_ERROR, ns),
+        }
+    }
+    latency
+}
+
+/// Whitespace-separated words in an `agent_message_chunk` text block; 0 otherwise.
+fn count_tokens(json: &str) -> usize {
+    match codec::decode(Direction::FromAgent, &mut CodecState::default(), json).map(|d| d.message) {
+        Ok(Message::FromAgent(AgentToClientMessage::SessionUpdate(n))) => match n.update {
+            SessionUpdate::AgentMessageChunk(chunk) => match chunk.content {
+                ContentBlock::Text(t) => t.text.split_whitespace().count(),
+                _ => 0,
+            },
+            _ => 0,
+        },
+        _ => 0,
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct TokensReport {
+    pub scenario: String,
+    pub messages: usize,
+    pub errors: usize,
+    pub tokens_per_msg: usize,
+    pub total_tokens: usize,
+    pub elapsed_ms: u128,
+    pub tokens_per_sec: u64,
+    pub msgs_per_sec: u64,
+    pub stats: Summary,
+    /// Per-message decode latency from a separate, untimed pass.
+    pub latency: MethodLatency,
+    /// Present with `--track-state`: what the tracker held after the last run.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<StateReport>,
+}
+
+/// Size of the session state folded from a tokens run, and what folding cost.
+#[derive(Debug, Serialize)]
+pub struct StateReport {
+    pub sessions: usize,
+    pub updates: usize,
+    pub text_bytes: usize,
+    /// Time spent in the tracker alone, from a separate pass that times
+    /// only the folds into a fresh tracker.
+    pub elapsed_ns: u128,
+    pub ns_per_update: u64,
+}
+
+impl StateReport {
+    fn of(tracker: &SessionTracker, elapsed_ns: u128) -> Self {
+        let updates: usize = tracker.sessions().map(|s| s.updates).sum();
+        StateReport {
+            sessions: tracker.len(),
+            updates,
+            text_bytes: tracker
+                .sessions()
+                .map(|s| s.agent_text.len() + s.user_text.len() + s.thought_text.len())
+                .sum(),
+            elapsed_ns,
+            ns_per_update: elapsed_ns.checked_div(updates as u128).unwrap_or(0) as u64,
+        }
+    }
+}
+
+/// Fold `count` decoded messages into a fresh tracker, timing only the folds.
+fn tracking_ns(
+    count: usize,
+    mut decode: impl FnMut(usize) -> Result<Decoded, DecodeError>,
+) -> u128 {
+    let mut tracker = SessionTracker::new();
+    let mut ns = 0u128;
+    for i in 0..count {
+        if let Ok(m) = decode(i) {
+            let t = Instant::now();
+            tracker.apply_message(&m.message);
+            ns += t.elapsed().as_nanos();
+        }
+    }
+    ns
+}
+
+/// Decode the scenario's updates; with `track_state`, also fold each into a
+/// [`SessionTracker`] inside the timed section.
+pub fn tokens(
+    scenario: &Scenario,
+    count: usize,
+    harness: Harness,
+    track_state: bool,
+) -> TokensReport {
+    let messages = &scenario.messages;
+    // Counted up front so the timed loop measures decoding only.
+    let tokens: Vec<usize> = messages.iter().map(|m| count_tokens(m)).collect();
+
+    let decode = |i: usize| {
+        codec::decode(
+            Direction::FromAgent,
+            &mut CodecState::default(),
+            &messages[i % messages.len()],
+        )
+    };
+
+    let ((decoded, errors, total_tokens, tracker), stats) = harness.measure(|| {
+        let mut decoded = 0usize;
+        let mut errors = 0usize;
+        let mut total_tokens = 0usize;
+        let mut tracker = SessionTracker::new();
+
+        for i in 0..count {
+            match decode(i) {
+                Ok(m) => {
+                    if track_state {
+                        tracker.apply_message(&m.message);
+                    }
+                    black_box(m);
+                    decoded += 1;
+                    total_tokens += tokens[i % messages.len()];
+                }
+                Err(_) => errors += 1,
+            }
+        }
+        (decoded, errors, total_tokens, tracker)
+    });
+    let latency = method_latency(count, decode);
+    let state = track_state.then(|| StateReport::of(&tracker, tracking_ns(count, decode)));
+
+    TokensReport {
+        scenario: scenario.source.to_string(),
+        messages: decoded,
+        errors,
+        tokens_per_msg: total_tokens.checked_div(decoded).unwrap_or(0),
+        total_tokens,
+        elapsed_ms: stats.mean_ms(),
+        tokens_per_sec: stats.per_sec(total_tokens),
+        msgs_per_sec: stats.per_sec(decoded),
+        stats,
+        latency,
+        state,
+    }
+}
+
+// -------------
+// Zero-copy
+// -------------
+
+/// One side of a decode-path comparison (zero-copy, schema).
+#[derive(Debug, Serialize)]
+pub struct DecodePath {
+    pub decoded: usize,
+    pub errors: usize,
+    pub elapsed_ms: u128,
+    pub msgs_per_sec: u64,
+    /// Zero unless [`alloc::CountingAlloc`] is the global allocator.
+    pub allocs_per_msg: f64,
+    pub bytes_per_msg: f64,
+    pub stats: Summary,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ZeroCopyReport {
+    pub scenario: String,
+    pub count: usize,
+    /// Messages the borrowed path decoded without falling back to the codec.
+    pub borrowed_chunks: usize,
+    pub total_tokens: usize,
+    pub owned: DecodePath,
+    pub borrowed: DecodePath,
+    /// Owned mean time over borrowed mean time.
+    pub speedup: f64,
+}
+
+/// Decode `count` messages with threaded state; returns (decoded, errors).
+fn decode_pass(
+    messages: &[(&str, Direction)],
+    count: usize,
+    decode_one: &impl Fn(Direction, &mut CodecState, &str) -> bool,
+) -> (usize, usize) {
+    let mut state = CodecState::default();
+    let mut decoded = 0usize;
+    for i in 0..count {
+        let idx = replay_pass(i, messages.len(), &mut state, CodecState::default);
+        let (msg, direction) = messages[idx];
+        if decode_one(direction, &mut state, msg) {
+            decoded += 1;
+        }
+    }
+    (decoded, count - decoded)
+}
+
+/// Allocations and bytes per message over one [`decode_pass`]; counting must
+/// already be enabled.
+fn allocs_per_msg(
+    messages: &[(&str, Direction)],
+    count: usize,
+    decode_one: &impl Fn(Direction, &mut CodecState, &str) -> bool,
+) -> (f64, f64) {
+    let before = alloc::snapshot();
+    decode_pass(messages, count, decode_one);
+    let delta = alloc::snapshot().since(before);
+    let per_msg = |n: usize| n as f64 / count.max(1) as f64;
+    (per_msg(delta.allocations), per_msg(delta.bytes))
+}
+
+impl DecodePath {
+    fn new(
+        (decoded, errors): (usize, usize),
+        stats: Summary,
+        (allocs_per_msg, bytes_per_msg): (f64, f64),
+    ) -> DecodePath {
+        DecodePath {
+            decoded,
+            errors,
+            elapsed_ms: stats.mean_ms(),
+            msgs_per_sec: stats.per_sec(decoded),
+            allocs_per_msg,
+            bytes_per_msg,
+            stats,
+        }
+    }
+}
+
+/// Compare the owned codec with [`borrowed::decode`] over the same messages.
+/// Both are timed before either is allocation-counted, so counting overhead
+/// stays out of the timings.
+pub fn zero_copy(scenario: &Scenario, count: usize, harness: Harness) -> ZeroCopyReport {
+    let messages = with_directions(scenario);
+    let tokens: Vec<usize> = scenario.messages.iter().map(|m| count_tokens(m)).collect();
+    let is_chunk: Vec<bool> = messages
+        .iter()
+        .map(|&(msg, direction)| {
+            matches!(
+                borrowed::decode(direction, &mut CodecState::default(), msg),
+                Ok(BorrowedDecoded::Chunk(_))
+            )
+        })
+        .collect();
+
+    let owned = |direction: Direction, state: &mut CodecState, msg: &str| {
+        black_box(codec::decode(direction, state, msg)).is_ok()
+    };
+    let zero_copy = |direction: Direction, state: &mut CodecState, msg: &str| {
+        black_box(borrowed::decode(direction, state, msg)).is_ok()
+    };
+
+    let (owned_counts, owned_stats) = harness.measure(|| decode_pass(&messages, count, &owned));
+    let (borrowed_counts, borrowed_stats) =
+        harness.measure(|| decode_pass(&messages, count, &zero_copy));
+
+    alloc::enable();
+    let owned_allocs = allocs_per_msg(&messages, count, &owned);
+    let borrowed_allocs = allocs_per_msg(&messages, count, &zero_copy);
+
+    let speedup = owned_stats.mean_ns / borrowed_stats.mean_ns.max(1.0);
+    let indices = (0..count).map(|i| i % messages.len());
+
+    ZeroCopyReport {
+        scenario: scenario.source.to_string(),
+        count,
+        borrowed_chunks: indices.clone().filter(|&i| is_chunk[i]).count(),
+        total_tokens: indices.map(|i| tokens[i]).sum(),
+        owned: DecodePath::new(owned_counts, owned_stats, owned_allocs),
+        borrowed: DecodePath::new(borrowed_counts, borrowed_stats, borrowed_allocs),
+        speedup,
+    }
+}
+
+// -------------
+// Schema
+// -------------
+
+#[derive(Debug, Serialize)]
+pub struct SchemaReport {
+    pub scenario: String,
+    pub schema_version: &'static str,
+    pub definitions: usize,
+    pub count: usize,
+    /// Messages a schema definition covers, and how many of those violate it.
+    pub checked: usize,
+    pub invalid: usize,
+    pub decode: DecodePath,
+    pub validated: DecodePath,
+    /// Decode-and-validate mean time over decode-only mean time.
+    pub overhead: f64,
+    /// Violations from one pass over the scenario's messages.
+    pub results: Vec<ValidationFinding>,
+}
+
+/// Measure what checking each decoded message against `schema` costs on
+/// top of decoding: the analogue of the Zod parsing the TS SDK does.
+pub fn schema(
+    scenario: &Scenario,
+    count: usize,
+    harness: Harness,
+    schema: &Schema,
+) -> SchemaReport {
+    let messages = with_directions(scenario);
+
+    let mut state = CodecState::default();
+    let mut results = Vec::new();
+    let outcomes: Vec<Option<bool>> = messages
+        .iter()
+        .enumerate()
+        .map(|(index, &(msg, direction))| {
+            let decoded = codec::decode(direction, &mut state, msg).ok()?;
+            let findings = schema.validate(&decoded.message, msg, Some(index))?;
+            let valid = findings.is_empty();
+            results.extend(findings);
+            Some(valid)
+        })
+        .collect();
+
+    let decode_only = |direction: Direction, state: &mut CodecState, msg: &str| {
+        black_box(codec::decode(direction, state, msg)).is_ok()
+    };
+    let validated = |direction: Direction, state: &mut CodecState, msg: &str| match codec::decode(
+        direction, state, msg,
+    ) {
+        Ok(decoded) => {
+            black_box(schema.validate(&decoded.message, msg, None));
+            true
+        }
+        Err(_) => false,
+    };
+
+    let (decode_counts, decode_stats) =
+        harness.measure(|| decode_pass(&messages, count, &decode_only));
+    let (validated_counts, validated_stats) =
+        harness.measure(|| decode_pass(&messages, count, &validated));
+
+    alloc::enable();
+    let decode_allocs = allocs_per_msg(&messages, count, &decode_only);
+    let validated_allocs = allocs_per_msg(&messages, count, &validated);
+
+    let overhead = validated_stats.mean_ns / decode_stats.mean_ns.max(1.0);
+    let indices = (0..count).map(|i| outcomes[i % messages.len()]);
+
+    SchemaReport {
+        scenario: scenario.source.to_string(),
+        schema_version: schema::SCHEMA_VERSION,
+        definitions: schema.definitions(),
+        count,
+        checked: indices.clone().filter(Option::is_some).count(),
+        invalid: indices.filter(|o| *o == Some(false)).count(),
+        decode: DecodePath::new(decode_counts, decode_stats, decode_allocs),
+        validated: DecodePath::new(validated_counts, validated_stats, validated_allocs),
+        overhead,
+        results,
+    }
+}
+
+// -------------
+// Pipe
+// -------------
+
+#[derive(Debug, Serialize)]
+pub struct PipeReport {
+    pub scenario: String,
+    pub framing: String,
+    pub frames: usize,
+    pub bytes: u64,
+    pub decoded: usize,
+    pub errors: usize,
+    pub transport_errors: usize,
+    pub elapsed_ms: u128,
+    pub msgs_per_sec: u64,
+    pub mb_per_sec: f64,
+    pub latency: MethodLatency,
+    /// Transport-lane findings from the framer.
+    pub results: Vec<ValidationFinding>,
+}
+
+/// Frame and decode a stream as it arrives, in a single pass, detecting the
+/// framing when `framing` is `None`. Trace frames use their recorded
+/// direction, as in [`decode_all`].
+pub fn pipe(
+    source: String,
+    input: impl BufRead,
+    framing: Option<Framing>,
+    max_frame_bytes: usize,
+) -> Result<PipeReport, BenchError> {
+    let start = Instant::now();
+    let mut reader = match framing {
+        Some(framing) => FrameReader::new(framing, input, max_frame_bytes),
+        None => FrameReader::detect(input, max_frame_bytes)?,
+    };
+    let mut state = CodecState::default();
+    let mut latency = MethodLatency::default();
+    let mut findings = Vec::new();
+    let mut frames = 0usize;
+    let mut decoded = 0usize;
+    let mut errors = 0usize;
+
+    while let Some(frame) = reader.next_frame() {
+        let frame = match frame {
+            Ok(frame) => frame,
+            Err(finding) => {
+                findings.push(finding);
+                continue;
+            }
+        };
+        frames += 1;
+
+        let t = Instant::now();
+        let result = match trace::parse_frame(frame.text) {
+            Ok(f) => codec::decode(f.direction, &mut state, &f.json),
+            Err(_) => codec::decode(codec::infer_direction(frame.text), &mut state, frame.text),
+        };
+        let ns = t.elapsed().as_nanos() as u64;
+        match result {
+            Ok(m) => {
+                latency.record(m.message.method(), ns);
+                black_box(m);
+                decoded += 1;
+            }
+            Err(_) => {
+                latency.record(crate::latency::DECODE_ERROR, ns);
+                errors += 1;
+            }
+        }
+    }
+
+    let elapsed = start.elapsed();
+    let secs = elapsed.as_secs_f64().max(f64::MIN_POSITIVE);
+    Ok(PipeReport {
+        scenario: source,
+        framing: reader.framing().to_string(),
+        frames,
+        bytes: reader.bytes_read(),
+        decoded,
+        errors,
+        transport_errors: findings.len(),
+        elapsed_ms: elapsed.as_millis(),
+        msgs_per_sec: (frames as f64 / secs) as u64,
+        mb_per_sec: reader.bytes_read() as f64 / 1_048_576.0 / secs,
+        latency,
+        results: findings,
+    })
+}
+
+// -------------
+// Memory
+// -------------
+
+/// Allocation totals for one kind of operation.
+#[derive(Debug, Default, Serialize)]
+pub struct AllocTotals {
+    pub ops: usize,
+    pub allocs: usize,
+    pub bytes: usize,
+    pub allocs_per_op: f64,
+    pub bytes_per_op: f64,
+}
+
+impl AllocTotals {
+    fn add(&mut self, delta: alloc::Snapshot) {
+        self.ops += 1;
+        self.allocs += delta.allocations;
+        self.bytes += delta.bytes;
+        self.allocs_per_op = self.allocs as f64 / self.ops as f64;
+        self.bytes_per_op = self.bytes as f64 / self.ops as f64;
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct MemoryReport {
+    pub scenario: String,
+    pub count: usize,
+    pub errors: usize,
+    pub decode: AllocTotals,
+    pub encode: AllocTotals,
+    /// Most heap held at once during the run, above what was live before it.
+    pub peak_heap_bytes: usize,
+    /// Most heap held at once by a single decode or encode.
+    pub peak_op_heap_bytes: usize,
+    pub peak_rss_bytes: Option<u64>,
+    pub by_method: BTreeMap<String, AllocTotals>,
+}
+
+/// Heap peaks seen by [`memory`]: over the run, and within one operation.
+#[derive(Default)]
+struct Peaks {
+    baseline: usize,
+    run: usize,
+    op: usize,
+}
+
+impl Peaks {
+    /// Start tracking one operation; returns the bytes live before it.
+    fn start(&self) -> usize {
+        let live = alloc::live_heap_bytes();
+        alloc::reset_peak();
+        live
+    }
+
+    fn finish(&mut self, live_before: usize) {
+        let peak = alloc::peak_heap_bytes();
+        self.run = self.run.max(peak.saturating_sub(self.baseline));
+        self.op = self.op.max(peak.saturating_sub(live_before));
+    }
+}
+
+/// Allocations per decoded scenario message and encoded [`samples::encode_mix`]
+/// message. Counts stay at zero unless [`alloc::CountingAlloc`] is the binary's
+/// global allocator.
+pub fn memory(scenario: &Scenario, count: usize) -> MemoryReport {
+    let messages = with_directions(scenario);
+    let mix = samples::encode_mix();
+
+    alloc::enable();
+    let mut state = CodecState::default();
+    let mut decode = AllocTotals::default();
+    let mut encode = AllocTotals::default();
+    let mut by_method: BTreeMap<String, AllocTotals> = BTreeMap::new();
+    let mut errors = 0usize;
+    let mut peaks = Peaks {
+        baseline: alloc::live_heap_bytes(),
+        ..Peaks::default()
+    };
+
+    for i in 0..count {
+        let idx = replay_pass(i, messages.len(), &mut state, CodecState::default);
+        let (msg, direction) = messages[idx];
+
+        // Only the operation itself sits between the snapshots; bookkeeping
+        // allocations fall outside them.
+        let live = peaks.start();
+        let before = alloc::snapshot();
+        let result = codec::decode(direction, &mut state, msg);
+        let delta = alloc::snapshot().since(before);
+        peaks.finish(live);
+        match &result {
+            Ok(d) => {
+                decode.add(delta);
+                let method = d.message.method();
+                match by_method.get_mut(method) {
+                    Some(totals) => totals.add(delta),
+                    None => {
+                        let mut totals = AllocTotals::default();
+                        totals.add(delta);
+                        by_method.insert(method.to_owned(), totals);
+                    }
+                }
+            }
+            Err(_) => errors += 1,
+        }
+        drop(black_box(result));
+
+        let decoded = &mix[i % mix.len()];
+        let live = peaks.start();
+        let before = alloc::snapshot();
+        let result = codec::encode(decoded.id.as_ref(), &decoded.message);
+        let delta = alloc::snapshot().since(before);
+        peaks.finish(live);
+        match result {
+            Ok(encoded) => {
+                encode.add(delta);
+                drop(black_box(encoded));
+            }
+            Err(_) => errors += 1,
+        }
+    }
+
+    MemoryReport {
+        scenario: scenario.source.to_string(),
+        count,
+        errors,
+        decode,
+        encode,
+        peak_heap_bytes: peaks.run,
+        peak_op_heap_bytes: peaks.op,
+        peak_rss_bytes: alloc::peak_rss_bytes(),
+        by_method,
+    }
+}
+
+// -------------
+// Stdio
+// -------------
+
+#[derive(Debug, Serialize)]
+pub struct StdioReport {
+    pub agent: String,
+    pub framing: String,
+    pub prompts: usize,
+    pub errors: usize,
+    pub updates: usize,
+    pub agent_exit_code: Option<i32>,
+    pub ready_ms: u128,
+    pub elapsed_ms: u128,
+    pub latency: MethodLatency,
+}
+
+/// Spawn `command`, then time initialize, session/new and `count`
+/// session/prompt roundtrips over `framing`.
+pub fn stdio(
+    command: &[String],
+    count: usize,
+    framing: Framing,
+) -> Result<StdioReport, BenchError> {
+    let start = Instant::now();
+    let mut process = AgentProcess::spawn(command, framing)?;
+    let mut latency = MethodLatency::default();
+    let mut errors = 0usize;
+    let mut updates = 0usize;
+
+    let mut roundtrip = |process: &mut AgentProcess, method: &str, params: Value| {
+        let exchange = process.request(method, params)?;
+        latency.record(method, exchange.elapsed.as_nanos() as u64);
+        updates += exchange.notifications;
+        Ok::<_, HarnessError>(exchange.response)
+    };
+
+    let init = roundtrip(
+        &mut process,
+        "initialize",
+        json!({
+            "protocolVersion": 1,
+            "clientCapabilities": {"fs": {"readTextFile": false, "writeTextFile": false}, "terminal": false},
+            "clientInfo": {"name": "acp-benchmark", "version": env!("CARGO_PKG_VERSION")}
+        }),
+    )?;
+    let ready_ms = start.elapsed().as_millis();
+    if !matches!(
+        init,
+        Message::FromAgent(AgentToClientMessage::InitializeResult(_))
+    ) {
+        return Err(BenchError::Handshake("initialize"));
+    }
+
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let session_id = match roundtrip(
+        &mut process,
+        "session/new",
+        json!({"cwd": cwd, "mcpServers": []}),
+    )? {
+        Message::FromAgent(AgentToClientMessage::SessionNewResult(r)) => r.session_id,
+        _ => return Err(BenchError::Handshake("session/new")),
+    };
+
+    for _ in 0..count {
+        let response = roundtrip(
+            &mut process,
+            "session/prompt",
+            json!({"sessionId": session_id, "prompt": [{"type": "text", "text": "What is 2+2?"}]}),
+        )?;
+        if !matches!(
+            response,
+            Message::FromAgent(AgentToClientMessage::SessionPromptResult(_))
+        ) {
+            errors += 1;
+        }
+    }
+
+    let status = process.finish()?;
+
+    Ok(StdioReport {
+        agent: command.join(" "),
+        framing: framing.to_string(),
+        prompts: count,
+        errors,
+        updates,
+        agent_exit_code: status.code(),
+        ready_ms,
+        elapsed_ms: start.elapsed().as_millis(),
+        latency,
+    })
+}
+
+// -------------
+// Transport
+// -------------
+
+/// One transport's run in [`TransportReport`].
+#[derive(Debug, Serialize)]
+pub struct TransportRun {
+    /// Messages received and decoded.
+    pub count: usize,
+    pub errors: usize,
+    pub elapsed_ms: u128,
+    pub msgs_per_sec: u64,
+    pub stats: Summary,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TransportReport {
+    pub scenario: String,
+    pub transports: BTreeMap<String, TransportRun>,
+}
+
+/// Receive `count` messages from `rx` and decode each with threaded state.
+/// Returns the number decoded and the number that failed.
+async fn receive_and_decode(
+    rx: &impl Transport,
+    messages: &[(&str, Direction)],
+    count: usize,
+) -> (usize, usize) {
+    let mut state = CodecState::default();
+    let mut decoded = 0usize;
+    for i in 0..count {
+        match rx.receive().await {
+            Ok(Some(msg)) => {
+                let idx = replay_pass(i, messages.len(), &mut state, CodecState::default);
+                let direction = messages[idx].1;
+                decoded += black_box(codec::decode(direction, &mut state, &msg)).is_ok() as usize;
+            }
+            Ok(None) => break,
+            Err(_) => {}
+        }
+    }
+    (decoded, count - decoded)
+}
+
+/// Send `count` scenario messages on `tx` while `rx` receives and decodes them.
+async fn pump(
+    tx: &impl Transport,
+    rx: &impl Transport,
+    messages: &[(&str, Direction)],
+    count: usize,
+) -> (usize, usize) {
+    let send = async {
+        for i in 0..count {
+            let (msg, _) = messages[i % messages.len()];
+            if tx.send(msg.to_owned()).await.is_err() {
+                break;
+            }
+        }
+    };
+    let ((), received) = tokio::join!(send, receive_and_decode(rx, messages, count));
+    received
+}
+
+/// Decode `count` scenario messages as received through each in-process
+/// transport: a [`MemoryTransport`] queue, a [`DuplexTransport`] pair, and
+/// NDJSON over an in-memory pipe. Compare with `throughput` for the
+/// transport overhead.
+pub fn transport(
+    scenario: &Scenario,
+    count: usize,
+    harness: Harness,
+) -> Result<TransportReport, BenchError> {
+    let messages = with_directions(scenario);
+    let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+
+    let run = |f: &dyn Fn() -> (usize, usize)| {
+        let ((decoded, errors), stats) = harness.measure(f);
+        TransportRun {
+            count: decoded,
+            errors,
+            elapsed_ms: stats.mean_ms(),
+            msgs_per_sec: stats.per_sec(decoded),
+            stats,
+        }
+    };
+
+    let mut transports = BTreeMap::new();
+    transports.insert(
+        "memory".to_owned(),
+        run(&|| {
+            runtime.block_on(async {
+                let queue = MemoryTransport::new();
+                for i in 0..count {
+                    queue.enqueue(messages[i % messages.len()].0);
+                }
+                receive_and_decode(&queue, &messages, count).await
+            })
+        }),
+    );
+    transports.insert(
+        "duplex".to_owned(),
+        run(&|| {
+            runtime.block_on(async {
+                let (tx, rx) = DuplexTransport::pair();
+                pump(&tx, &rx, &messages, count).await
+            })
+        }),
+    );
+    transports.insert(
+        "stdio".to_owned(),
+        run(&|| {
+            runtime.block_on(async {
+                let (near, far) = tokio::io::duplex(64 * 1024);
+                let tx = StdioTransport::new(tokio::io::empty(), near, DEFAULT_MAX_FRAME_BYTES);
+                let rx = StdioTransport::new(far, tokio::io::sink(), DEFAULT_MAX_FRAME_BYTES);
+                pump(&tx, &rx, &messages, count).await
+            })
+        }),
+    );
+
+    Ok(TransportReport {
+        scenario: scenario.source.to_string(),
+        transports,
+    })
+}
+
+// -------------
+// Connection
+// -------------
+
+#[derive(Debug, Serialize)]
+pub struct ConnectionReport {
+    pub transport: &'static str,
+    pub prompts: usize,
+    pub errors: usize,
+    pub updates: usize,
+    pub elapsed_ms: u128,
+    pub prompts_per_sec: u64,
+    /// Request to response, through both connections and the agent handler.
+    /// From the last timed iteration.
+    pub latency: MethodLatency,
+    pub stats: Summary,
+}
+
+/// Answers nothing: the stub agent makes no requests of the client.
+struct NoClient;
+
+impl Client for NoClient {}
+
+/// Initialize, open a session and run `count` prompt turns, timing each
+/// request. Returns the latencies and the number of failed prompts.
+async fn drive(
+    client: &ClientConnection<DuplexTransport>,
+    count: usize,
+) -> Result<(MethodLatency, usize), BenchError> {
+    let mut latency = MethodLatency::default();
+
+    let init = InitializeParams {
+        protocol_version: 1,
+        client_capabilities: ClientCapabilities::default(),
+        client_info: None,
+    };
+    let start = Instant::now();
+    let init = client.initialize(init).await;
+    latency.record("initialize", start.elapsed().as_nanos() as u64);
+    init.map_err(|_| BenchError::Handshake("initialize"))?;
+
+    let new = NewSessionParams {
+        cwd: std::env::current_dir()
+            .unwrap_or_default()
+            .display()
+            .to_string(),
+        mcp_servers: Vec::new(),
+    };
+    let start = Instant::now();
+    let session = client.new_session(new).await;
+    latency.record("session/new", start.elapsed().as_nanos() as u64);
+    let session_id = session
+        .map_err(|_| BenchError::Handshake("session/new"))?
+        .session_id;
+
+    let mut errors = 0usize;
+    for _ in 0..count {
+        let prompt = SessionPromptParams {
+            session_id: session_id.clone(),
+            prompt: vec![ContentBlock::Text(TextContent {
+                text: "What is 2+2?".to_owned(),
+                annotations: None,
+            })],
+            meta: None,
+        };
+        let start = Instant::now();
+        let turn = client.prompt(prompt).await;
+        latency.record("session/prompt", start.elapsed().as_nanos() as u64);
+        errors += turn.is_err() as usize;
+    }
+    Ok((latency, errors))
+}
+
+/// Time initialize, session/new and `count` session/prompt roundtrips from a
+/// [`ClientConnection`] to the stub agent's [`AgentConnection`] over a
+/// [`DuplexTransport`] pair: the request, handler and response path that
+/// `transport` leaves out, without the process boundary `stdio` adds.
+pub fn connection(count: usize, harness: Harness) -> Result<ConnectionReport, BenchError> {
+    let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+
+    let (run, stats) = harness.measure(|| {
+        runtime.block_on(async {
+            let (near, far) = DuplexTransport::pair();
+            let (client, mut updates) = ClientConnection::new(near);
+            let (agent, _) = AgentConnection::new(far);
+            let stub = StubAgent::new(&agent);
+
+            let (client_run, agent_run, driven) =
+                tokio::join!(client.run(&NoClient), agent.run(&stub), async {
+                    let driven = drive(&client, count).await;
+                    client.close().await?;
+                    driven
+                });
+            client_run?;
+            agent_run?;
+            let (latency, errors) = driven?;
+
+            let mut received = 0usize;
+            while updates.recv().await.is_some() {
+                received += 1;
+            }
+            Ok::<_, BenchError>((latency, errors, received))
+        })
+    });
+    let (latency, errors, updates) = run?;
+
+    Ok(ConnectionReport {
+        transport: "duplex",
+        prompts: count,
+        errors,
+        updates,
+        elapsed_ms: stats.mean_ms(),
+        prompts_per_sec: stats.per_sec(count),
+        latency,
+        stats,
+    })
+}
+
+// -------------
+// Protocol, replay and validation
+// -------------
+
+#[derive(Debug, Serialize)]
+pub struct ProtocolReport {
+    pub scenario: String,
+    pub steps: usize,
+    pub errors: usize,
+    pub decode_errors: usize,
+    pub error_codes: BTreeMap<&'static str, usize>,
+    pub elapsed_ms: u128,
+    pub msgs_per_sec: u64,
+    pub ns_per_msg: u64,
+    pub stats: Summary,
+}
+
+pub fn protocol(
+    scenario: &Scenario,
+    count: usize,
+    harness: Harness,
+) -> Result<ProtocolReport, BenchError> {
+    // Decode once up front: this mode measures the state machine, not the codec.
+    let (messages, decode_errors) = decode_all(scenario)?;
+    let spec = protocol::spec();
+
+    let ((steps, errors, error_codes), stats) = harness.measure(|| {
+        let mut phase = spec.initial.clone();
+        let mut steps = 0usize;
+        let mut errors = 0usize;
+        let mut error_codes: BTreeMap<&'static str, usize> = BTreeMap::new();
+
+        for i in 0..count {
+            let idx = replay_pass(i, messages.len(), &mut phase, || spec.initial.clone());
+            match (spec.step)(&mut phase, &messages[idx]) {
+                Ok(()) => steps += 1,
+                Err(e) => {
+                    *error_codes.entry(e.code()).or_default() += 1;
+                    errors += 1;
+                }
+            }
+        }
+        black_box(&phase);
+        (steps, errors, error_codes)
+    });
+
+    Ok(ProtocolReport {
+        scenario: scenario.source.to_string(),
+        steps,
+        errors,
+        decode_errors,
+        error_codes,
+        elapsed_ms: stats.mean_ms(),
+        msgs_per_sec: stats.per_sec(count),
+        ns_per_msg: (stats.mean_ns / count.max(1) as f64) as u64,
+        stats,
+    })
+}
+
+/// Outcome of one replayed frame.
+#[derive(Debug, Serialize)]
+pub struct FrameResult {
+    pub index: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub direction: Option<String>,
+    /// `ok`, `error` (decode failed) or `invalid` (not a trace frame).
+    pub status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<RequestId>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decode_ns: Option<u128>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReplayReport {
+    pub scenario: String,
+    pub frames: usize,
+    pub decoded: usize,
+    pub errors: usize,
+    pub invalid_frames: usize,
+    pub honor_timestamps: bool,
+    pub recorded_span_ms: i64,
+    pub elapsed_ms: u128,
+    pub decode_ns: u128,
+    pub mean_decode_ns: u128,
+    pub stats: Summary,
+    pub results: Vec<FrameResult>,
+}
+
+/// Decode a trace frame by frame with threaded codec state, optionally
+/// sleeping to reproduce the recorded timing.
+pub fn replay(scenario: &Scenario, honor_timestamps: bool, harness: Harness) -> ReplayReport {
+    // Frame parsing is not part of the measured decode time.
+    let frames: Vec<_> = scenario
+        .messages
+        .iter()
+        .map(|line| trace::parse_frame(line))
+        .collect();
+    let first_ts = frames.iter().find_map(|f| f.as_ref().ok().map(|f| f.ts));
+    let last_ts = frames
+        .iter()
+        .rev()
+        .find_map(|f| f.as_ref().ok().map(|f| f.ts));
+
+    let ((results, decode_ns, decoded, errors, invalid), stats) = harness.measure(|| {
+        let start = Instant::now();
+        let mut state = CodecState::default();
+        let mut results = Vec::with_capacity(frames.len());
+        let mut decode_ns = 0u128;
+        let mut decoded = 0usize;
+        let mut errors = 0usize;
+        let mut invalid = 0usize;
+
+        for (i, frame) in frames.iter().enumerate() {
+            let index = i + 1;
+            let frame = match frame {
+                Ok(frame) => frame,
+                Err(e) => {
+                    invalid += 1;
+                    results.push(FrameResult {
+                        index,
+                        direction: None,
+                        status: "invalid",
+                        id: None,
+                        error: Some(e.to_string()),
+                        decode_ns: None,
+                    });
+                    continue;
+                }
+            };
+
+            if let (true, Some(first)) = (honor_timestamps, first_ts) {
+                // Sleep to the frame's offset from the first frame, so waits don't drift.
+                let offset = (frame.ts - first).to_std().unwrap_or_default();
+                if let Some(wait) = offset.checked_sub(start.elapsed()) {
+                    std::thread::sleep(wait);
+                }
+            }
+
+            let t = Instant::now();
+            let result = codec::decode(frame.direction, &mut state, &frame.json);
+            let ns = t.elapsed().as_nanos();
+            decode_ns += ns;
+
+            let (status, id, error) = match result {
+                Ok(d) => {
+                    decoded += 1;
+                    ("ok", d.id, None)
+                }
+                Err(e) => {
+                    errors += 1;
+                    ("error", None, Some(e.to_string()))
+                }
+            };
+            results.push(FrameResult {
+                index,
+                direction: Some(frame.direction.to_string()),
+                status,
+                id,
+                error,
+                decode_ns: Some(ns),
+            });
+        }
+        (results, decode_ns, decoded, errors, invalid)
+    });
+
+    let recorded_span_ms = match (first_ts, last_ts) {
+        (Some(first), Some(last)) => (last - first).num_milliseconds(),
+        _ => 0,
+    };
+
+    ReplayReport {
+        scenario: scenario.source.to_string(),
+        frames: frames.len(),
+        decoded,
+        errors,
+        invalid_frames: invalid,
+        honor_timestamps,
+        recorded_span_ms,
+        elapsed_ms: stats.mean_ms(),
+        decode_ns,
+        mean_decode_ns: decode_ns / (decoded + errors).max(1) as u128,
+        stats,
+        results,
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ValidateReport {
+    pub scenario: String,
+    pub messages: usize,
+    pub decode_errors: usize,
+    pub runs: usize,
+    pub final_phase: &'static str,
+    /// Number of findings.
+    pub findings: usize,
+    pub by_lane: BTreeMap<String, usize>,
+    pub elapsed_ms: u128,
+    pub ns_per_run: u64,
+    pub ns_per_msg: u64,
+    pub stats: Summary,
+    pub results: Vec<ValidationFinding>,
+}
+
+/// Run the validation engine `count` times per measured iteration.
+pub fn validate(
+    scenario: &Scenario,
+    count: usize,
+    harness: Harness,
+) -> Result<ValidateReport, BenchError> {
+    // As in protocol mode, decoding is not part of the measured time.
+    let (messages, decode_errors) = decode_all(scenario)?;
+    let spec = protocol::spec();
+    let runs = count.max(1);
+
+    let (result, stats) = harness.measure(|| {
+        let mut result = validation::run_with_validation(&spec, &messages);
+        for _ in 1..runs {
+            result = black_box(validation::run_with_validation(&spec, &messages));
+        }
+        result
+    });
+
+    let ns_per_run = stats.mean_ns / runs as f64;
+    let mut by_lane: BTreeMap<String, usize> = BTreeMap::new();
+    for finding in &result.findings {
+        *by_lane.entry(format!("{:?}", finding.lane)).or_default() += 1;
+    }
+
+    Ok(ValidateReport {
+        scenario: scenario.source.to_string(),
+        messages: messages.len(),
+        decode_errors,
+        runs,
+        final_phase: match &result.final_phase {
+            Ok(phase) => phase.name(),
+            Err(_) => "error",
+        },
+        findings: result.findings.len(),
+        by_lane,
+        elapsed_ms: stats.mean_ms(),
+        ns_per_run: ns_per_run as u64,
+        ns_per_msg: (ns_per_run / messages.len() as f64) as u64,
+        stats,
+        results: result.findings,
+    })
+}
+
+// -------------
+// Conformance
+// -------------
+
+/// A corpus message that did not round-trip exactly.
+#[derive(Debug, Serialize)]
+pub struct ConformanceResult {
+    pub file: String,
+    /// 1-based line number within `file`.
+    pub line: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    /// `differs` (decoded and encoded, but not faithfully) or `error`.
+    pub status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub differences: Vec<Difference>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConformanceReport {
+    pub files: usize,
+    pub messages: usize,
+    pub faithful: usize,
+    pub differing: usize,
+    pub errors: usize,
+    pub elapsed_ms: u128,
+    pub results: Vec<ConformanceResult>,
+}
+
+/// Round-trip every line of `files` through decode and encode, threading
+/// codec state per file. Lines that parse as trace frames use their recorded
+/// direction, as in [`decode_all`].
+pub fn conformance(files: &[PathBuf]) -> Result<ConformanceReport, BenchError> {
+    let start = Instant::now();
+    let mut results = Vec::new();
+    let mut messages = 0usize;
+    let mut faithful = 0usize;
+    let mut errors = 0usize;
+
+    for path in files {
+        let text = std::fs::read_to_string(path)?;
+        let file = path.display().to_string();
+        let mut state = CodecState::default();
+
+        for (i, line) in text.lines().enumerate() {
+            let line_text = line.trim();
+            if line_text.is_empty() {
+                continue;
+            }
+            messages += 1;
+            let trip = match trace::parse_frame(line_text) {
+                Ok(frame) => conformance::round_trip(frame.direction, &mut state, &frame.json),
+                Err(_) => conformance::round_trip(
+                    codec::infer_direction(line_text),
+                    &mut state,
+                    line_text,
+                ),
+            };
+            let result = match trip {
+                Ok(trip) if trip.differences.is_empty() => {
+                    faithful += 1;
+                    continue;
+                }
+                Ok(trip) => ConformanceResult {
+                    file: file.clone(),
+                    line: i + 1,
+                    method: Some(trip.method),
+                    status: "differs",
+                    error: None,
+                    differences: trip.differences,
+                },
+                Err(e) => {
+                    errors += 1;
+                    ConformanceResult {
+                        file: file.clone(),
+                        line: i + 1,
+                        method: None,
+                        status: "error",
+                        error: Some(e.to_string()),
+                        differences: Vec::new(),
+                    }
+                }
+            };
+            results.push(result);
+        }
+    }
+
+    Ok(ConformanceReport {
+        files: files.len(),
+        messages,
+        faithful,
+        differing: messages - faithful - errors,
+        errors,
+        elapsed_ms: start.elapsed().as_millis(),
+        results,
+    })
+}
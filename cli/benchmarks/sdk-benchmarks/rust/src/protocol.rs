@@ -0,0 +1,470 @@
+//! ACP protocol state machine: phases, sessions and prompt turns.
+//! Mirrors `protocol/src/Acp.Protocol.fs`.
+
+use crate::domain::*;
+use std::collections::HashMap;
+
+// -------------
+// State & errors
+// -------------
+
+/// Per-session prompt-turn lifecycle on a single ACP connection.
+///
+/// At most one prompt is in flight per session; `cancelled: true` means a
+/// cancel was requested and the agent must still finish the turn.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TurnState {
+    Idle {
+        last_stop_reason: Option<StopReason>,
+    },
+    PromptInFlight {
+        cancelled: bool,
+    },
+}
+
+/// Per-session state tracked once the session has been created or loaded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionState {
+    pub session_id: SessionId,
+    pub mode_state: Option<SessionModeState>,
+    pub turn_state: TurnState,
+}
+
+impl SessionState {
+    fn new(session_id: SessionId, mode_state: Option<SessionModeState>) -> Self {
+        SessionState {
+            session_id,
+            mode_state,
+            turn_state: TurnState::Idle {
+                last_stop_reason: None,
+            },
+        }
+    }
+
+    fn set_current_mode(&mut self, mode_id: &SessionModeId) {
+        if let Some(ms) = &mut self.mode_state {
+            ms.current_mode_id = mode_id.clone();
+        }
+    }
+}
+
+/// Initialization + session table once the connection is ready.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InitializedContext {
+    pub client_init: InitializeParams,
+    pub agent_init: InitializeResult,
+    pub sessions: HashMap<SessionId, SessionState>,
+}
+
+/// High-level protocol phase for a single JSON-RPC stream.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Phase {
+    AwaitingInitialize,
+    WaitingForInitializeResult(InitializeParams),
+    Ready(InitializedContext),
+}
+
+impl Phase {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Phase::AwaitingInitialize => "AwaitingInitialize",
+            Phase::WaitingForInitializeResult(_) => "WaitingForInitializeResult",
+            Phase::Ready(_) => "Ready",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProtocolError {
+    UnexpectedMessage {
+        phase: &'static str,
+        message: Box<Message>,
+    },
+    DuplicateInitialize,
+    InitializeResultWithoutRequest,
+    UnknownSession(SessionId),
+    SessionAlreadyExists(SessionId),
+    PromptAlreadyInFlight(SessionId),
+    NoPromptInFlight(SessionId),
+}
+
+impl ProtocolError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ProtocolError::UnexpectedMessage { .. } => "ACP.PROTOCOL.UNEXPECTED_MESSAGE",
+            ProtocolError::DuplicateInitialize => "ACP.PROTOCOL.DUPLICATE_INITIALIZE",
+            ProtocolError::InitializeResultWithoutRequest => {
+                "ACP.PROTOCOL.INIT_RESULT_WITHOUT_REQUEST"
+            }
+            ProtocolError::UnknownSession(_) => "ACP.PROTOCOL.UNKNOWN_SESSION",
+            ProtocolError::SessionAlreadyExists(_) => "ACP.PROTOCOL.SESSION_ALREADY_EXISTS",
+            ProtocolError::PromptAlreadyInFlight(_) => "ACP.PROTOCOL.PROMPT_ALREADY_IN_FLIGHT",
+            ProtocolError::NoPromptInFlight(_) => "ACP.PROTOCOL.NO_PROMPT_IN_FLIGHT",
+        }
+    }
+}
+
+impl std::fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ProtocolError::UnexpectedMessage { phase, message } => {
+                write!(f, "Unexpected message {message:?} in phase {phase}")
+            }
+            ProtocolError::DuplicateInitialize => {
+                f.write_str("initialize was called more than once")
+            }
+            ProtocolError::InitializeResultWithoutRequest => {
+                f.write_str("initialize result observed without a pending initialize request")
+            }
+            ProtocolError::UnknownSession(sid) => write!(f, "Unknown session {}", sid.0),
+            ProtocolError::SessionAlreadyExists(sid) => {
+                write!(f, "Session {} already exists", sid.0)
+            }
+            ProtocolError::PromptAlreadyInFlight(sid) => {
+                write!(f, "Prompt already in flight for session {}", sid.0)
+            }
+            ProtocolError::NoPromptInFlight(sid) => {
+                write!(f, "No prompt in flight for session {}", sid.0)
+            }
+        }
+    }
+}
+
+/// A tiny internal DSL: one transition function plus an initial phase.
+///
+/// `step` updates the phase in place; on error the phase is left unchanged.
+pub struct Spec<P, M, E> {
+    pub initial: P,
+    pub step: fn(&mut P, &M) -> Result<(), E>,
+}
+
+// -------------
+// Concrete ACP Spec<Phase, Message>
+// -------------
+
+/// MVP spec for the ACP "core slice". Rules encoded:
+///   - initialize must be first
+///   - exactly one initialize result
+///   - sessions are created by session/new or session/load results
+///   - at most one prompt in flight per session
+///   - cancel only allowed while a prompt is in flight
+///   - request_permission only allowed while a prompt is in flight
+pub fn spec() -> Spec<Phase, Message, ProtocolError> {
+    Spec {
+        initial: Phase::AwaitingInitialize,
+        step,
+    }
+}
+
+fn unexpected(phase: &Phase, message: &Message) -> ProtocolError {
+    ProtocolError::UnexpectedMessage {
+        phase: phase.name(),
+        message: Box::new(message.clone()),
+    }
+}
+
+fn is_proxy_successor(message: &Message) -> bool {
+    use AgentToClientMessage as A;
+    use ClientToAgentMessage as C;
+
+    matches!(
+        message,
+        Message::FromClient(
+            C::ProxySuccessorRequest(_)
+                | C::ProxySuccessorNotification(_)
+                | C::ProxySuccessorResponse { .. }
+                | C::ProxySuccessorError { .. }
+        ) | Message::FromAgent(
+            A::ProxySuccessorRequest(_)
+                | A::ProxySuccessorNotification(_)
+                | A::ProxySuccessorResponse { .. }
+                | A::ProxySuccessorError { .. }
+        )
+    )
+}
+
+fn step(phase: &mut Phase, message: &Message) -> Result<(), ProtocolError> {
+    use AgentToClientMessage as A;
+    use ClientToAgentMessage as C;
+
+    // Allow proxy successor traffic in any phase (draft proxy chains).
+    if is_proxy_successor(message) {
+        return Ok(());
+    }
+
+    match phase {
+        // --- Initialization handshake ---
+        Phase::AwaitingInitialize => match message {
+            Message::FromClient(C::Initialize(init) | C::ProxyInitialize(init)) => {
+                *phase = Phase::WaitingForInitializeResult(init.clone());
+                Ok(())
+            }
+            _ => Err(unexpected(phase, message)),
+        },
+
+        Phase::WaitingForInitializeResult(client_init) => match message {
+            Message::FromClient(C::Initialize(_) | C::ProxyInitialize(_)) => {
+                Err(ProtocolError::DuplicateInitialize)
+            }
+            Message::FromAgent(
+                A::InitializeResult(agent_init) | A::ProxyInitializeResult(agent_init),
+            ) => {
+                let ctx = InitializedContext {
+                    client_init: client_init.clone(),
+                    agent_init: agent_init.clone(),
+                    sessions: HashMap::new(),
+                };
+                *phase = Phase::Ready(ctx);
+                Ok(())
+            }
+            // If initialize fails, the connection is not ready; allow the client to retry.
+            Message::FromAgent(A::InitializeError(_) | A::ProxyInitializeError(_)) => {
+                *phase = Phase::AwaitingInitialize;
+                Ok(())
+            }
+            Message::FromAgent(_) => Err(ProtocolError::InitializeResultWithoutRequest),
+            Message::FromClient(_) => Err(unexpected(phase, message)),
+        },
+
+        // --- Ready: sessions and turns ---
+        Phase::Ready(ctx) => step_ready(ctx, message),
+    }
+}
+
+fn session_mut<'a>(
+    ctx: &'a mut InitializedContext,
+    sid: &SessionId,
+) -> Result<&'a mut SessionState, ProtocolError> {
+    ctx.sessions
+        .get_mut(sid)
+        .ok_or_else(|| ProtocolError::UnknownSession(sid.clone()))
+}
+
+fn step_ready(ctx: &mut InitializedContext, message: &Message) -> Result<(), ProtocolError> {
+    use AgentToClientMessage as A;
+    use ClientToAgentMessage as C;
+
+    match message {
+        Message::FromClient(C::Initialize(_) | C::ProxyInitialize(_))
+        | Message::FromAgent(A::InitializeResult(_) | A::ProxyInitializeResult(_)) => {
+            Err(ProtocolError::DuplicateInitialize)
+        }
+
+        // session/new request does not change state; result creates the session.
+        Message::FromClient(C::SessionNew(_)) => Ok(()),
+        Message::FromAgent(A::SessionNewResult(r)) => {
+            if ctx.sessions.contains_key(&r.session_id) {
+                return Err(ProtocolError::SessionAlreadyExists(r.session_id.clone()));
+            }
+            let s = SessionState::new(r.session_id.clone(), r.modes.clone());
+            ctx.sessions.insert(r.session_id.clone(), s);
+            Ok(())
+        }
+
+        // session/load request: state unchanged; result ensures the session is tracked.
+        Message::FromClient(C::SessionLoad(_)) => Ok(()),
+        Message::FromAgent(A::SessionLoadResult(r)) => {
+            match ctx.sessions.get_mut(&r.session_id) {
+                Some(s) => {
+                    if r.modes.is_some() {
+                        s.mode_state = r.modes.clone();
+                    }
+                }
+                None => {
+                    let s = SessionState::new(r.session_id.clone(), r.modes.clone());
+                    ctx.sessions.insert(r.session_id.clone(), s);
+                }
+            }
+            Ok(())
+        }
+
+        // session/set_mode: allowed whenever the session exists (idle or prompt in flight).
+        Message::FromClient(C::SessionSetMode(p)) => session_mut(ctx, &p.session_id).map(|_| ()),
+        Message::FromAgent(A::SessionSetModeResult(r)) => {
+            session_mut(ctx, &r.session_id)?.set_current_mode(&r.mode_id);
+            Ok(())
+        }
+
+        // session/prompt: ensure known session and no prompt in flight.
+        Message::FromClient(C::SessionPrompt(p)) => {
+            let s = session_mut(ctx, &p.session_id)?;
+            match s.turn_state {
+                TurnState::Idle { .. } => {
+                    s.turn_state = TurnState::PromptInFlight { cancelled: false };
+                    Ok(())
+                }
+                TurnState::PromptInFlight { .. } => {
+                    Err(ProtocolError::PromptAlreadyInFlight(s.session_id.clone()))
+                }
+            }
+        }
+
+        // session/prompt result or error: close the turn.
+        Message::FromAgent(A::SessionPromptResult(r)) => {
+            end_turn(ctx, &r.session_id, Some(r.stop_reason))
+        }
+        Message::FromAgent(A::SessionPromptError(req, _)) => end_turn(ctx, &req.session_id, None),
+
+        // session/cancel: only valid while a prompt is in flight.
+        Message::FromClient(C::SessionCancel(c)) => {
+            let s = session_mut(ctx, &c.session_id)?;
+            match s.turn_state {
+                TurnState::PromptInFlight { .. } => {
+                    s.turn_state = TurnState::PromptInFlight { cancelled: true };
+                    Ok(())
+                }
+                TurnState::Idle { .. } => {
+                    Err(ProtocolError::NoPromptInFlight(s.session_id.clone()))
+                }
+            }
+        }
+
+        // session/update: allowed whenever the session exists.
+        // This covers both prompt streaming and session/load replay.
+        Message::FromAgent(A::SessionUpdate(u)) => {
+            let s = session_mut(ctx, &u.session_id)?;
+            if let SessionUpdate::CurrentModeUpdate(m) = &u.update {
+                s.set_current_mode(&m.current_mode_id);
+            }
+            Ok(())
+        }
+
+        // session/request_permission: must be inside a prompt turn.
+        Message::FromAgent(A::SessionRequestPermissionRequest(p)) => {
+            let s = session_mut(ctx, &p.session_id)?;
+            match s.turn_state {
+                TurnState::PromptInFlight { .. } => Ok(()),
+                TurnState::Idle { .. } => {
+                    Err(ProtocolError::NoPromptInFlight(s.session_id.clone()))
+                }
+            }
+        }
+
+        // Everything else: state-neutral (JSON-RPC correlation lives in the codec layer).
+        _ => Ok(()),
+    }
+}
+
+fn end_turn(
+    ctx: &mut InitializedContext,
+    sid: &SessionId,
+    stop_reason: Option<StopReason>,
+) -> Result<(), ProtocolError> {
+    let s = session_mut(ctx, sid)?;
+    match s.turn_state {
+        TurnState::PromptInFlight { .. } => {
+            s.turn_state = TurnState::Idle {
+                last_stop_reason: stop_reason,
+            };
+            Ok(())
+        }
+        TurnState::Idle { .. } => Err(ProtocolError::NoPromptInFlight(s.session_id.clone())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::{self, CodecState, Direction};
+
+    /// Decode `(direction, json)` pairs through one codec state.
+    fn messages(frames: &[(Direction, &str)]) -> Vec<Message> {
+        let mut state = CodecState::default();
+        frames
+            .iter()
+            .map(|(d, json)| codec::decode(*d, &mut state, json).unwrap().message)
+            .collect()
+    }
+
+    fn run(messages: &[Message]) -> (Phase, Vec<ProtocolError>) {
+        let spec = spec();
+        let mut phase = spec.initial;
+        let errors = messages
+            .iter()
+            .filter_map(|m| (spec.step)(&mut phase, m).err())
+            .collect();
+        (phase, errors)
+    }
+
+    const INIT: (Direction, &str) = (
+        Direction::FromClient,
+        r#"{"jsonrpc":"2.0","method":"initialize","params":{"protocolVersion":1},"id":1}"#,
+    );
+    const INIT_RESULT: (Direction, &str) = (
+        Direction::FromAgent,
+        r#"{"jsonrpc":"2.0","result":{"protocolVersion":1},"id":1}"#,
+    );
+    const NEW: (Direction, &str) = (
+        Direction::FromClient,
+        r#"{"jsonrpc":"2.0","method":"session/new","params":{"cwd":"/","mcpServers":[]},"id":2}"#,
+    );
+    const NEW_RESULT: (Direction, &str) = (
+        Direction::FromAgent,
+        r#"{"jsonrpc":"2.0","result":{"sessionId":"s1"},"id":2}"#,
+    );
+    const PROMPT: (Direction, &str) = (
+        Direction::FromClient,
+        r#"{"jsonrpc":"2.0","method":"session/prompt","params":{"sessionId":"s1","prompt":[]},"id":3}"#,
+    );
+    const CANCEL: (Direction, &str) = (
+        Direction::FromClient,
+        r#"{"jsonrpc":"2.0","method":"session/cancel","params":{"sessionId":"s1"}}"#,
+    );
+    const PROMPT_RESULT: (Direction, &str) = (
+        Direction::FromAgent,
+        r#"{"jsonrpc":"2.0","result":{"stopReason":"cancelled"},"id":3}"#,
+    );
+
+    #[test]
+    fn happy_path_ends_idle_with_stop_reason() {
+        let trace = [
+            INIT,
+            INIT_RESULT,
+            NEW,
+            NEW_RESULT,
+            PROMPT,
+            CANCEL,
+            PROMPT_RESULT,
+        ];
+        let (phase, errors) = run(&messages(&trace));
+        assert!(errors.is_empty(), "{errors:?}");
+        match phase {
+            Phase::Ready(ctx) => assert_eq!(
+                ctx.sessions[&SessionId("s1".into())].turn_state,
+                TurnState::Idle {
+                    last_stop_reason: Some(StopReason::Cancelled)
+                }
+            ),
+            other => panic!("unexpected phase {other:?}"),
+        }
+    }
+
+    #[test]
+    fn rejects_out_of_order_messages_with_codes() {
+        let (_, errors) = run(&messages(&[NEW]));
+        assert_eq!(errors[0].code(), "ACP.PROTOCOL.UNEXPECTED_MESSAGE");
+
+        let second_prompt = (
+            Direction::FromClient,
+            r#"{"jsonrpc":"2.0","method":"session/prompt","params":{"sessionId":"s1","prompt":[]},"id":4}"#,
+        );
+        let trace = [
+            INIT,
+            INIT_RESULT,
+            NEW,
+            NEW_RESULT,
+            CANCEL,
+            PROMPT,
+            second_prompt,
+        ];
+        let (_, errors) = run(&messages(&trace));
+        let codes: Vec<_> = errors.iter().map(ProtocolError::code).collect();
+        assert_eq!(
+            codes,
+            [
+                "ACP.PROTOCOL.NO_PROMPT_IN_FLIGHT",
+                "ACP.PROTOCOL.PROMPT_ALREADY_IN_FLIGHT"
+            ]
+        );
+    }
+}
@@ -0,0 +1,181 @@
+//! Built-in sample ACP messages, used when no scenario is provided.
+
+use crate::codec::{self, CodecState, Decoded, Direction};
+use serde_json::json;
+
+pub const INITIALIZE_REQUEST: &str = r#"{"jsonrpc":"2.0","method":"initialize","params":{"protocolVersion":1,"clientCapabilities":{"fs":{"readTextFile":true,"writeTextFile":true},"terminal":true},"clientInfo":{"name":"benchmark","version":"1.0.0"}},"id":1}"#;
+
+pub const INITIALIZE_RESPONSE: &str = r#"{"jsonrpc":"2.0","result":{"protocolVersion":1,"agentCapabilities":{"loadSession":true},"authMethods":[]},"id":1}"#;
+
+pub const SESSION_NEW_REQUEST: &str =
+    r#"{"jsonrpc":"2.0","method":"session/new","params":{"cwd":"/tmp","mcpServers":[]},"id":2}"#;
+
+pub const SESSION_NEW_RESPONSE: &str =
+    r#"{"jsonrpc":"2.0","result":{"sessionId":"sess-001"},"id":2}"#;
+
+pub const SESSION_UPDATE_NOTIFICATION: &str = r#"{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"sess-001","update":{"sessionUpdate":"agent_message_chunk","content":{"type":"text","text":"Hello, this is a test message."}}}}"#;
+
+pub const PROMPT_REQUEST: &str = r#"{"jsonrpc":"2.0","method":"session/prompt","params":{"sessionId":"sess-001","prompt":[{"type":"text","text":"What is 2+2?"}]},"id":3}"#;
+
+pub const PROMPT_RESPONSE: &str = r#"{"jsonrpc":"2.0","result":{"stopReason":"end_turn"},"id":3}"#;
+
+/// A full session with tool use: initialize with rich capabilities, a prompt
+/// turn with a permission request, fs and terminal requests with their
+/// results, an error response on each side, and updates throughout. Responses
+/// carry no method, so each message is paired with its direction.
+pub const TOOL_SESSION: [(Direction, &str); 23] = [
+    (
+        Direction::FromClient,
+        r#"{"jsonrpc":"2.0","method":"initialize","params":{"protocolVersion":1,"clientCapabilities":{"fs":{"readTextFile":true,"writeTextFile":true},"terminal":true},"clientInfo":{"name":"benchmark","version":"1.0.0"}},"id":1}"#,
+    ),
+    (
+        Direction::FromAgent,
+        r#"{"jsonrpc":"2.0","result":{"protocolVersion":1,"agentCapabilities":{"loadSession":true,"promptCapabilities":{"image":true,"embeddedContext":true},"mcpCapabilities":{"http":true}},"agentInfo":{"name":"bench-agent","version":"1.0.0"},"authMethods":[{"id":"api-key","name":"API key"}]},"id":1}"#,
+    ),
+    (
+        Direction::FromClient,
+        r#"{"jsonrpc":"2.0","method":"session/new","params":{"cwd":"/tmp/project","mcpServers":[]},"id":2}"#,
+    ),
+    (
+        Direction::FromAgent,
+        r#"{"jsonrpc":"2.0","result":{"sessionId":"sess-001","modes":{"currentModeId":"code","availableModes":[{"id":"code","name":"Code"},{"id":"ask","name":"Ask"}]}},"id":2}"#,
+    ),
+    (
+        Direction::FromClient,
+        r#"{"jsonrpc":"2.0","method":"session/prompt","params":{"sessionId":"sess-001","prompt":[{"type":"text","text":"Run the tests and fix the failure"}]},"id":10}"#,
+    ),
+    (
+        Direction::FromAgent,
+        r#"{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"sess-001","update":{"sessionUpdate":"plan","entries":[{"content":"Run the test suite","priority":"high","status":"in_progress"},{"content":"Fix the failing test","priority":"medium","status":"pending"}]}}}"#,
+    ),
+    (
+        Direction::FromAgent,
+        r#"{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"sess-001","update":{"sessionUpdate":"tool_call","toolCallId":"call-1","title":"cargo test","kind":"execute","status":"pending","locations":[{"path":"/tmp/project"}],"rawInput":{"command":"cargo","args":["test"]}}}}"#,
+    ),
+    (
+        Direction::FromAgent,
+        r#"{"jsonrpc":"2.0","method":"session/request_permission","params":{"sessionId":"sess-001","toolCall":{"toolCallId":"call-1","title":"cargo test","kind":"execute"},"options":[{"optionId":"allow","name":"Allow once","kind":"allow_once"},{"optionId":"reject","name":"Reject","kind":"reject_once"}]},"id":"perm-1"}"#,
+    ),
+    (
+        Direction::FromClient,
+        r#"{"jsonrpc":"2.0","result":{"outcome":{"outcome":"selected","optionId":"allow"}},"id":"perm-1"}"#,
+    ),
+    (
+        Direction::FromAgent,
+        r#"{"jsonrpc":"2.0","method":"terminal/create","params":{"sessionId":"sess-001","command":"cargo","args":["test"],"cwd":"/tmp/project","env":[{"name":"RUST_BACKTRACE","value":"1"}],"outputByteLimit":65536},"id":"term-1"}"#,
+    ),
+    (
+        Direction::FromClient,
+        r#"{"jsonrpc":"2.0","result":{"terminalId":"t-1"},"id":"term-1"}"#,
+    ),
+    (
+        Direction::FromAgent,
+        r#"{"jsonrpc":"2.0","method":"terminal/wait_for_exit","params":{"sessionId":"sess-001","terminalId":"t-1"},"id":"term-2"}"#,
+    ),
+    (
+        Direction::FromClient,
+        r#"{"jsonrpc":"2.0","result":{"exitCode":101},"id":"term-2"}"#,
+    ),
+    (
+        Direction::FromAgent,
+        r#"{"jsonrpc":"2.0","method":"terminal/output","params":{"sessionId":"sess-001","terminalId":"t-1"},"id":"term-3"}"#,
+    ),
+    (
+        Direction::FromClient,
+        r#"{"jsonrpc":"2.0","result":{"output":"test parser::tests::round_trip ... FAILED\nassertion failed: left == right","truncated":false,"exitStatus":{"exitCode":101}},"id":"term-3"}"#,
+    ),
+    (
+        Direction::FromAgent,
+        r#"{"jsonrpc":"2.0","method":"fs/read_text_file","params":{"sessionId":"sess-001","path":"/tmp/project/src/parser.rs","line":40,"limit":20},"id":"fs-1"}"#,
+    ),
+    (
+        Direction::FromClient,
+        r#"{"jsonrpc":"2.0","result":{"content":"fn round_trip() {\n    assert_eq!(parse(\"1\"), Ok(1));\n}"},"id":"fs-1"}"#,
+    ),
+    (
+        Direction::FromAgent,
+        r#"{"jsonrpc":"2.0","method":"fs/write_text_file","params":{"sessionId":"sess-001","path":"/etc/hosts","content":"127.0.0.1 localhost"},"id":"fs-2"}"#,
+    ),
+    (
+        Direction::FromClient,
+        r#"{"jsonrpc":"2.0","error":{"code":-32000,"message":"Write outside the workspace denied","data":{"path":"/etc/hosts"}},"id":"fs-2"}"#,
+    ),
+    (
+        Direction::FromAgent,
+        r#"{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"sess-001","update":{"sessionUpdate":"tool_call_update","toolCallId":"call-1","status":"completed","content":[{"type":"content","content":{"type":"text","text":"1 test failed"}}]}}}"#,
+    ),
+    (
+        Direction::FromAgent,
+        r#"{"jsonrpc":"2.0","result":{"stopReason":"end_turn","usage":{"inputTokens":1200,"outputTokens":340}},"id":10}"#,
+    ),
+    (
+        Direction::FromClient,
+        r#"{"jsonrpc":"2.0","method":"session/set_mode","params":{"sessionId":"sess-001","modeId":"architect"},"id":11}"#,
+    ),
+    (
+        Direction::FromAgent,
+        r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"Unknown mode: architect"},"id":11}"#,
+    ),
+];
+
+/// A `session/update` agent message chunk of `token_count` words.
+pub fn token_update(token_count: usize) -> String {
+    let text = "word ".repeat(token_count);
+    serde_json::to_string(&json!({
+        "jsonrpc": "2.0",
+        "method": "session/update",
+        "params": {
+            "sessionId": "sess-001",
+            "update": {
+                "sessionUpdate": "agent_message_chunk",
+                "content": {"type": "text", "text": text}
+            }
+        }
+    }))
+    .unwrap()
+}
+
+/// Messages for modes that cycle through a mixed batch: one full exchange,
+/// so every request is settled before the next pass reuses its id.
+pub fn batch() -> Vec<String> {
+    [
+        INITIALIZE_REQUEST,
+        INITIALIZE_RESPONSE,
+        SESSION_NEW_REQUEST,
+        SESSION_NEW_RESPONSE,
+        SESSION_UPDATE_NOTIFICATION,
+        PROMPT_REQUEST,
+        PROMPT_RESPONSE,
+    ]
+    .map(str::to_owned)
+    .to_vec()
+}
+
+/// The sample batch as a trace, one frame every 100ms.
+pub fn trace() -> Vec<String> {
+    batch()
+        .into_iter()
+        .enumerate()
+        .map(|(i, msg)| {
+            json!({
+                "ts": format!("2025-01-01T00:00:00.{:03}Z", i * 100),
+                "direction": codec::infer_direction(&msg).to_string(),
+                "json": msg,
+            })
+            .to_string()
+        })
+        .collect()
+}
+
+/// [`TOOL_SESSION`] decoded with threaded state: typed messages covering
+/// every payload kind, for encoding benchmarks.
+pub fn encode_mix() -> Vec<Decoded> {
+    let mut state = CodecState::default();
+    TOOL_SESSION
+        .iter()
+        .map(|&(direction, line)| {
+            codec::decode(direction, &mut state, line)
+                .unwrap_or_else(|e| panic!("sample message failed to decode: {e}\n{line}"))
+        })
+        .collect()
+}
@@ -0,0 +1,87 @@
+//! Scenario loading: NDJSON messages from `--scenario <path>` or stdin,
+//! falling back to built-in sample messages when no input is provided.
+
+use std::fmt;
+use std::io::{self, IsTerminal, Read};
+use std::path::{Path, PathBuf};
+
+/// Where a scenario's messages came from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    File(PathBuf),
+    Stdin,
+    BuiltIn,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Source::File(path) => write!(f, "{}", path.display()),
+            Source::Stdin => f.write_str("stdin"),
+            Source::BuiltIn => f.write_str("builtin"),
+        }
+    }
+}
+
+/// Pre-loaded benchmark input: one raw JSON-RPC message per entry.
+#[derive(Debug, Clone)]
+pub struct Scenario {
+    pub source: Source,
+    pub messages: Vec<String>,
+}
+
+impl Scenario {
+    pub fn built_in(messages: Vec<String>) -> Self {
+        Scenario {
+            source: Source::BuiltIn,
+            messages,
+        }
+    }
+}
+
+/// Split NDJSON text into messages, skipping blank lines and CRLF endings.
+pub fn parse_ndjson(text: &str) -> Vec<String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Load a scenario from `path`, else from piped stdin. Returns `None` when
+/// neither yields any message, so the caller can use its built-in fallback.
+pub fn load(path: Option<&Path>) -> io::Result<Option<Scenario>> {
+    let (source, text) = match path {
+        Some(path) => (
+            Source::File(path.to_owned()),
+            std::fs::read_to_string(path)?,
+        ),
+        None => {
+            let stdin = io::stdin();
+            if stdin.is_terminal() {
+                return Ok(None);
+            }
+            let mut text = String::new();
+            stdin.lock().read_to_string(&mut text)?;
+            (Source::Stdin, text)
+        }
+    };
+
+    let messages = parse_ndjson(&text);
+    if messages.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(Scenario { source, messages }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_ndjson_skips_blank_lines_and_crlf() {
+        let text = "{\"a\":1}\r\n\r\n  \n{\"b\":2}\n";
+        assert_eq!(parse_ndjson(text), vec!["{\"a\":1}", "{\"b\":2}"]);
+    }
+}
@@ -0,0 +1,275 @@
+//! Structural validation against the pinned ACP JSON Schema
+//! (`schema/acp-0.10.5.json`, the version `Acp.Domain.Spec.Schema` pins; see
+//! `schema/README.md` for vendoring it from upstream). Method definitions are
+//! selected from the whole file by their `x-method` annotation; a decoded
+//! message is checked against the `*Request`/`*Notification` definition for
+//! its params or the `*Response` definition for its result, and error
+//! responses against `Error`. Messages no definition covers (extensions,
+//! proxy chains) are not checked.
+
+use crate::domain::Message;
+use crate::validation::{Lane, Severity, Subject, ValidationFailure, ValidationFinding};
+use jsonschema::Validator;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::fmt;
+
+/// ACP schema version of the pinned file.
+pub const SCHEMA_VERSION: &str = "0.10.5";
+
+const PINNED: &str = include_str!("../schema/acp-0.10.5.json");
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchemaError {
+    InvalidJson(String),
+    MissingDefs,
+    /// No definition carries `x-method`, so nothing would be checked.
+    NoMethods,
+    Definition {
+        name: String,
+        error: String,
+    },
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SchemaError::InvalidJson(e) => write!(f, "invalid schema JSON: {e}"),
+            SchemaError::MissingDefs => write!(f, "schema has no $defs"),
+            SchemaError::NoMethods => write!(f, "schema has no x-method definitions"),
+            SchemaError::Definition { name, error } => {
+                write!(f, "schema definition {name} does not compile: {error}")
+            }
+        }
+    }
+}
+
+/// Which envelope member a definition describes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Part {
+    Params,
+    Result,
+}
+
+impl Part {
+    fn member(self) -> &'static str {
+        match self {
+            Part::Params => "params",
+            Part::Result => "result",
+        }
+    }
+}
+
+struct Definition {
+    name: String,
+    validator: Validator,
+}
+
+/// Compiled per-method validators for one ACP schema file.
+pub struct Schema {
+    methods: HashMap<(Part, String), Definition>,
+    error: Option<Definition>,
+}
+
+impl Schema {
+    /// The schema file compiled into this crate.
+    pub fn pinned() -> Schema {
+        Schema::from_json(PINNED).expect("pinned ACP schema compiles")
+    }
+
+    /// Compile every `x-method` definition (and `Error`) in `schema`, a whole
+    /// upstream `schema.json`; other definitions are only reachable by `$ref`.
+    pub fn from_json(schema: &str) -> Result<Schema, SchemaError> {
+        let root: Value =
+            serde_json::from_str(schema).map_err(|e| SchemaError::InvalidJson(e.to_string()))?;
+        let defs = root
+            .get("$defs")
+            .and_then(Value::as_object)
+            .ok_or(SchemaError::MissingDefs)?;
+
+        let mut methods = HashMap::new();
+        for (name, def) in defs {
+            let Some(method) = def.get("x-method").and_then(Value::as_str) else {
+                continue;
+            };
+            let part = if name.ends_with("Response") {
+                Part::Result
+            } else {
+                Part::Params
+            };
+            methods.insert((part, method.to_owned()), compile(&root, defs, name)?);
+        }
+        if methods.is_empty() {
+            return Err(SchemaError::NoMethods);
+        }
+        let error = match defs.contains_key("Error") {
+            true => Some(compile(&root, defs, "Error")?),
+            false => None,
+        };
+        Ok(Schema { methods, error })
+    }
+
+    /// Number of method definitions, params and results counted separately.
+    pub fn definitions(&self) -> usize {
+        self.methods.len()
+    }
+
+    /// Check `json`, which decoded to `message`, against the definition for
+    /// its method. `None` when no definition covers it; otherwise one finding
+    /// per violation, located by JSON pointer into the message.
+    pub fn validate(
+        &self,
+        message: &Message,
+        json: &str,
+        trace_index: Option<usize>,
+    ) -> Option<Vec<ValidationFinding>> {
+        let envelope: Map<String, Value> = serde_json::from_str(json).ok()?;
+        let method = message.method();
+
+        let (member, instance, definition) = if let Some(error) = envelope.get("error") {
+            ("error", error, self.error.as_ref()?)
+        } else {
+            let part = match envelope.contains_key("method") {
+                true => Part::Params,
+                false => Part::Result,
+            };
+            let value = envelope.get(part.member()).unwrap_or(&Value::Null);
+            let definition = self.methods.get(&(part, method.to_owned()))?;
+            (part.member(), value, definition)
+        };
+
+        let findings = definition
+            .validator
+            .iter_errors(instance)
+            .map(|e| {
+                violation(
+                    format!("{method}: /{member}{}: {e}", e.instance_path),
+                    format!("#/$defs/{}{}", definition.name, e.schema_path),
+                    trace_index,
+                )
+            })
+            .collect();
+        Some(findings)
+    }
+}
+
+fn compile(root: &Value, defs: &Map<String, Value>, name: &str) -> Result<Definition, SchemaError> {
+    let mut schema = json!({ "$defs": defs, "$ref": format!("#/$defs/{name}") });
+    if let Some(dialect) = root.get("$schema") {
+        schema["$schema"] = dialect.clone();
+    }
+    let validator = jsonschema::validator_for(&schema).map_err(|e| SchemaError::Definition {
+        name: name.to_owned(),
+        error: e.to_string(),
+    })?;
+    Ok(Definition {
+        name: name.to_owned(),
+        validator,
+    })
+}
+
+/// A protocol-lane schema violation; the note points into the schema.
+fn violation(
+    message: String,
+    schema_path: String,
+    trace_index: Option<usize>,
+) -> ValidationFinding {
+    let subject = match trace_index {
+        Some(index) => Subject::MessageAt { index },
+        None => Subject::Connection,
+    };
+    ValidationFinding {
+        lane: Lane::Protocol,
+        severity: Severity::Error,
+        subject: subject.clone(),
+        failure: Some(ValidationFailure {
+            code: "ACP.SCHEMA.VIOLATION".to_owned(),
+            message,
+            subject,
+        }),
+        session_id: None,
+        trace_index,
+        note: Some(schema_path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::{self, CodecState, Direction};
+    use crate::samples;
+
+    #[test]
+    fn sample_session_conforms() {
+        let schema = Schema::pinned();
+        for (decoded, &(_, line)) in samples::encode_mix().iter().zip(&samples::TOOL_SESSION) {
+            let findings = schema.validate(&decoded.message, line, None);
+            assert_eq!(findings, Some(vec![]), "{line}");
+        }
+    }
+
+    #[test]
+    fn selects_method_definitions_from_the_whole_file() {
+        // Upstream's shape: unions and shared types beside the annotated
+        // method definitions, plus a top-level `anyOf`.
+        let file = json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "$defs": {
+                "SessionId": {"type": "string"},
+                "CancelNotification": {
+                    "type": "object",
+                    "properties": {"sessionId": {"$ref": "#/$defs/SessionId"}},
+                    "required": ["sessionId"],
+                    "x-method": "session/cancel",
+                    "x-side": "agent"
+                },
+                "ClientNotification": {"anyOf": [{"$ref": "#/$defs/CancelNotification"}]}
+            },
+            "anyOf": [{"$ref": "#/$defs/ClientNotification"}]
+        });
+        let schema = Schema::from_json(&file.to_string()).unwrap();
+        assert_eq!(schema.definitions(), 1);
+
+        let cancel = r#"{"jsonrpc":"2.0","method":"session/cancel","params":{"sessionId":"s1"}}"#;
+        let decoded =
+            codec::decode(Direction::FromClient, &mut CodecState::default(), cancel).unwrap();
+        assert_eq!(
+            schema.validate(&decoded.message, cancel, None),
+            Some(vec![])
+        );
+        let bad = cancel.replace(r#""s1""#, "1");
+        assert_eq!(
+            schema.validate(&decoded.message, &bad, None).unwrap().len(),
+            1
+        );
+
+        let no_methods = json!({"$defs": {"SessionId": {"type": "string"}}});
+        assert_eq!(
+            Schema::from_json(&no_methods.to_string()).err(),
+            Some(SchemaError::NoMethods)
+        );
+    }
+
+    #[test]
+    fn reports_json_pointer_to_the_violation() {
+        // Checked against the line as given; the decoded message only
+        // supplies the method.
+        let prompt = r#"{"jsonrpc":"2.0","method":"session/prompt","params":{"sessionId":"s1","prompt":[{"type":"text","text":"hi"}]},"id":1}"#;
+        let decoded =
+            codec::decode(Direction::FromClient, &mut CodecState::default(), prompt).unwrap();
+        let bad = prompt.replace(r#""text":"hi""#, r#""text":42"#);
+        let findings = Schema::pinned()
+            .validate(&decoded.message, &bad, Some(3))
+            .unwrap();
+        assert!(!findings.is_empty());
+        let failure = findings[0].failure.as_ref().unwrap();
+        assert!(
+            failure
+                .message
+                .starts_with("session/prompt: /params/prompt/0"),
+            "{}",
+            failure.message
+        );
+        assert_eq!(findings[0].trace_index, Some(3));
+    }
+}
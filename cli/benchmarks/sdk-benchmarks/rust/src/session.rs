@@ -0,0 +1,318 @@
+//! Session state folded from `session/update` notifications. Mirrors
+//! `runtime/src/Acp.Contrib.SessionState.fs`, except that one tracker keeps
+//! every session it sees instead of resetting when the session id changes.
+//!
+//! Text chunks are concatenated per stream (agent, user, thought); plan
+//! entries and available commands are replaced wholesale, as the protocol
+//! sends them; tool calls are merged field by field from `tool_call_update`.
+
+use crate::domain::{
+    AgentToClientMessage, AvailableCommand, ContentBlock, ContentChunk, Message, PlanEntry,
+    SessionId, SessionModeId, SessionUpdate, SessionUpdateNotification, ToolCall, ToolCallUpdate,
+};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+// -------------
+// State
+// -------------
+
+/// Everything known about one session so far.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionState {
+    pub session_id: SessionId,
+    /// From a `session_info_update`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current_mode_id: Option<SessionModeId>,
+    pub agent_text: String,
+    pub user_text: String,
+    pub thought_text: String,
+    /// Non-text content blocks across all three streams (images, resources).
+    pub other_chunks: usize,
+    pub plan_entries: Vec<PlanEntry>,
+    pub available_commands: Vec<AvailableCommand>,
+    /// Keyed by tool call id.
+    pub tool_calls: BTreeMap<String, ToolCall>,
+    /// `usage_update` payloads in arrival order.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub usage_updates: Vec<Map<String, Value>>,
+    /// Notifications folded into this state.
+    pub updates: usize,
+}
+
+impl SessionState {
+    pub fn new(session_id: SessionId) -> Self {
+        SessionState {
+            session_id,
+            title: None,
+            current_mode_id: None,
+            agent_text: String::new(),
+            user_text: String::new(),
+            thought_text: String::new(),
+            other_chunks: 0,
+            plan_entries: Vec::new(),
+            available_commands: Vec::new(),
+            tool_calls: BTreeMap::new(),
+            usage_updates: Vec::new(),
+            updates: 0,
+        }
+    }
+
+    /// Fold one update into the state.
+    pub fn apply(&mut self, update: &SessionUpdate) {
+        self.updates += 1;
+        match update {
+            SessionUpdate::AgentMessageChunk(chunk) => {
+                append(&mut self.agent_text, &mut self.other_chunks, chunk)
+            }
+            SessionUpdate::UserMessageChunk(chunk) => {
+                append(&mut self.user_text, &mut self.other_chunks, chunk)
+            }
+            SessionUpdate::AgentThoughtChunk(chunk) => {
+                append(&mut self.thought_text, &mut self.other_chunks, chunk)
+            }
+            SessionUpdate::ToolCall(call) => {
+                self.tool_calls
+                    .insert(call.tool_call_id.clone(), call.clone());
+            }
+            SessionUpdate::ToolCallUpdate(update) => self.apply_tool_call_update(update),
+            SessionUpdate::Plan(plan) => self.plan_entries = plan.entries.clone(),
+            SessionUpdate::AvailableCommandsUpdate(update) => {
+                self.available_commands = update.available_commands.clone()
+            }
+            SessionUpdate::CurrentModeUpdate(update) => {
+                self.current_mode_id = Some(update.current_mode_id.clone())
+            }
+            SessionUpdate::Ext { tag, payload } => match tag.as_str() {
+                "session_info_update" => {
+                    if let Some(Value::String(title)) = payload.get("title") {
+                        self.title = Some(title.clone());
+                    }
+                }
+                "usage_update" => self.usage_updates.push(payload.clone()),
+                _ => {}
+            },
+        }
+    }
+
+    /// Merge the fields an update carries; an unseen id starts a new call.
+    fn apply_tool_call_update(&mut self, update: &ToolCallUpdate) {
+        let call = self
+            .tool_calls
+            .entry(update.tool_call_id.clone())
+            .or_insert_with(|| ToolCall {
+                tool_call_id: update.tool_call_id.clone(),
+                title: String::new(),
+                kind: Default::default(),
+                status: Default::default(),
+                content: Vec::new(),
+                locations: Vec::new(),
+                raw_input: None,
+                raw_output: None,
+            });
+        if let Some(title) = &update.title {
+            call.title = title.clone();
+        }
+        if let Some(kind) = update.kind {
+            call.kind = kind;
+        }
+        if let Some(status) = update.status {
+            call.status = status;
+        }
+        if let Some(content) = &update.content {
+            call.content = content.clone();
+        }
+        if let Some(locations) = &update.locations {
+            call.locations = locations.clone();
+        }
+        if update.raw_input.is_some() {
+            call.raw_input = update.raw_input.clone();
+        }
+        if update.raw_output.is_some() {
+            call.raw_output = update.raw_output.clone();
+        }
+    }
+}
+
+fn append(text: &mut String, other: &mut usize, chunk: &ContentChunk) {
+    match &chunk.content {
+        ContentBlock::Text(t) => text.push_str(&t.text),
+        _ => *other += 1,
+    }
+}
+
+// -------------
+// Tracker
+// -------------
+
+/// Per-session state for every session seen in a notification stream.
+#[derive(Debug, Clone, Default)]
+pub struct SessionTracker {
+    sessions: BTreeMap<SessionId, SessionState>,
+}
+
+impl SessionTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fold a notification into its session's state and return that state.
+    pub fn apply(&mut self, notification: &SessionUpdateNotification) -> &SessionState {
+        let state = self
+            .sessions
+            .entry(notification.session_id.clone())
+            .or_insert_with(|| SessionState::new(notification.session_id.clone()));
+        state.apply(&notification.update);
+        state
+    }
+
+    /// Fold `message` if it is a `session/update`; returns whether it was.
+    pub fn apply_message(&mut self, message: &Message) -> bool {
+        match message {
+            Message::FromAgent(AgentToClientMessage::SessionUpdate(n)) => {
+                self.apply(n);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// The current state of a session, borrowed.
+    pub fn get(&self, session_id: &SessionId) -> Option<&SessionState> {
+        self.sessions.get(session_id)
+    }
+
+    /// A copy of a session's current state that later updates do not change.
+    pub fn snapshot(&self, session_id: &SessionId) -> Option<SessionState> {
+        self.get(session_id).cloned()
+    }
+
+    pub fn sessions(&self) -> impl Iterator<Item = &SessionState> {
+        self.sessions.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    /// Forget one session; returns its final state.
+    pub fn remove(&mut self, session_id: &SessionId) -> Option<SessionState> {
+        self.sessions.remove(session_id)
+    }
+
+    pub fn clear(&mut self) {
+        self.sessions.clear();
+    }
+
+    /// All sessions as a JSON object keyed by session id.
+    pub fn to_json(&self) -> Value {
+        Value::Object(
+            self.sessions
+                .iter()
+                .map(|(id, state)| {
+                    let state = serde_json::to_value(state).expect("session state serializes");
+                    (id.0.clone(), state)
+                })
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::{self, CodecState, Direction};
+    use crate::domain::ToolCallStatus;
+
+    fn track(tracker: &mut SessionTracker, lines: &[&str]) {
+        let mut state = CodecState::default();
+        for line in lines {
+            let decoded = codec::decode(Direction::FromAgent, &mut state, line).unwrap();
+            assert!(tracker.apply_message(&decoded.message));
+        }
+    }
+
+    fn update(session: &str, update: &str) -> String {
+        format!(
+            r#"{{"jsonrpc":"2.0","method":"session/update","params":{{"sessionId":"{session}","update":{update}}}}}"#
+        )
+    }
+
+    #[test]
+    fn folds_text_mode_plan_and_tool_calls() {
+        let lines = [
+            update(
+                "s1",
+                r#"{"sessionUpdate":"agent_message_chunk","content":{"type":"text","text":"Hel"}}"#,
+            ),
+            update(
+                "s1",
+                r#"{"sessionUpdate":"agent_message_chunk","content":{"type":"text","text":"lo"}}"#,
+            ),
+            update(
+                "s1",
+                r#"{"sessionUpdate":"agent_thought_chunk","content":{"type":"text","text":"hmm"}}"#,
+            ),
+            update(
+                "s1",
+                r#"{"sessionUpdate":"current_mode_update","currentModeId":"code"}"#,
+            ),
+            update(
+                "s1",
+                r#"{"sessionUpdate":"plan","entries":[{"content":"Build","priority":"high","status":"pending"}]}"#,
+            ),
+            update(
+                "s1",
+                r#"{"sessionUpdate":"tool_call","toolCallId":"t1","title":"Read config","kind":"read"}"#,
+            ),
+            update(
+                "s1",
+                r#"{"sessionUpdate":"tool_call_update","toolCallId":"t1","status":"completed"}"#,
+            ),
+        ];
+        let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+        let mut tracker = SessionTracker::new();
+        track(&mut tracker, &lines);
+
+        let state = tracker.get(&SessionId("s1".into())).unwrap();
+        assert_eq!(state.agent_text, "Hello");
+        assert_eq!(state.thought_text, "hmm");
+        assert_eq!(state.current_mode_id, Some(SessionModeId("code".into())));
+        assert_eq!(state.plan_entries.len(), 1);
+        let call = &state.tool_calls["t1"];
+        assert_eq!(call.title, "Read config");
+        assert_eq!(call.status, ToolCallStatus::Completed);
+        assert_eq!(state.updates, 7);
+    }
+
+    #[test]
+    fn keeps_sessions_apart_and_snapshots_are_detached() {
+        let chunk = |s: &str, text: &str| {
+            update(
+                s,
+                &format!(
+                    r#"{{"sessionUpdate":"agent_message_chunk","content":{{"type":"text","text":"{text}"}}}}"#
+                ),
+            )
+        };
+        let mut tracker = SessionTracker::new();
+        track(&mut tracker, &[&chunk("a", "one"), &chunk("b", "two")]);
+        let before = tracker.snapshot(&SessionId("a".into())).unwrap();
+        track(&mut tracker, &[&chunk("a", " more")]);
+
+        assert_eq!(tracker.len(), 2);
+        assert_eq!(before.agent_text, "one");
+        let dump = tracker.to_json();
+        assert_eq!(dump["a"]["agentText"], "one more");
+        assert_eq!(dump["b"]["agentText"], "two");
+        assert_eq!(dump["b"]["sessionId"], "b");
+    }
+}
@@ -0,0 +1,134 @@
+//! In-process timing harness: untimed warmup runs, then timed iterations
+//! summarised at nanosecond resolution (hyperfine's `--warmup`/`--runs`,
+//! without process start-up in the numbers).
+
+use serde::Serialize;
+use std::time::Instant;
+
+/// Nanosecond summary of the timed iterations.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Summary {
+    pub warmup: usize,
+    pub iterations: usize,
+    pub min_ns: u64,
+    pub mean_ns: f64,
+    pub median_ns: f64,
+    pub p90_ns: u64,
+    pub p99_ns: u64,
+    pub max_ns: u64,
+    pub stddev_ns: f64,
+}
+
+impl Summary {
+    /// Summarise raw samples; `None` when there are none.
+    pub fn of(warmup: usize, samples: &[u64]) -> Option<Summary> {
+        if samples.is_empty() {
+            return None;
+        }
+        let mut sorted = samples.to_vec();
+        sorted.sort_unstable();
+        let n = sorted.len();
+
+        let mean = sorted.iter().map(|&s| s as f64).sum::<f64>() / n as f64;
+        let variance = sorted
+            .iter()
+            .map(|&s| (s as f64 - mean).powi(2))
+            .sum::<f64>()
+            / n as f64;
+        let median = if n.is_multiple_of(2) {
+            (sorted[n / 2 - 1] as f64 + sorted[n / 2] as f64) / 2.0
+        } else {
+            sorted[n / 2] as f64
+        };
+
+        Some(Summary {
+            warmup,
+            iterations: n,
+            min_ns: sorted[0],
+            mean_ns: mean,
+            median_ns: median,
+            p90_ns: percentile(&sorted, 90.0),
+            p99_ns: percentile(&sorted, 99.0),
+            max_ns: sorted[n - 1],
+            stddev_ns: variance.sqrt(),
+        })
+    }
+
+    /// Units per second at the mean iteration time.
+    pub fn per_sec(&self, units: usize) -> u64 {
+        (units as f64 * 1e9 / self.mean_ns.max(1.0)) as u64
+    }
+
+    /// Mean iteration time in whole milliseconds, for the legacy `elapsed_ms` field.
+    pub fn mean_ms(&self) -> u128 {
+        (self.mean_ns / 1e6) as u128
+    }
+}
+
+/// Nearest-rank percentile of an ascending, non-empty slice.
+fn percentile(sorted: &[u64], p: f64) -> u64 {
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Warmup and iteration counts shared by every mode.
+#[derive(Debug, Clone, Copy)]
+pub struct Harness {
+    pub warmup: usize,
+    pub iterations: usize,
+}
+
+impl Harness {
+    /// Run `f` `warmup` times untimed, then `iterations` (at least one) times
+    /// timed. Returns the result of the last timed iteration with the summary.
+    pub fn measure<T>(&self, mut f: impl FnMut() -> T) -> (T, Summary) {
+        for _ in 0..self.warmup {
+            std::hint::black_box(f());
+        }
+
+        let iterations = self.iterations.max(1);
+        let mut samples = Vec::with_capacity(iterations);
+        let mut last = None;
+        for _ in 0..iterations {
+            let start = Instant::now();
+            let out = f();
+            samples.push(start.elapsed().as_nanos() as u64);
+            last = Some(std::hint::black_box(out));
+        }
+
+        let summary = Summary::of(self.warmup, &samples).expect("at least one iteration");
+        (last.expect("at least one iteration"), summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summarises_samples() {
+        let samples: Vec<u64> = (1..=100).rev().collect();
+        let s = Summary::of(3, &samples).unwrap();
+        assert_eq!((s.min_ns, s.max_ns), (1, 100));
+        assert_eq!(s.mean_ns, 50.5);
+        assert_eq!(s.median_ns, 50.5);
+        assert_eq!((s.p90_ns, s.p99_ns), (90, 99));
+        assert!((s.stddev_ns - 28.866).abs() < 0.001);
+        assert_eq!((s.warmup, s.iterations), (3, 100));
+        assert_eq!(Summary::of(0, &[]), None);
+    }
+
+    #[test]
+    fn measure_runs_warmup_then_iterations() {
+        let mut calls = 0;
+        let harness = Harness {
+            warmup: 2,
+            iterations: 0,
+        };
+        let (last, s) = harness.measure(|| {
+            calls += 1;
+            calls
+        });
+        assert_eq!((calls, last, s.iterations), (3, 3, 1));
+    }
+}
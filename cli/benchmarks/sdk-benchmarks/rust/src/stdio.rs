@@ -0,0 +1,417 @@
+//! Stdio harness: drives an ACP agent subprocess over NDJSON or
+//! `Content-Length` framed JSON-RPC, decoding every frame it sends back
+//! through the codec. A [`Peer`] answers the agent's requests, and the
+//! connection can be recorded as a trace.
+
+use crate::codec::{self, CodecState, DecodeError, Direction, EncodeError};
+use crate::connection::method_not_found;
+use crate::domain::{AgentToClientMessage, ClientToAgentMessage, JsonRpcError, Message, RequestId};
+use crate::framing::{FrameReader, Framing, DEFAULT_MAX_FRAME_BYTES};
+use crate::trace::TraceFrame;
+use crate::validation::ValidationFinding;
+use serde_json::{json, Value};
+use std::fmt;
+use std::io::{self, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, ExitStatus, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long [`AgentProcess::request`] waits for a response by default.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long [`AgentProcess::finish`] lets the agent exit before killing it.
+pub const FINISH_GRACE: Duration = Duration::from_secs(5);
+
+#[derive(Debug)]
+pub enum HarnessError {
+    Io(io::Error),
+    /// The agent closed stdout before answering.
+    Closed,
+    /// The agent sent no response within the deadline.
+    Timeout(Duration),
+    Decode(DecodeError),
+    Encode(EncodeError),
+    /// The agent sent a frame the framer rejected.
+    Transport(Box<ValidationFinding>),
+}
+
+impl fmt::Display for HarnessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HarnessError::Io(e) => write!(f, "agent i/o failed: {e}"),
+            HarnessError::Closed => f.write_str("agent closed stdout before responding"),
+            HarnessError::Timeout(after) => write!(f, "agent did not respond within {after:?}"),
+            HarnessError::Decode(e) => write!(f, "agent sent an undecodable message: {e}"),
+            HarnessError::Encode(e) => write!(f, "message does not encode: {e}"),
+            HarnessError::Transport(finding) => match &finding.failure {
+                Some(failure) => write!(f, "agent sent a bad frame: {}", failure.message),
+                None => f.write_str("agent sent a bad frame"),
+            },
+        }
+    }
+}
+
+impl From<io::Error> for HarnessError {
+    fn from(e: io::Error) -> Self {
+        HarnessError::Io(e)
+    }
+}
+
+/// One request/response roundtrip.
+#[derive(Debug)]
+pub struct Exchange {
+    /// The decoded response (a result or an error variant).
+    pub response: Message,
+    /// From writing the request to reading its response.
+    pub elapsed: Duration,
+    /// Notifications received while waiting.
+    pub notifications: usize,
+}
+
+/// Agent -> client requests need an answer, or the agent may stall waiting.
+fn is_agent_request(msg: &AgentToClientMessage) -> bool {
+    use AgentToClientMessage as A;
+    matches!(
+        msg,
+        A::FsReadTextFileRequest(_)
+            | A::FsWriteTextFileRequest(_)
+            | A::SessionRequestPermissionRequest(_)
+            | A::TerminalCreateRequest(_)
+            | A::TerminalOutputRequest(_)
+            | A::TerminalWaitForExitRequest(_)
+            | A::TerminalKillRequest(_)
+            | A::TerminalReleaseRequest(_)
+            | A::ProxySuccessorRequest(_)
+            | A::ExtRequest { .. }
+    )
+}
+
+fn is_notification(msg: &AgentToClientMessage) -> bool {
+    use AgentToClientMessage as A;
+    matches!(
+        msg,
+        A::SessionUpdate(_) | A::ProxySuccessorNotification(_) | A::ExtNotification { .. }
+    )
+}
+
+/// The client's side of what the agent sends while a request waits.
+pub trait Peer {
+    /// Answer an agent -> client request; by default, "method not found".
+    fn answer(
+        &mut self,
+        request: AgentToClientMessage,
+    ) -> Result<ClientToAgentMessage, JsonRpcError> {
+        Err(method_not_found(request.method()))
+    }
+
+    /// See a notification.
+    fn notified(&mut self, _notification: &AgentToClientMessage) {}
+}
+
+/// A [`Peer`] that refuses every agent request.
+pub struct Refuse;
+
+impl Peer for Refuse {}
+
+/// A running agent process and the client side of its connection.
+pub struct AgentProcess {
+    child: Child,
+    /// Taken by [`AgentProcess::finish`] to close it.
+    stdin: Option<ChildStdin>,
+    /// Frames read from stdout on a separate thread, so waits can time out.
+    frames: Receiver<Result<String, ValidationFinding>>,
+    framing: Framing,
+    state: CodecState,
+    next_id: i64,
+    timeout: Duration,
+    /// Receives every frame in both directions as a trace line.
+    trace: Option<Box<dyn Write + Send>>,
+}
+
+impl AgentProcess {
+    /// Spawn `command[0]` with the remaining arguments, speaking `framing` in
+    /// both directions; stderr is inherited.
+    pub fn spawn(command: &[String], framing: Framing) -> io::Result<AgentProcess> {
+        let (program, args) = command
+            .split_first()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty agent command"))?;
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()?;
+        let stdin = child.stdin.take().expect("piped stdin");
+        let stdout = BufReader::new(child.stdout.take().expect("piped stdout"));
+
+        let (tx, frames) = mpsc::channel();
+        thread::spawn(move || {
+            let mut reader = FrameReader::new(framing, stdout, DEFAULT_MAX_FRAME_BYTES);
+            while let Some(frame) = reader.next_frame() {
+                if tx.send(frame.map(|f| f.text.to_owned())).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(AgentProcess {
+            child,
+            stdin: Some(stdin),
+            frames,
+            framing,
+            state: CodecState::default(),
+            next_id: 1,
+            timeout: DEFAULT_TIMEOUT,
+            trace: None,
+        })
+    }
+
+    /// Fail requests that get no response within `timeout` (default
+    /// [`DEFAULT_TIMEOUT`]).
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Record every frame sent and received as a [`TraceFrame`] line.
+    pub fn with_trace(mut self, trace: impl Write + Send + 'static) -> Self {
+        self.trace = Some(Box::new(trace));
+        self
+    }
+
+    fn record(&mut self, direction: Direction, json: &str) -> io::Result<()> {
+        match &mut self.trace {
+            Some(trace) => writeln!(
+                trace,
+                "{}",
+                TraceFrame::now(direction, json.to_owned()).to_line()
+            ),
+            None => Ok(()),
+        }
+    }
+
+    fn send(&mut self, line: &str) -> Result<(), HarnessError> {
+        codec::decode(Direction::FromClient, &mut self.state, line)
+            .map_err(HarnessError::Decode)?;
+        self.record(Direction::FromClient, line)?;
+        let stdin = self.stdin.as_mut().expect("stdin open until finish");
+        self.framing.write(stdin, line)?;
+        Ok(())
+    }
+
+    /// Send `answer` to the agent's request `id`, or the error response.
+    fn reply(
+        &mut self,
+        id: &RequestId,
+        answer: Result<ClientToAgentMessage, JsonRpcError>,
+    ) -> Result<(), HarnessError> {
+        let reply = match answer {
+            Ok(reply) => Message::FromClient(reply),
+            Err(error) => codec::error_reply(Direction::FromClient, &self.state, id, error)
+                .map_err(HarnessError::Decode)?,
+        };
+        let line = codec::encode(Some(id), &reply).map_err(HarnessError::Encode)?;
+        self.send(&line)
+    }
+
+    fn next_id(&mut self) -> RequestId {
+        let id = RequestId::Number(self.next_id);
+        self.next_id += 1;
+        id
+    }
+
+    /// Send a request and wait for its response, answering any agent requests
+    /// on the way with "method not found".
+    pub fn request(&mut self, method: &str, params: Value) -> Result<Exchange, HarnessError> {
+        let id = self.next_id();
+        let request = json!({"jsonrpc": "2.0", "method": method, "params": params, "id": id});
+        self.roundtrip(id, &request.to_string(), &mut Refuse)
+    }
+
+    /// Send a typed request and wait for its response, with `peer` answering
+    /// the agent's requests and seeing its notifications meanwhile.
+    pub fn call(
+        &mut self,
+        request: ClientToAgentMessage,
+        peer: &mut impl Peer,
+    ) -> Result<Exchange, HarnessError> {
+        let id = self.next_id();
+        let line = codec::encode(Some(&id), &Message::FromClient(request))
+            .map_err(HarnessError::Encode)?;
+        self.roundtrip(id, &line, peer)
+    }
+
+    fn roundtrip(
+        &mut self,
+        id: RequestId,
+        line: &str,
+        peer: &mut impl Peer,
+    ) -> Result<Exchange, HarnessError> {
+        let start = Instant::now();
+        self.send(line)?;
+
+        let deadline = start + self.timeout;
+        let mut notifications = 0usize;
+        loop {
+            let wait = deadline.saturating_duration_since(Instant::now());
+            let frame = match self.frames.recv_timeout(wait) {
+                Ok(frame) => frame.map_err(|f| HarnessError::Transport(Box::new(f)))?,
+                Err(RecvTimeoutError::Timeout) => return Err(HarnessError::Timeout(self.timeout)),
+                Err(RecvTimeoutError::Disconnected) => return Err(HarnessError::Closed),
+            };
+            let frame = frame.trim();
+            self.record(Direction::FromAgent, frame)?;
+            let decoded = codec::decode(Direction::FromAgent, &mut self.state, frame)
+                .map_err(HarnessError::Decode)?;
+            let Message::FromAgent(msg) = decoded.message else {
+                unreachable!("decoded from the agent direction")
+            };
+
+            match decoded.id {
+                Some(request_id) if is_agent_request(&msg) => {
+                    let answer = peer.answer(msg);
+                    self.reply(&request_id, answer)?;
+                }
+                Some(response_id) if response_id == id => {
+                    return Ok(Exchange {
+                        response: Message::FromAgent(msg),
+                        elapsed: start.elapsed(),
+                        notifications,
+                    });
+                }
+                _ if is_notification(&msg) => {
+                    notifications += 1;
+                    peer.notified(&msg);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Close the agent's stdin and wait for it to exit, killing it if it is
+    /// still running after [`FINISH_GRACE`].
+    pub fn finish(mut self) -> io::Result<ExitStatus> {
+        drop(self.stdin.take());
+        if let Some(trace) = &mut self.trace {
+            trace.flush()?;
+        }
+        let deadline = Instant::now() + FINISH_GRACE;
+        while Instant::now() < deadline {
+            if let Some(status) = self.child.try_wait()? {
+                return Ok(status);
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        // It may exit between the last poll and the kill.
+        let _ = self.child.kill();
+        self.child.wait()
+    }
+}
+
+/// An agent abandoned after an error (a timeout included) is killed rather
+/// than left running.
+impl Drop for AgentProcess {
+    fn drop(&mut self) {
+        if let Ok(None) = self.child.try_wait() {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn times_out_a_silent_agent() {
+        let command = [
+            "sh".to_owned(),
+            "-c".to_owned(),
+            "cat > /dev/null".to_owned(),
+        ];
+        let mut agent = AgentProcess::spawn(&command, Framing::Ndjson)
+            .unwrap()
+            .with_timeout(Duration::from_millis(100));
+        let result = agent.request("initialize", json!({"protocolVersion": 1}));
+        assert!(matches!(result, Err(HarnessError::Timeout(_))));
+        assert!(agent.finish().unwrap().success());
+    }
+
+    /// A trace sink the test can read back.
+    #[derive(Clone, Default)]
+    struct Shared(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    struct Files;
+
+    impl Peer for Files {
+        fn answer(
+            &mut self,
+            request: AgentToClientMessage,
+        ) -> Result<ClientToAgentMessage, JsonRpcError> {
+            match request {
+                AgentToClientMessage::FsReadTextFileRequest(_) => Ok(
+                    ClientToAgentMessage::FsReadTextFileResult(crate::domain::ReadTextFileResult {
+                        content: "hello".to_owned(),
+                    }),
+                ),
+                other => Err(method_not_found(other.method())),
+            }
+        }
+    }
+
+    #[test]
+    fn peer_answers_agent_requests_and_frames_are_traced() {
+        let script = r#"read request
+echo '{"jsonrpc":"2.0","method":"fs/read_text_file","params":{"sessionId":"s","path":"/a"},"id":"r1"}'
+read reply
+echo '{"jsonrpc":"2.0","result":{"protocolVersion":1},"id":1}'
+cat > /dev/null"#;
+        let command = ["sh".to_owned(), "-c".to_owned(), script.to_owned()];
+        let trace = Shared::default();
+        let mut agent = AgentProcess::spawn(&command, Framing::Ndjson)
+            .unwrap()
+            .with_timeout(Duration::from_secs(10))
+            .with_trace(trace.clone());
+        let initialize = ClientToAgentMessage::Initialize(crate::domain::InitializeParams {
+            protocol_version: 1,
+            client_capabilities: Default::default(),
+            client_info: None,
+        });
+        let exchange = agent.call(initialize, &mut Files).unwrap();
+        assert!(matches!(
+            exchange.response,
+            Message::FromAgent(AgentToClientMessage::InitializeResult(_))
+        ));
+        assert!(agent.finish().unwrap().success());
+
+        let trace = String::from_utf8(trace.0.lock().unwrap().clone()).unwrap();
+        let frames: Vec<_> = trace
+            .lines()
+            .map(|line| crate::trace::parse_frame(line).unwrap())
+            .collect();
+        let directions: Vec<_> = frames.iter().map(|f| f.direction).collect();
+        assert_eq!(
+            directions,
+            [
+                Direction::FromClient,
+                Direction::FromAgent,
+                Direction::FromClient,
+                Direction::FromAgent
+            ]
+        );
+        assert!(frames[2].json.contains(r#""content":"hello""#));
+        assert!(frames[2].json.contains(r#""id":"r1""#));
+    }
+}
@@ -0,0 +1,264 @@
+//! Bundled stub agent: answers initialize, session/new and session/prompt,
+//! so the stdio mode has a target offline. It replies in whichever framing
+//! the client's first bytes use. [`StubAgent`] gives the same answers as an
+//! [`Agent`] handler for in-process connections.
+
+use crate::codec::{self, CodecState, Direction};
+use crate::connection::{internal_error, Agent, AgentConnection};
+use crate::domain::*;
+use crate::framing::{FrameReader, Framing, DEFAULT_MAX_FRAME_BYTES};
+use crate::transport::Transport;
+use serde_json::{json, Value};
+use std::io::{self, BufRead, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Write `value` to the client, passing it back through the codec so the
+/// request it answers no longer counts as pending.
+fn write_line(
+    output: &mut impl Write,
+    framing: Framing,
+    state: &mut CodecState,
+    value: &Value,
+) -> io::Result<()> {
+    let line = value.to_string();
+    // Replies to malformed requests settle nothing; they go out regardless.
+    let _ = codec::decode(Direction::FromAgent, state, &line);
+    framing.write(output, &line)
+}
+
+fn error_response(id: &Value, code: i32, message: &str) -> Value {
+    json!({"jsonrpc": "2.0", "error": {"code": code, "message": message}, "id": id})
+}
+
+/// Serve requests from `input` until it closes.
+pub fn serve(input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+    let mut reader = FrameReader::detect(input, DEFAULT_MAX_FRAME_BYTES)?;
+    let framing = reader.framing();
+    let mut state = CodecState::default();
+    let mut sessions = 0usize;
+
+    while let Some(frame) = reader.next_frame() {
+        // Bad frames carry no usable id, so there is nothing to answer.
+        let Ok(frame) = frame else { continue };
+        let line = frame.text;
+
+        let decoded = match codec::decode(Direction::FromClient, &mut state, line) {
+            Ok(decoded) => decoded,
+            Err(e) => {
+                // Answer malformed requests that still carry an id; drop the rest.
+                let id = serde_json::from_str::<Value>(line)
+                    .ok()
+                    .and_then(|v| v.get("id").cloned())
+                    .filter(|id| !id.is_null());
+                if let Some(id) = id {
+                    write_line(
+                        &mut output,
+                        framing,
+                        &mut state,
+                        &error_response(&id, -32602, &e.to_string()),
+                    )?;
+                }
+                continue;
+            }
+        };
+        let Some(id) = decoded.id.as_ref().map(|id| json!(id)) else {
+            // Notifications (session/cancel included) need no answer.
+            continue;
+        };
+
+        let result = match decoded.message {
+            Message::FromClient(ClientToAgentMessage::Initialize(params)) => json!({
+                "protocolVersion": params.protocol_version,
+                "agentCapabilities": {"loadSession": false},
+                "agentInfo": {"name": "acp-benchmark-stub", "version": env!("CARGO_PKG_VERSION")},
+                "authMethods": []
+            }),
+            Message::FromClient(ClientToAgentMessage::SessionNew(_)) => {
+                sessions += 1;
+                json!({"sessionId": format!("stub-session-{sessions}")})
+            }
+            Message::FromClient(ClientToAgentMessage::SessionPrompt(params)) => {
+                let update = json!({
+                    "jsonrpc": "2.0",
+                    "method": "session/update",
+                    "params": {
+                        "sessionId": params.session_id,
+                        "update": {
+                            "sessionUpdate": "agent_message_chunk",
+                            "content": {"type": "text", "text": "stub reply"}
+                        }
+                    }
+                });
+                write_line(&mut output, framing, &mut state, &update)?;
+                json!({"stopReason": "end_turn"})
+            }
+            _ => {
+                write_line(
+                    &mut output,
+                    framing,
+                    &mut state,
+                    &error_response(&id, -32601, "Method not found"),
+                )?;
+                continue;
+            }
+        };
+        write_line(
+            &mut output,
+            framing,
+            &mut state,
+            &json!({"jsonrpc": "2.0", "result": result, "id": id}),
+        )?;
+    }
+
+    Ok(())
+}
+
+// -------------
+// Handler
+// -------------
+
+/// The stub's answers for an [`AgentConnection`]: one `agent_message_chunk`
+/// update per prompt turn, then `end_turn`.
+pub struct StubAgent<'a, T> {
+    conn: &'a AgentConnection<T>,
+    sessions: AtomicUsize,
+}
+
+impl<'a, T: Transport> StubAgent<'a, T> {
+    pub fn new(conn: &'a AgentConnection<T>) -> Self {
+        StubAgent {
+            conn,
+            sessions: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl<T: Transport> Agent for StubAgent<'_, T> {
+    async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult, JsonRpcError> {
+        Ok(InitializeResult {
+            protocol_version: params.protocol_version,
+            agent_capabilities: AgentCapabilities::default(),
+            agent_info: Some(ImplementationInfo {
+                name: "acp-benchmark-stub".to_owned(),
+                title: None,
+                version: env!("CARGO_PKG_VERSION").to_owned(),
+            }),
+            auth_methods: Vec::new(),
+        })
+    }
+
+    async fn new_session(&self, _: NewSessionParams) -> Result<NewSessionResult, JsonRpcError> {
+        let n = self.sessions.fetch_add(1, Ordering::Relaxed) + 1;
+        Ok(NewSessionResult {
+            session_id: SessionId(format!("stub-session-{n}")),
+            modes: None,
+        })
+    }
+
+    async fn prompt(
+        &self,
+        params: SessionPromptParams,
+    ) -> Result<SessionPromptResult, JsonRpcError> {
+        let update = SessionUpdateNotification {
+            session_id: params.session_id.clone(),
+            update: SessionUpdate::AgentMessageChunk(ContentChunk {
+                content: ContentBlock::Text(TextContent {
+                    text: "stub reply".to_owned(),
+                    annotations: None,
+                }),
+            }),
+            meta: None,
+        };
+        self.conn
+            .session_update(update)
+            .await
+            .map_err(internal_error)?;
+        Ok(SessionPromptResult {
+            session_id: params.session_id,
+            stop_reason: StopReason::EndTurn,
+            usage: None,
+            meta: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(input: &str) -> Vec<Value> {
+        let mut output = Vec::new();
+        serve(input.as_bytes(), &mut output).unwrap();
+        serde_json::Deserializer::from_slice(&output)
+            .into_iter::<Value>()
+            .map(Result::unwrap)
+            .collect()
+    }
+
+    #[test]
+    fn answers_a_prompt_turn() {
+        let out = run(concat!(
+            r#"{"jsonrpc":"2.0","method":"initialize","params":{"protocolVersion":1},"id":1}"#,
+            "\n",
+            r#"{"jsonrpc":"2.0","method":"session/new","params":{"cwd":"/","mcpServers":[]},"id":2}"#,
+            "\n",
+            r#"{"jsonrpc":"2.0","method":"session/prompt","params":{"sessionId":"stub-session-1","prompt":[]},"id":3}"#,
+            "\n",
+        ));
+        assert_eq!(out.len(), 4);
+        assert_eq!(out[0]["result"]["protocolVersion"], 1);
+        assert_eq!(out[1]["result"]["sessionId"], "stub-session-1");
+        assert_eq!(out[2]["method"], "session/update");
+        assert_eq!(out[3]["result"]["stopReason"], "end_turn");
+        assert_eq!(out[3]["id"], 3);
+    }
+
+    #[test]
+    fn rejects_unsupported_and_malformed_requests() {
+        let out = run(concat!(
+            r#"{"jsonrpc":"2.0","method":"_custom/thing","params":{},"id":"a"}"#,
+            "\n",
+            r#"{"jsonrpc":"2.0","method":"session/prompt","params":{},"id":7}"#,
+            "\n",
+            r#"{"jsonrpc":"2.0","method":"session/cancel","params":{"sessionId":"s"}}"#,
+            "\n",
+        ));
+        assert_eq!(out.len(), 2);
+        assert_eq!(
+            (out[0]["id"].clone(), out[0]["error"]["code"].clone()),
+            (json!("a"), json!(-32601))
+        );
+        assert_eq!(
+            (out[1]["id"].clone(), out[1]["error"]["code"].clone()),
+            (json!(7), json!(-32602))
+        );
+    }
+
+    #[test]
+    fn settles_each_request_it_answers() {
+        // A client may reuse an id once its request has been answered.
+        let initialize =
+            r#"{"jsonrpc":"2.0","method":"initialize","params":{"protocolVersion":1},"id":1}"#;
+        let out = run(&format!("{initialize}\n{initialize}\n"));
+        assert_eq!(out.len(), 2);
+        assert!(out
+            .iter()
+            .all(|reply| reply["result"]["protocolVersion"] == 1));
+    }
+
+    #[test]
+    fn replies_in_the_clients_framing() {
+        let mut input = Vec::new();
+        let request =
+            r#"{"jsonrpc":"2.0","method":"initialize","params":{"protocolVersion":1},"id":1}"#;
+        Framing::ContentLength.write(&mut input, request).unwrap();
+
+        let mut output = Vec::new();
+        serve(&input[..], &mut output).unwrap();
+        let mut reader = FrameReader::detect(&output[..], DEFAULT_MAX_FRAME_BYTES).unwrap();
+        assert_eq!(reader.framing(), Framing::ContentLength);
+        let reply: Value =
+            serde_json::from_str(reader.next_frame().unwrap().unwrap().text).unwrap();
+        assert_eq!(reply["result"]["protocolVersion"], 1);
+    }
+}
@@ -0,0 +1,143 @@
+//! Sentinel trace frames: one `{ts, direction, json}` object per JSONL line,
+//! as recorded by the inspector into `sentinel/tests/traces/*.jsonl`.
+//! Mirrors `TraceFrame` in `cli/apps/ACP.Cli/Commands/InspectCommand.fs`.
+
+use crate::codec::Direction;
+use chrono::{DateTime, FixedOffset, SecondsFormat};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// One recorded JSON-RPC message with its timestamp and direction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceFrame {
+    pub ts: DateTime<FixedOffset>,
+    pub direction: Direction,
+    pub json: String,
+}
+
+impl TraceFrame {
+    /// A frame stamped with the current time.
+    pub fn now(direction: Direction, json: String) -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        TraceFrame {
+            ts: DateTime::from_timestamp_millis(now.as_millis() as i64)
+                .unwrap_or_default()
+                .fixed_offset(),
+            direction,
+            json,
+        }
+    }
+
+    /// One trace line, as [`parse_frame`] reads it back: `ts` in RFC 3339
+    /// with milliseconds, as the inspector writes it.
+    pub fn to_line(&self) -> String {
+        let wire = FrameOut {
+            ts: self.ts.to_rfc3339_opts(SecondsFormat::Millis, true),
+            direction: self.direction.to_string(),
+            json: &self.json,
+        };
+        serde_json::to_string(&wire).expect("trace frames serialize")
+    }
+}
+
+/// Why a trace line could not be turned into a [`TraceFrame`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum FrameError {
+    InvalidFormat(String),
+    UnknownDirection(String),
+}
+
+impl std::fmt::Display for FrameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            FrameError::InvalidFormat(d) => write!(f, "invalid frame: {d}"),
+            FrameError::UnknownDirection(d) => write!(f, "unknown direction '{d}'"),
+        }
+    }
+}
+
+/// Accepts the spellings understood by the F# CLI (`Parsing.parseDirection`).
+pub fn parse_direction(s: &str) -> Option<Direction> {
+    match s.trim().to_ascii_lowercase().as_str() {
+        "fromclient" | "client" | "c2a" | "c->a" => Some(Direction::FromClient),
+        "fromagent" | "agent" | "a2c" | "a->c" => Some(Direction::FromAgent),
+        _ => None,
+    }
+}
+
+#[derive(Deserialize)]
+struct FrameWire {
+    ts: Value,
+    direction: String,
+    json: String,
+}
+
+#[derive(Serialize)]
+struct FrameOut<'a> {
+    ts: String,
+    direction: String,
+    json: &'a str,
+}
+
+/// `ts` is an RFC 3339 string or a number of Unix milliseconds.
+fn parse_ts(ts: &Value) -> Option<DateTime<FixedOffset>> {
+    match ts {
+        Value::String(s) => DateTime::parse_from_rfc3339(s).ok(),
+        Value::Number(n) => DateTime::from_timestamp_millis(n.as_i64()?).map(|d| d.fixed_offset()),
+        _ => None,
+    }
+}
+
+/// Parse one trace line.
+pub fn parse_frame(line: &str) -> Result<TraceFrame, FrameError> {
+    let wire: FrameWire =
+        serde_json::from_str(line).map_err(|e| FrameError::InvalidFormat(e.to_string()))?;
+    let ts = parse_ts(&wire.ts)
+        .ok_or_else(|| FrameError::InvalidFormat(format!("invalid ts {}", wire.ts)))?;
+    let direction = parse_direction(&wire.direction)
+        .ok_or_else(|| FrameError::UnknownDirection(wire.direction.clone()))?;
+
+    Ok(TraceFrame {
+        ts,
+        direction,
+        json: wire.json,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_string_and_numeric_timestamps() {
+        let line = r#"{"ts":"2025-01-01T00:00:00.100Z","direction":"fromAgent","json":"{}"}"#;
+        let frame = parse_frame(line).unwrap();
+        assert_eq!(frame.direction, Direction::FromAgent);
+        assert_eq!(frame.ts.timestamp_millis(), 1_735_689_600_100);
+
+        let line = r#"{"ts":1735689600100,"direction":"c2a","json":"{}"}"#;
+        let frame = parse_frame(line).unwrap();
+        assert_eq!(frame.direction, Direction::FromClient);
+        assert_eq!(frame.ts.timestamp_millis(), 1_735_689_600_100);
+
+        assert_eq!(
+            frame.to_line(),
+            r#"{"ts":"2025-01-01T00:00:00.100Z","direction":"fromClient","json":"{}"}"#
+        );
+    }
+
+    #[test]
+    fn reports_bad_frames() {
+        assert!(matches!(
+            parse_frame(r#"{"ts":"2025-01-01T00:00:00Z","json":"{}"}"#),
+            Err(FrameError::InvalidFormat(_))
+        ));
+        assert_eq!(
+            parse_frame(r#"{"ts":"2025-01-01T00:00:00Z","direction":"sideways","json":"{}"}"#),
+            Err(FrameError::UnknownDirection("sideways".into()))
+        );
+    }
+}
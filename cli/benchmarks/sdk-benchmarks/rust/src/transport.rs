@@ -0,0 +1,412 @@
+//! Async message transports. Mirrors `runtime/src/Acp.Transport.fs`: a
+//! [`Transport`] sends and receives whole JSON-RPC messages as strings.
+//! [`MemoryTransport`] queues them for tests, [`StdioTransport`] frames them
+//! as NDJSON over any async byte streams, and [`DuplexTransport::pair`]
+//! connects two ends in-process.
+
+use crate::framing::{self, LineScan, DEFAULT_MAX_FRAME_BYTES};
+use crate::validation::ValidationFinding;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::Notify;
+
+/// Sends and receives JSON-RPC messages as raw JSON strings.
+///
+/// Methods take `&self` so one task can wait in `receive` while others send.
+pub trait Transport: Send + Sync {
+    /// Send one message. Fails with `BrokenPipe` once the transport is closed.
+    fn send(&self, message: String) -> impl Future<Output = io::Result<()>> + Send;
+
+    /// The next message, or `None` once the transport is closed or the input
+    /// has ended. Messages still queued when it closes are not delivered.
+    fn receive(&self) -> impl Future<Output = io::Result<Option<String>>> + Send;
+
+    /// Close the transport, waking any pending `receive`.
+    fn close(&self) -> impl Future<Output = io::Result<()>> + Send;
+}
+
+fn closed_error() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "transport is closed")
+}
+
+/// Lock `mutex`, recovering it if a holder panicked. Used for the transport
+/// queues and the connection state, whose critical sections each make one
+/// whole update (a queue push, a map insert, a codec step that leaves the
+/// state untouched on error), so a poisoned lock still guards valid data.
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Run `future` to completion on a fresh single-threaded runtime.
+#[cfg(test)]
+pub(crate) fn block_on<T>(future: impl Future<Output = T>) -> T {
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
+
+// -------------
+// Queues
+// -------------
+
+/// Messages waiting to be received, with a wakeup for blocked receivers.
+#[derive(Default)]
+struct Inbox {
+    messages: Mutex<VecDeque<String>>,
+    ready: Notify,
+}
+
+impl Inbox {
+    fn push(&self, message: String) {
+        lock(&self.messages).push_back(message);
+        self.ready.notify_waiters();
+    }
+
+    /// Wake receivers so they notice `closed`.
+    fn wake(&self) {
+        self.ready.notify_waiters();
+    }
+
+    async fn pop(&self, closed: &AtomicBool) -> Option<String> {
+        loop {
+            // Register before checking, so a push in between is not missed.
+            let notified = self.ready.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            if closed.load(Ordering::Acquire) {
+                return None;
+            }
+            if let Some(message) = lock(&self.messages).pop_front() {
+                return Some(message);
+            }
+            notified.await;
+        }
+    }
+}
+
+// -------------
+// MemoryTransport
+// -------------
+
+/// In-memory transport for tests: `enqueue` plays the remote side and
+/// `dequeue_sent` inspects what was sent.
+#[derive(Default)]
+pub struct MemoryTransport {
+    inbound: Inbox,
+    sent: Mutex<VecDeque<String>>,
+    closed: AtomicBool,
+}
+
+impl MemoryTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue a message for `receive`, as if the remote sent it. Ignored once
+    /// closed.
+    pub fn enqueue(&self, message: impl Into<String>) {
+        if !self.closed.load(Ordering::Acquire) {
+            self.inbound.push(message.into());
+        }
+    }
+
+    /// The oldest message passed to `send` and not yet dequeued.
+    pub fn dequeue_sent(&self) -> Option<String> {
+        lock(&self.sent).pop_front()
+    }
+}
+
+impl Transport for MemoryTransport {
+    async fn send(&self, message: String) -> io::Result<()> {
+        if self.closed.load(Ordering::Acquire) {
+            return Err(closed_error());
+        }
+        lock(&self.sent).push_back(message);
+        Ok(())
+    }
+
+    async fn receive(&self) -> io::Result<Option<String>> {
+        Ok(self.inbound.pop(&self.closed).await)
+    }
+
+    async fn close(&self) -> io::Result<()> {
+        self.closed.store(true, Ordering::Release);
+        self.inbound.wake();
+        Ok(())
+    }
+}
+
+// -------------
+// DuplexTransport
+// -------------
+
+/// One end of a connected in-process pair; see [`DuplexTransport::pair`].
+pub struct DuplexTransport {
+    inbound: Arc<Inbox>,
+    outbound: Arc<Inbox>,
+    /// Shared by both ends: closing either closes the connection.
+    closed: Arc<AtomicBool>,
+}
+
+impl DuplexTransport {
+    /// Two connected ends: messages sent on one are received on the other.
+    pub fn pair() -> (DuplexTransport, DuplexTransport) {
+        let a = Arc::new(Inbox::default());
+        let b = Arc::new(Inbox::default());
+        let closed = Arc::new(AtomicBool::new(false));
+        (
+            DuplexTransport {
+                inbound: a.clone(),
+                outbound: b.clone(),
+                closed: closed.clone(),
+            },
+            DuplexTransport {
+                inbound: b,
+                outbound: a,
+                closed,
+            },
+        )
+    }
+}
+
+impl Transport for DuplexTransport {
+    async fn send(&self, message: String) -> io::Result<()> {
+        if self.closed.load(Ordering::Acquire) {
+            return Err(closed_error());
+        }
+        self.outbound.push(message);
+        Ok(())
+    }
+
+    async fn receive(&self) -> io::Result<Option<String>> {
+        Ok(self.inbound.pop(&self.closed).await)
+    }
+
+    async fn close(&self) -> io::Result<()> {
+        self.closed.store(true, Ordering::Release);
+        self.inbound.wake();
+        self.outbound.wake();
+        Ok(())
+    }
+}
+
+// -------------
+// StdioTransport
+// -------------
+
+/// Newline-delimited JSON over an async reader and writer: the process's
+/// stdin/stdout, a child's pipes, or an in-memory stream. Lines are split and
+/// checked as by [`framing::NdjsonReader`].
+pub struct StdioTransport<R, W> {
+    reader: tokio::sync::Mutex<NdjsonInput<R>>,
+    writer: tokio::sync::Mutex<W>,
+    max_frame_bytes: usize,
+    closed: AtomicBool,
+    closing: Notify,
+}
+
+struct NdjsonInput<R> {
+    input: BufReader<R>,
+    buf: Vec<u8>,
+    /// Lines read so far, blank ones included.
+    line: usize,
+}
+
+/// The Transport-lane finding behind an error from [`StdioTransport`]'s
+/// `receive`: a frame over the size limit or not UTF-8. Reading can go on
+/// after one; any other error ends the stream.
+pub fn frame_finding(error: &io::Error) -> Option<&ValidationFinding> {
+    error.get_ref()?.downcast_ref()
+}
+
+fn invalid_frame(finding: ValidationFinding) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, finding)
+}
+
+impl StdioTransport<tokio::io::Stdin, tokio::io::Stdout> {
+    /// This process's stdin and stdout.
+    pub fn stdio() -> Self {
+        StdioTransport::new(
+            tokio::io::stdin(),
+            tokio::io::stdout(),
+            DEFAULT_MAX_FRAME_BYTES,
+        )
+    }
+}
+
+impl<R, W> StdioTransport<R, W>
+where
+    R: AsyncRead + Unpin + Send,
+    W: AsyncWrite + Unpin + Send,
+{
+    /// Lines over `max_frame_bytes` are skipped and reported as errors
+    /// carrying a [`frame_finding`].
+    pub fn new(input: R, output: W, max_frame_bytes: usize) -> Self {
+        StdioTransport {
+            reader: tokio::sync::Mutex::new(NdjsonInput {
+                input: BufReader::new(input),
+                buf: Vec::new(),
+                line: 0,
+            }),
+            writer: tokio::sync::Mutex::new(output),
+            max_frame_bytes,
+            closed: AtomicBool::new(false),
+            closing: Notify::new(),
+        }
+    }
+
+    /// The next non-blank line, without its line ending.
+    async fn read_frame(&self) -> io::Result<Option<String>> {
+        let mut reader = self.reader.lock().await;
+        let NdjsonInput { input, buf, line } = &mut *reader;
+        loop {
+            buf.clear();
+            let mut scan = LineScan::default();
+            loop {
+                let (used, complete) =
+                    scan.feed(input.fill_buf().await?, buf, self.max_frame_bytes);
+                input.consume(used);
+                if complete {
+                    break;
+                }
+            }
+            let Some(len) = scan.finish() else {
+                return Ok(None);
+            };
+            *line += 1;
+            match framing::ndjson_line(buf, len, *line, self.max_frame_bytes) {
+                Err(finding) => return Err(invalid_frame(*finding)),
+                Ok(false) => continue,
+                Ok(true) => {}
+            }
+            return framing::utf8_frame(buf, *line)
+                .map(|frame| Some(frame.text.to_owned()))
+                .map_err(|finding| invalid_frame(*finding));
+        }
+    }
+}
+
+impl<R, W> Transport for StdioTransport<R, W>
+where
+    R: AsyncRead + Unpin + Send,
+    W: AsyncWrite + Unpin + Send,
+{
+    async fn send(&self, message: String) -> io::Result<()> {
+        if self.closed.load(Ordering::Acquire) {
+            return Err(closed_error());
+        }
+        // A raw newline would split the message into two frames.
+        if message.contains('\n') {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "NDJSON messages must be a single line",
+            ));
+        }
+        let mut writer = self.writer.lock().await;
+        writer.write_all(message.as_bytes()).await?;
+        writer.write_all(b"\n").await?;
+        writer.flush().await
+    }
+
+    async fn receive(&self) -> io::Result<Option<String>> {
+        let closing = self.closing.notified();
+        tokio::pin!(closing);
+        closing.as_mut().enable();
+        if self.closed.load(Ordering::Acquire) {
+            return Ok(None);
+        }
+        tokio::select! {
+            frame = self.read_frame() => frame,
+            () = closing => Ok(None),
+        }
+    }
+
+    async fn close(&self) -> io::Result<()> {
+        self.closed.store(true, Ordering::Release);
+        self.closing.notify_waiters();
+        self.writer.lock().await.shutdown().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_transport_queues_both_directions() {
+        block_on(async {
+            let transport = MemoryTransport::new();
+            transport.enqueue("from remote");
+            transport.send("to remote".into()).await.unwrap();
+            assert_eq!(
+                transport.receive().await.unwrap().as_deref(),
+                Some("from remote")
+            );
+            assert_eq!(transport.dequeue_sent().as_deref(), Some("to remote"));
+
+            transport.close().await.unwrap();
+            transport.enqueue("ignored");
+            assert_eq!(transport.receive().await.unwrap(), None);
+        });
+    }
+
+    #[test]
+    fn duplex_pair_delivers_both_ways_until_closed() {
+        block_on(async {
+            let (client, agent) = DuplexTransport::pair();
+            let receive = async { agent.receive().await.unwrap() };
+            let send = async { client.send("ping".into()).await.unwrap() };
+            let (received, ()) = tokio::join!(receive, send);
+            assert_eq!(received.as_deref(), Some("ping"));
+
+            agent.send("pong".into()).await.unwrap();
+            assert_eq!(client.receive().await.unwrap().as_deref(), Some("pong"));
+
+            // A receive waiting on one end wakes when the other closes.
+            let (received, ()) =
+                tokio::join!(client.receive(), async { agent.close().await.unwrap() });
+            assert_eq!(received.unwrap(), None);
+            assert_eq!(
+                client.send("late".into()).await.unwrap_err().kind(),
+                io::ErrorKind::BrokenPipe
+            );
+        });
+    }
+
+    #[test]
+    fn stdio_frames_ndjson_and_skips_oversized_lines() {
+        block_on(async {
+            let input: &[u8] = b"\n{\"a\":1}\r\n{\"too\":\"long\"}\n{\"b\":2}";
+            let mut output = Vec::new();
+            let transport = StdioTransport::new(input, &mut output, 8);
+            assert_eq!(
+                transport.receive().await.unwrap().as_deref(),
+                Some(r#"{"a":1}"#)
+            );
+            let oversized = transport.receive().await.unwrap_err();
+            let finding = frame_finding(&oversized).unwrap();
+            assert_eq!(
+                finding.failure.as_ref().unwrap().code,
+                "ACP.TRANSPORT.MAX_MESSAGE_BYTES_EXCEEDED"
+            );
+            assert_eq!(finding.trace_index, Some(3));
+            assert_eq!(
+                transport.receive().await.unwrap().as_deref(),
+                Some(r#"{"b":2}"#)
+            );
+            assert_eq!(transport.receive().await.unwrap(), None);
+
+            transport.send(r#"{"c":3}"#.into()).await.unwrap();
+            assert!(transport.send("a\nb".into()).await.is_err());
+            transport.close().await.unwrap();
+            drop(transport);
+            assert_eq!(output, b"{\"c\":3}\n");
+        });
+    }
+}
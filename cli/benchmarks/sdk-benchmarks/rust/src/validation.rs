@@ -0,0 +1,596 @@
+//! Structured validation findings over a decoded message trace.
+//! Mirrors `sentinel/src/Acp.Validation.fs`: protocol errors from the
+//! [`protocol`](crate::protocol) spec plus session-lane invariants.
+
+use crate::domain::*;
+use crate::protocol::{InitializedContext, Phase, ProtocolError, Spec};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Assurance lanes – where in the assurance stack a finding lives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Lane {
+    /// ACP/JSON-RPC shape & sequencing
+    Protocol,
+    /// Session/turn invariants
+    Session,
+    /// Tool call execution & resources
+    ToolSurface,
+    /// Stdio framing, timeouts, truncation
+    Transport,
+    /// Eval judges running alongside the sentinel
+    Eval,
+    /// Agent/client local checks (logging, perf, etc.)
+    Implementation,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+/// Location / subject of a validation observation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum Subject {
+    Connection,
+    Session {
+        session_id: SessionId,
+    },
+    PromptTurn {
+        session_id: SessionId,
+        turn_ordinal: usize,
+    },
+    MessageAt {
+        index: usize,
+    },
+    ToolCall {
+        tool_call_id: String,
+    },
+}
+
+/// Validator-detected violation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ValidationFailure {
+    /// Stable ID, e.g. `ACP.PROTOCOL.UNEXPECTED_MESSAGE`.
+    pub code: String,
+    pub message: String,
+    pub subject: Subject,
+}
+
+/// Structured validation event (a failure or a positive/neutral note).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidationFinding {
+    pub lane: Lane,
+    pub severity: Severity,
+    pub subject: Subject,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure: Option<ValidationFailure>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<SessionId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace_index: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+/// The failure's code and message, or the note for a neutral finding.
+impl fmt::Display for ValidationFinding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.failure, &self.note) {
+            (Some(failure), _) => write!(f, "{}: {}", failure.code, failure.message),
+            (None, Some(note)) => f.write_str(note),
+            (None, None) => write!(f, "{:?} finding", self.lane),
+        }
+    }
+}
+
+impl std::error::Error for ValidationFinding {}
+
+impl ValidationFinding {
+    /// A session-lane failure anchored at `trace_index`.
+    fn session(
+        severity: Severity,
+        subject: Subject,
+        session_id: &SessionId,
+        trace_index: usize,
+        code: &str,
+        message: &str,
+        note: Option<String>,
+    ) -> Self {
+        ValidationFinding {
+            lane: Lane::Session,
+            severity,
+            subject: subject.clone(),
+            failure: Some(ValidationFailure {
+                code: code.to_owned(),
+                message: message.to_owned(),
+                subject,
+            }),
+            session_id: Some(session_id.clone()),
+            trace_index: Some(trace_index),
+            note,
+        }
+    }
+}
+
+// -----------------
+// Protocol lane
+// -----------------
+
+fn session_from_message(msg: &Message) -> Option<&SessionId> {
+    use AgentToClientMessage as A;
+    use ClientToAgentMessage as C;
+
+    match msg {
+        Message::FromClient(c) => match c {
+            C::SessionPrompt(p) => Some(&p.session_id),
+            C::SessionCancel(p) => Some(&p.session_id),
+            C::SessionLoad(p) => Some(&p.session_id),
+            C::SessionSetMode(p) => Some(&p.session_id),
+            C::FsReadTextFileError(req, _) => Some(&req.session_id),
+            C::FsWriteTextFileError(req, _) => Some(&req.session_id),
+            C::SessionRequestPermissionError(req, _) => Some(&req.session_id),
+            C::TerminalCreateError(req, _) => Some(&req.session_id),
+            C::TerminalOutputError(req, _) => Some(&req.session_id),
+            C::TerminalWaitForExitError(req, _) => Some(&req.session_id),
+            C::TerminalKillError(req, _) => Some(&req.session_id),
+            C::TerminalReleaseError(req, _) => Some(&req.session_id),
+            _ => None,
+        },
+        Message::FromAgent(a) => match a {
+            A::SessionNewResult(r) => Some(&r.session_id),
+            A::SessionPromptResult(r) => Some(&r.session_id),
+            A::SessionPromptError(req, _) => Some(&req.session_id),
+            A::SessionLoadResult(r) => Some(&r.session_id),
+            A::SessionLoadError(req, _) => Some(&req.session_id),
+            A::SessionSetModeResult(r) => Some(&r.session_id),
+            A::SessionSetModeError(req, _) => Some(&req.session_id),
+            A::SessionUpdate(u) => Some(&u.session_id),
+            A::SessionRequestPermissionRequest(r) => Some(&r.session_id),
+            _ => None,
+        },
+    }
+}
+
+/// Map a protocol error raised at `trace_index` to a protocol-lane finding.
+pub fn from_protocol_error(
+    ctx: Option<&InitializedContext>,
+    msg: &Message,
+    error: &ProtocolError,
+    trace_index: Option<usize>,
+) -> ValidationFinding {
+    let sid_from_msg = session_from_message(msg);
+
+    let subject_from_error = match error {
+        ProtocolError::UnknownSession(sid)
+        | ProtocolError::SessionAlreadyExists(sid)
+        | ProtocolError::PromptAlreadyInFlight(sid)
+        | ProtocolError::NoPromptInFlight(sid) => Subject::Session {
+            session_id: sid.clone(),
+        },
+        ProtocolError::UnexpectedMessage { .. } => match trace_index {
+            Some(index) => Subject::MessageAt { index },
+            None => Subject::Connection,
+        },
+        ProtocolError::DuplicateInitialize | ProtocolError::InitializeResultWithoutRequest => {
+            Subject::Connection
+        }
+    };
+
+    let session_id = match (&subject_from_error, sid_from_msg) {
+        (Subject::Session { session_id }, _) => Some(session_id.clone()),
+        (_, Some(sid)) => Some(sid.clone()),
+        // With exactly one session open, attribute connection-level errors to it.
+        _ => ctx
+            .filter(|c| c.sessions.len() == 1)
+            .and_then(|c| c.sessions.keys().next().cloned()),
+    };
+
+    let subject = match (subject_from_error, sid_from_msg) {
+        (Subject::Connection, Some(sid)) => Subject::Session {
+            session_id: sid.clone(),
+        },
+        (s, _) => s,
+    };
+
+    ValidationFinding {
+        lane: Lane::Protocol,
+        severity: Severity::Error,
+        subject: subject.clone(),
+        failure: Some(ValidationFailure {
+            code: error.code().to_owned(),
+            message: error.to_string(),
+            subject,
+        }),
+        session_id,
+        trace_index,
+        note: None,
+    }
+}
+
+// -----------------
+// Session-lane invariants
+// -----------------
+
+fn is_prompt_for(msg: &Message, sid: &SessionId) -> bool {
+    matches!(msg, Message::FromClient(ClientToAgentMessage::SessionPrompt(p)) if &p.session_id == sid)
+}
+
+/// Session-lane invariant: if a cancel occurs between the first prompt and its
+/// result, the stopReason must be `cancelled`.
+fn check_session_cancel_invariant(sid: &SessionId, messages: &[Message]) -> Vec<ValidationFinding> {
+    use AgentToClientMessage as A;
+
+    let Some(prompt_idx) = messages.iter().position(|m| is_prompt_for(m, sid)) else {
+        return vec![];
+    };
+
+    let result = messages
+        .iter()
+        .enumerate()
+        .skip(prompt_idx + 1)
+        .find_map(|(i, m)| match m {
+            Message::FromAgent(A::SessionPromptResult(r)) if &r.session_id == sid => {
+                Some((i, Some(r.stop_reason)))
+            }
+            Message::FromAgent(A::SessionPromptError(req, _)) if &req.session_id == sid => {
+                Some((i, None))
+            }
+            _ => None,
+        });
+    let Some((result_idx, Some(stop_reason))) = result else {
+        return vec![];
+    };
+
+    let cancel_idx = (prompt_idx + 1..result_idx).find(|&i| {
+        matches!(&messages[i], Message::FromClient(ClientToAgentMessage::SessionCancel(c)) if &c.session_id == sid)
+    });
+
+    match cancel_idx {
+        Some(cancel_idx) if stop_reason != StopReason::Cancelled => {
+            let turn_ordinal = messages[..prompt_idx]
+                .iter()
+                .filter(|m| is_prompt_for(m, sid))
+                .count();
+            vec![ValidationFinding::session(
+                Severity::Error,
+                Subject::PromptTurn {
+                    session_id: sid.clone(),
+                    turn_ordinal,
+                },
+                sid,
+                result_idx,
+                "ACP.SESSION.CANCEL_MISMATCH",
+                "SessionCancel was sent, but the prompt result stopReason is not Cancelled.",
+                Some(format!(
+                    "promptIdx={prompt_idx}; cancelIdx={cancel_idx}; resultIdx={result_idx}"
+                )),
+            )]
+        }
+        _ => vec![],
+    }
+}
+
+/// Session-lane invariants:
+/// - At most one prompt in flight per session.
+/// - A prompt result or error must not appear before any prompt.
+fn check_session_prompt_concurrency(
+    sid: &SessionId,
+    messages: &[Message],
+) -> Vec<ValidationFinding> {
+    use AgentToClientMessage as A;
+
+    let mut open_prompts = 0usize;
+    let mut prompt_ordinal = 0usize;
+    let mut findings = Vec::new();
+
+    let turn = |turn_ordinal| Subject::PromptTurn {
+        session_id: sid.clone(),
+        turn_ordinal,
+    };
+
+    for (idx, msg) in messages.iter().enumerate() {
+        let closes = match msg {
+            _ if is_prompt_for(msg, sid) => {
+                if open_prompts > 0 {
+                    findings.push(ValidationFinding::session(
+                        Severity::Error,
+                        turn(prompt_ordinal),
+                        sid,
+                        idx,
+                        "ACP.SESSION.MULTIPLE_PROMPTS_IN_FLIGHT",
+                        "A new SessionPrompt was sent while a previous prompt is still in flight.",
+                        Some(format!("index={idx}; openPrompts={open_prompts}")),
+                    ));
+                }
+                open_prompts += 1;
+                prompt_ordinal += 1;
+                continue;
+            }
+            Message::FromAgent(A::SessionPromptResult(r)) if &r.session_id == sid => (
+                "ACP.SESSION.RESULT_WITHOUT_PROMPT",
+                "SessionPromptResult was sent without a prior in-flight SessionPrompt.",
+            ),
+            Message::FromAgent(A::SessionPromptError(req, _)) if &req.session_id == sid => (
+                "ACP.SESSION.ERROR_WITHOUT_PROMPT",
+                "SessionPrompt error response was sent without a prior in-flight SessionPrompt.",
+            ),
+            // A cancel does not close the prompt; ACP still requires a result.
+            _ => continue,
+        };
+
+        if open_prompts == 0 {
+            let (code, message) = closes;
+            findings.push(ValidationFinding::session(
+                Severity::Error,
+                turn(0),
+                sid,
+                idx,
+                code,
+                message,
+                Some(format!("index={idx}; openPrompts={open_prompts}")),
+            ));
+        } else {
+            open_prompts -= 1;
+        }
+    }
+
+    findings
+}
+
+/// Session-lane invariants for session modes, once a mode state is known:
+/// - set_mode requests must target a mode in availableModes.
+/// - current_mode_update must report a mode in availableModes.
+fn check_session_modes(sid: &SessionId, messages: &[Message]) -> Vec<ValidationFinding> {
+    use AgentToClientMessage as A;
+    use ClientToAgentMessage as C;
+
+    let subject = Subject::Session {
+        session_id: sid.clone(),
+    };
+    let mut mode_state: Option<SessionModeState> = None;
+    let mut findings = Vec::new();
+
+    let is_known = |ms: &SessionModeState, mode_id: &SessionModeId| {
+        ms.available_modes.iter().any(|m| &m.id == mode_id)
+    };
+
+    for (idx, msg) in messages.iter().enumerate() {
+        match msg {
+            Message::FromAgent(A::SessionNewResult(r)) if &r.session_id == sid => {
+                mode_state = r.modes.clone();
+            }
+            Message::FromAgent(A::SessionLoadResult(r)) if &r.session_id == sid => {
+                mode_state = r.modes.clone();
+            }
+            Message::FromClient(C::SessionSetMode(p)) if &p.session_id == sid => match &mode_state {
+                None => findings.push(ValidationFinding::session(
+                    Severity::Warning,
+                    subject.clone(),
+                    sid,
+                    idx,
+                    "ACP.SESSION.SET_MODE_WITHOUT_MODES",
+                    "session/set_mode was sent, but the agent did not advertise any session modes.",
+                    None,
+                )),
+                Some(ms) if !is_known(ms, &p.mode_id) => findings.push(ValidationFinding::session(
+                    Severity::Error,
+                    subject.clone(),
+                    sid,
+                    idx,
+                    "ACP.SESSION.INVALID_MODE_ID",
+                    "session/set_mode requested a modeId that is not present in availableModes.",
+                    Some(format!("modeId={}", p.mode_id.0)),
+                )),
+                Some(_) => {}
+            },
+            Message::FromAgent(A::SessionSetModeResult(r)) if &r.session_id == sid => {
+                if let Some(ms) = &mut mode_state {
+                    ms.current_mode_id = r.mode_id.clone();
+                }
+            }
+            Message::FromAgent(A::SessionUpdate(u)) if &u.session_id == sid => {
+                if let (SessionUpdate::CurrentModeUpdate(m), Some(ms)) =
+                    (&u.update, &mut mode_state)
+                {
+                    if !is_known(ms, &m.current_mode_id) {
+                        findings.push(ValidationFinding::session(
+                            Severity::Error,
+                            subject.clone(),
+                            sid,
+                            idx,
+                            "ACP.SESSION.CURRENT_MODE_NOT_IN_AVAILABLE_MODES",
+                            "current_mode_update reported a currentModeId that is not present in availableModes.",
+                            Some(format!("currentModeId={}", m.current_mode_id.0)),
+                        ));
+                    }
+                    ms.current_mode_id = m.current_mode_id.clone();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    findings
+}
+
+// -----------------
+// Transport lane
+// -----------------
+
+/// A transport-lane error on the connection, e.g. from stdio framing.
+pub fn transport_error(
+    code: &str,
+    message: String,
+    trace_index: Option<usize>,
+) -> ValidationFinding {
+    let subject = Subject::Connection;
+    ValidationFinding {
+        lane: Lane::Transport,
+        severity: Severity::Error,
+        subject: subject.clone(),
+        failure: Some(ValidationFailure {
+            code: code.to_owned(),
+            message,
+            subject,
+        }),
+        session_id: None,
+        trace_index,
+        note: None,
+    }
+}
+
+/// Mirrors `Validation.Transport.validateSize`: `limit` is the profile's
+/// `maxMessageBytes`, and no limit means no finding.
+pub fn validate_size(
+    limit: Option<usize>,
+    trace_index: Option<usize>,
+    actual_bytes: usize,
+) -> Option<ValidationFinding> {
+    match limit {
+        Some(limit) if actual_bytes > limit => Some(transport_error(
+            "ACP.TRANSPORT.MAX_MESSAGE_BYTES_EXCEEDED",
+            format!("Message size {actual_bytes} bytes exceeds limit {limit}"),
+            trace_index,
+        )),
+        _ => None,
+    }
+}
+
+// -----------------
+// Runner
+// -----------------
+
+/// Outcome of folding a trace through a spec with validation.
+#[derive(Debug, Clone)]
+pub struct SpecRunResult {
+    pub final_phase: Result<Phase, ProtocolError>,
+    pub findings: Vec<ValidationFinding>,
+}
+
+/// Sessions in order of first appearance.
+fn sessions_in(messages: &[Message]) -> Vec<&SessionId> {
+    let mut sessions: Vec<&SessionId> = Vec::new();
+    for sid in messages.iter().filter_map(session_from_message) {
+        if !sessions.contains(&sid) {
+            sessions.push(sid);
+        }
+    }
+    sessions
+}
+
+/// Step `messages` through `spec` until the first protocol error, then run the
+/// session-lane invariants for every session seen in the trace.
+pub fn run_with_validation(
+    spec: &Spec<Phase, Message, ProtocolError>,
+    messages: &[Message],
+) -> SpecRunResult {
+    let mut phase = spec.initial.clone();
+    let mut findings = Vec::new();
+    let mut error = None;
+
+    for (idx, msg) in messages.iter().enumerate() {
+        if let Err(e) = (spec.step)(&mut phase, msg) {
+            let ctx = match &phase {
+                Phase::Ready(ctx) => Some(ctx),
+                _ => None,
+            };
+            findings.push(from_protocol_error(ctx, msg, &e, Some(idx)));
+            error = Some(e);
+            break;
+        }
+    }
+
+    for sid in sessions_in(messages) {
+        findings.extend(check_session_cancel_invariant(sid, messages));
+        findings.extend(check_session_prompt_concurrency(sid, messages));
+        findings.extend(check_session_modes(sid, messages));
+    }
+
+    SpecRunResult {
+        final_phase: match error {
+            Some(e) => Err(e),
+            None => Ok(phase),
+        },
+        findings,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::{self, CodecState};
+    use crate::protocol;
+
+    fn messages(lines: &[&str]) -> Vec<Message> {
+        let mut state = CodecState::default();
+        lines
+            .iter()
+            .map(|json| {
+                let direction = codec::infer_direction(json);
+                codec::decode(direction, &mut state, json).unwrap().message
+            })
+            .collect()
+    }
+
+    const HANDSHAKE: [&str; 4] = [
+        r#"{"jsonrpc":"2.0","method":"initialize","params":{"protocolVersion":1},"id":1}"#,
+        r#"{"jsonrpc":"2.0","result":{"protocolVersion":1},"id":1}"#,
+        r#"{"jsonrpc":"2.0","method":"session/new","params":{"cwd":"/","mcpServers":[]},"id":2}"#,
+        r#"{"jsonrpc":"2.0","result":{"sessionId":"s1","modes":{"currentModeId":"ask","availableModes":[{"id":"ask","name":"Ask"}]}},"id":2}"#,
+    ];
+
+    fn codes(result: &SpecRunResult) -> Vec<&str> {
+        result
+            .findings
+            .iter()
+            .filter_map(|f| f.failure.as_ref().map(|x| x.code.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn cancel_must_end_with_cancelled_stop_reason() {
+        let mut lines = HANDSHAKE.to_vec();
+        lines.extend([
+            r#"{"jsonrpc":"2.0","method":"session/prompt","params":{"sessionId":"s1","prompt":[]},"id":3}"#,
+            r#"{"jsonrpc":"2.0","method":"session/cancel","params":{"sessionId":"s1"}}"#,
+            r#"{"jsonrpc":"2.0","result":{"stopReason":"end_turn"},"id":3}"#,
+        ]);
+        let result = run_with_validation(&protocol::spec(), &messages(&lines));
+        assert!(result.final_phase.is_ok());
+        assert_eq!(codes(&result), ["ACP.SESSION.CANCEL_MISMATCH"]);
+        assert_eq!(result.findings[0].trace_index, Some(6));
+    }
+
+    #[test]
+    fn reports_protocol_error_and_unknown_mode() {
+        let mut lines = HANDSHAKE.to_vec();
+        lines.extend([
+            r#"{"jsonrpc":"2.0","method":"session/set_mode","params":{"sessionId":"s1","modeId":"yolo"},"id":3}"#,
+            r#"{"jsonrpc":"2.0","method":"session/prompt","params":{"sessionId":"s1","prompt":[]},"id":4}"#,
+            r#"{"jsonrpc":"2.0","method":"session/prompt","params":{"sessionId":"s1","prompt":[]},"id":5}"#,
+        ]);
+        let result = run_with_validation(&protocol::spec(), &messages(&lines));
+        assert!(result.final_phase.is_err());
+        assert_eq!(
+            codes(&result),
+            [
+                "ACP.PROTOCOL.PROMPT_ALREADY_IN_FLIGHT",
+                "ACP.SESSION.MULTIPLE_PROMPTS_IN_FLIGHT",
+                "ACP.SESSION.INVALID_MODE_ID",
+            ]
+        );
+        let json = serde_json::to_value(&result.findings[0]).unwrap();
+        assert_eq!(json["lane"], "Protocol");
+        assert_eq!(json["subject"]["kind"], "session");
+        assert_eq!(json["sessionId"], "s1");
+    }
+}
@@ -0,0 +1,107 @@
+//! Codec properties: typed messages survive encode/decode, and the decoder
+//! rejects anything else with an error rather than a panic.
+
+use crate::generators::{exchange, json_value, request_id};
+use acp_benchmark::codec::{self, CodecState, Direction};
+use proptest::collection::vec;
+use proptest::prelude::*;
+use serde_json::{json, Value};
+
+const DIRECTIONS: [Direction; 2] = [Direction::FromClient, Direction::FromAgent];
+
+/// Methods the decoder routes specially, plus one it does not know.
+const METHODS: [&str; 20] = [
+    "initialize",
+    "proxy/initialize",
+    "authenticate",
+    "session/new",
+    "session/load",
+    "session/prompt",
+    "session/set_mode",
+    "session/cancel",
+    "session/update",
+    "session/request_permission",
+    "fs/read_text_file",
+    "fs/write_text_file",
+    "terminal/create",
+    "terminal/output",
+    "terminal/wait_for_exit",
+    "terminal/kill",
+    "terminal/release",
+    "proxy/successor",
+    "_ext/thing",
+    "unknown/method",
+];
+
+/// A JSON-RPC-shaped envelope with arbitrary members, so decoding gets past
+/// the envelope checks into the typed params and results.
+fn envelope() -> impl Strategy<Value = String> {
+    (
+        prop::sample::select(METHODS.to_vec()),
+        json_value(),
+        prop::option::of(request_id()),
+        0u8..3,
+    )
+        .prop_map(|(method, payload, id, shape)| {
+            let mut envelope = json!({ "jsonrpc": "2.0" });
+            match shape {
+                0 => {
+                    envelope["method"] = json!(method);
+                    envelope["params"] = payload;
+                }
+                1 => envelope["result"] = payload,
+                _ => envelope["error"] = payload,
+            }
+            if let Some(id) = id {
+                envelope["id"] = serde_json::to_value(id).unwrap();
+            }
+            envelope.to_string()
+        })
+}
+
+proptest! {
+    #[test]
+    fn exchanges_round_trip_through_encode_and_decode(exchanges in vec(exchange(), 1..8)) {
+        let mut state = CodecState::default();
+        for frame in exchanges.into_iter().flatten() {
+            let json = codec::encode(frame.id.as_ref(), &frame.message)
+                .map_err(|e| TestCaseError::fail(format!("encode: {e}")))?;
+            let decoded = codec::decode(frame.direction, &mut state, &json)
+                .map_err(|e| TestCaseError::fail(format!("decode: {e}\n{json}")))?;
+            prop_assert_eq!(&decoded.id, &frame.id, "{}", json);
+            prop_assert_eq!(&decoded.message, &frame.message, "{}", json);
+        }
+        prop_assert!(state.pending_client_requests.is_empty());
+        prop_assert!(state.pending_agent_requests.is_empty());
+    }
+
+    #[test]
+    fn decode_never_panics_on_arbitrary_text(text in any::<String>()) {
+        for direction in DIRECTIONS {
+            let _ = codec::decode(direction, &mut CodecState::default(), &text);
+        }
+    }
+
+    #[test]
+    fn decode_never_panics_on_arbitrary_json(value in json_value()) {
+        let text = value.to_string();
+        for direction in DIRECTIONS {
+            let _ = codec::decode(direction, &mut CodecState::default(), &text);
+        }
+    }
+
+    #[test]
+    fn decode_never_panics_on_arbitrary_envelopes(lines in vec(envelope(), 1..8)) {
+        // Shared state, so responses may meet pending requests.
+        let mut state = CodecState::default();
+        for line in &lines {
+            let direction = codec::infer_direction(line);
+            if let Ok(decoded) = codec::decode(direction, &mut state, line) {
+                // Whatever decodes must encode to valid JSON again.
+                if let Ok(json) = codec::encode(decoded.id.as_ref(), &decoded.message) {
+                    prop_assert!(serde_json::from_str::<Value>(&json).is_ok(), "{}", json);
+                }
+            }
+        }
+    }
+}
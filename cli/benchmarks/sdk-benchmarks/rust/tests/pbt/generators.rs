@@ -0,0 +1,1154 @@
+//! Generators for ACP domain types. Mirrors `sentinel/tests/Pbt/Generators.fs`,
+//! extended to every message variant: [`exchange`] yields well-formed JSON-RPC
+//! exchanges (request then response, or a notification) in wire order.
+
+use acp_benchmark::codec::Direction;
+use acp_benchmark::domain::*;
+use proptest::collection::{btree_map, vec};
+use proptest::option;
+use proptest::prelude::*;
+use serde_json::{Map, Value};
+
+// -----------------
+// Basic primitives
+// -----------------
+
+pub fn small_string() -> impl Strategy<Value = String> {
+    "[a-z0-9_-]{1,12}"
+}
+
+/// Arbitrary text, including escapes and non-ASCII.
+fn text() -> impl Strategy<Value = String> {
+    "(?s).{0,24}"
+}
+
+/// Mostly a small fixed pool, so traces reuse sessions.
+pub fn session_id() -> impl Strategy<Value = SessionId> {
+    prop_oneof![
+        3 => prop::sample::select(vec!["s1", "s2", "s3"]).prop_map(|s| SessionId(s.to_owned())),
+        1 => small_string().prop_map(SessionId),
+    ]
+}
+
+pub fn request_id() -> impl Strategy<Value = RequestId> {
+    prop_oneof![
+        any::<i64>().prop_map(RequestId::Number),
+        small_string().prop_map(RequestId::String),
+    ]
+}
+
+pub fn stop_reason() -> impl Strategy<Value = StopReason> {
+    prop_oneof![
+        5 => Just(StopReason::EndTurn),
+        3 => Just(StopReason::Cancelled),
+        1 => Just(StopReason::MaxTokens),
+        1 => Just(StopReason::MaxTurnRequests),
+        1 => Just(StopReason::Refusal),
+    ]
+}
+
+/// Any JSON value. Numbers are integers: float text does not always
+/// re-parse to the same `f64` without serde_json's `float_roundtrip`.
+pub fn json_value() -> impl Strategy<Value = Value> {
+    let leaf = prop_oneof![
+        Just(Value::Null),
+        any::<bool>().prop_map(Value::Bool),
+        any::<i64>().prop_map(Value::from),
+        text().prop_map(Value::String),
+    ];
+    leaf.prop_recursive(3, 16, 4, |inner| {
+        prop_oneof![
+            vec(inner.clone(), 0..4).prop_map(Value::Array),
+            btree_map(small_string(), inner, 0..4)
+                .prop_map(|m| Value::Object(m.into_iter().collect())),
+        ]
+    })
+}
+
+/// Non-null JSON: `null` and absent are the same thing for optional fields.
+fn json_present() -> impl Strategy<Value = Value> {
+    json_value().prop_filter("non-null", |v| !v.is_null())
+}
+
+fn json_object() -> impl Strategy<Value = Map<String, Value>> {
+    btree_map(small_string(), json_value(), 0..4).prop_map(|m| m.into_iter().collect())
+}
+
+fn ext_method() -> impl Strategy<Value = String> {
+    small_string().prop_map(|s| format!("_{s}"))
+}
+
+pub fn rpc_error() -> impl Strategy<Value = JsonRpcError> {
+    (any::<i32>(), text(), option::of(json_present())).prop_map(|(code, message, data)| {
+        JsonRpcError {
+            code,
+            message,
+            data,
+        }
+    })
+}
+
+// -----------------
+// Capabilities and setup
+// -----------------
+
+fn implementation_info() -> impl Strategy<Value = ImplementationInfo> {
+    (small_string(), option::of(text()), small_string()).prop_map(|(name, title, version)| {
+        ImplementationInfo {
+            name,
+            title,
+            version,
+        }
+    })
+}
+
+pub fn initialize_params() -> impl Strategy<Value = InitializeParams> {
+    (
+        any::<u16>(),
+        any::<[bool; 3]>(),
+        option::of(implementation_info()),
+    )
+        .prop_map(|(protocol_version, [read, write, terminal], client_info)| {
+            InitializeParams {
+                protocol_version,
+                client_capabilities: ClientCapabilities {
+                    fs: FileSystemCapabilities {
+                        read_text_file: read,
+                        write_text_file: write,
+                    },
+                    terminal,
+                },
+                client_info,
+            }
+        })
+}
+
+pub fn initialize_result() -> impl Strategy<Value = InitializeResult> {
+    (
+        any::<u16>(),
+        any::<[bool; 6]>(),
+        option::of(implementation_info()),
+        vec(
+            (small_string(), text(), option::of(text())).prop_map(|(id, name, description)| {
+                AuthMethod {
+                    id,
+                    name,
+                    description,
+                }
+            }),
+            0..3,
+        ),
+    )
+        .prop_map(|(protocol_version, caps, agent_info, auth_methods)| {
+            let [load, http, sse, audio, image, embedded] = caps;
+            InitializeResult {
+                protocol_version,
+                agent_capabilities: AgentCapabilities {
+                    load_session: load,
+                    mcp_capabilities: McpCapabilities { http, sse },
+                    prompt_capabilities: PromptCapabilities {
+                        audio,
+                        image,
+                        embedded_context: embedded,
+                    },
+                    session_capabilities: SessionCapabilities {},
+                },
+                agent_info,
+                auth_methods,
+            }
+        })
+}
+
+fn name_value() -> impl Strategy<Value = (String, String)> {
+    (small_string(), text())
+}
+
+fn mcp_server() -> impl Strategy<Value = McpServer> {
+    let headers = || {
+        vec(
+            name_value().prop_map(|(name, value)| HttpHeader { name, value }),
+            0..3,
+        )
+    };
+    prop_oneof![
+        (small_string(), text(), headers()).prop_map(|(name, url, headers)| {
+            McpServer::Http(McpServerHttp { name, url, headers })
+        }),
+        (small_string(), text(), headers()).prop_map(|(name, url, headers)| {
+            McpServer::Sse(McpServerSse { name, url, headers })
+        }),
+        (
+            small_string(),
+            text(),
+            vec(text(), 0..3),
+            vec(
+                name_value().prop_map(|(name, value)| EnvVariable { name, value }),
+                0..3
+            ),
+        )
+            .prop_map(
+                |(name, command, args, env)| McpServer::Stdio(McpServerStdio {
+                    name,
+                    command,
+                    args,
+                    env,
+                })
+            ),
+        (small_string(), small_string())
+            .prop_map(|(name, uuid)| McpServer::Acp(McpServerAcp { name, uuid })),
+    ]
+}
+
+pub fn mode_id() -> impl Strategy<Value = SessionModeId> {
+    prop::sample::select(vec!["code", "ask", "architect"]).prop_map(|m| SessionModeId(m.into()))
+}
+
+fn session_mode_state() -> impl Strategy<Value = SessionModeState> {
+    (
+        mode_id(),
+        vec(
+            (mode_id(), text(), option::of(text())).prop_map(|(id, name, description)| {
+                SessionMode {
+                    id,
+                    name,
+                    description,
+                }
+            }),
+            0..3,
+        ),
+    )
+        .prop_map(|(current_mode_id, available_modes)| SessionModeState {
+            current_mode_id,
+            available_modes,
+        })
+}
+
+fn new_session_params() -> impl Strategy<Value = NewSessionParams> {
+    (text(), vec(mcp_server(), 0..3))
+        .prop_map(|(cwd, mcp_servers)| NewSessionParams { cwd, mcp_servers })
+}
+
+fn load_session_params() -> impl Strategy<Value = LoadSessionParams> {
+    (session_id(), text(), vec(mcp_server(), 0..3)).prop_map(|(session_id, cwd, mcp_servers)| {
+        LoadSessionParams {
+            session_id,
+            cwd,
+            mcp_servers,
+        }
+    })
+}
+
+// -----------------
+// Prompt content
+// -----------------
+
+fn annotations() -> impl Strategy<Value = Option<Annotations>> {
+    option::of(
+        (
+            option::of(vec(prop::sample::select(vec!["user", "assistant"]), 1..3)),
+            // Exact binary fractions, for the same reason as `json_value`.
+            option::of(prop::sample::select(vec![0.0, 0.5, 1.0])),
+            option::of(text()),
+        )
+            .prop_map(|(audience, priority, last_modified)| Annotations {
+                audience: audience.map(|a| a.into_iter().map(str::to_owned).collect()),
+                priority,
+                last_modified,
+            }),
+    )
+}
+
+pub fn content_block() -> BoxedStrategy<ContentBlock> {
+    prop_oneof![
+        7 => (text(), annotations())
+            .prop_map(|(text, annotations)| ContentBlock::Text(TextContent { text, annotations })),
+        1 => (text(), small_string(), option::of(text()), annotations()).prop_map(
+            |(data, mime_type, uri, annotations)| ContentBlock::Image(ImageContent {
+                data,
+                mime_type,
+                uri,
+                annotations,
+            })
+        ),
+        1 => (text(), small_string(), annotations()).prop_map(|(data, mime_type, annotations)| {
+            ContentBlock::Audio(AudioContent {
+                data,
+                mime_type,
+                annotations,
+            })
+        }),
+        2 => (small_string(), option::of(text()), option::of(any::<i64>())).prop_map(
+            |(name, title, size)| ContentBlock::ResourceLink(ResourceLink {
+                uri: format!("file:///{name}"),
+                name,
+                title,
+                description: None,
+                mime_type: None,
+                size,
+                annotations: None,
+            })
+        ),
+        1 => (small_string(), text(), any::<bool>(), option::of(small_string())).prop_map(
+            |(name, body, blob, mime_type)| {
+                let uri = format!("file:///{name}");
+                let resource = match blob {
+                    true => EmbeddedResourceResource::Blob {
+                        uri,
+                        blob: body,
+                        mime_type,
+                    },
+                    false => EmbeddedResourceResource::Text {
+                        uri,
+                        text: body,
+                        mime_type,
+                    },
+                };
+                ContentBlock::Resource(EmbeddedResource {
+                    resource,
+                    annotations: None,
+                })
+            }
+        ),
+    ]
+    .boxed()
+}
+
+// -----------------
+// Tool calls and session updates
+// -----------------
+
+fn tool_kind() -> impl Strategy<Value = ToolKind> {
+    prop::sample::select(vec![
+        ToolKind::Read,
+        ToolKind::Edit,
+        ToolKind::Delete,
+        ToolKind::Move,
+        ToolKind::Search,
+        ToolKind::Execute,
+        ToolKind::Think,
+        ToolKind::Fetch,
+        ToolKind::SwitchMode,
+        ToolKind::Other,
+    ])
+}
+
+fn tool_call_status() -> impl Strategy<Value = ToolCallStatus> {
+    prop::sample::select(vec![
+        ToolCallStatus::Pending,
+        ToolCallStatus::InProgress,
+        ToolCallStatus::Completed,
+        ToolCallStatus::Failed,
+    ])
+}
+
+fn tool_call_content() -> impl Strategy<Value = ToolCallContent> {
+    prop_oneof![
+        content_block().prop_map(|content| ToolCallContent::Content { content }),
+        (text(), option::of(text()), text()).prop_map(|(path, old_text, new_text)| {
+            ToolCallContent::Diff(Diff {
+                path,
+                old_text,
+                new_text,
+            })
+        }),
+        small_string()
+            .prop_map(|terminal_id| ToolCallContent::Terminal(TerminalRef { terminal_id })),
+    ]
+}
+
+fn tool_call_location() -> impl Strategy<Value = ToolCallLocation> {
+    (text(), option::of(any::<u32>())).prop_map(|(path, line)| ToolCallLocation { path, line })
+}
+
+fn tool_call() -> BoxedStrategy<ToolCall> {
+    (
+        small_string(),
+        text(),
+        tool_kind(),
+        tool_call_status(),
+        vec(tool_call_content(), 0..3),
+        vec(tool_call_location(), 0..3),
+        option::of(json_present()),
+        option::of(json_present()),
+    )
+        .prop_map(
+            |(tool_call_id, title, kind, status, content, locations, raw_input, raw_output)| {
+                ToolCall {
+                    tool_call_id,
+                    title,
+                    kind,
+                    status,
+                    content,
+                    locations,
+                    raw_input,
+                    raw_output,
+                }
+            },
+        )
+        .boxed()
+}
+
+pub fn tool_call_update() -> BoxedStrategy<ToolCallUpdate> {
+    (
+        small_string(),
+        option::of(text()),
+        option::of(tool_kind()),
+        option::of(tool_call_status()),
+        option::of(vec(tool_call_content(), 0..3)),
+        option::of(vec(tool_call_location(), 0..3)),
+        option::of(json_present()),
+        option::of(json_present()),
+    )
+        .prop_map(
+            |(tool_call_id, title, kind, status, content, locations, raw_input, raw_output)| {
+                ToolCallUpdate {
+                    tool_call_id,
+                    title,
+                    kind,
+                    status,
+                    content,
+                    locations,
+                    raw_input,
+                    raw_output,
+                }
+            },
+        )
+        .boxed()
+}
+
+fn plan() -> impl Strategy<Value = Plan> {
+    let priority = prop::sample::select(vec![
+        PlanEntryPriority::High,
+        PlanEntryPriority::Medium,
+        PlanEntryPriority::Low,
+    ]);
+    let status = prop::sample::select(vec![
+        PlanEntryStatus::Pending,
+        PlanEntryStatus::InProgress,
+        PlanEntryStatus::Completed,
+    ]);
+    vec(
+        (text(), priority, status).prop_map(|(content, priority, status)| PlanEntry {
+            content,
+            priority,
+            status,
+        }),
+        0..4,
+    )
+    .prop_map(|entries| Plan { entries })
+}
+
+fn available_commands() -> impl Strategy<Value = AvailableCommandsUpdate> {
+    vec(
+        (small_string(), text(), option::of(text())).prop_map(|(name, description, hint)| {
+            AvailableCommand {
+                name,
+                description,
+                input: hint.map(|hint| {
+                    AvailableCommandInput::Unstructured(UnstructuredCommandInput { hint })
+                }),
+            }
+        }),
+        0..3,
+    )
+    .prop_map(|available_commands| AvailableCommandsUpdate { available_commands })
+}
+
+pub fn session_update() -> BoxedStrategy<SessionUpdate> {
+    let chunk = || content_block().prop_map(|content| ContentChunk { content });
+    prop_oneof![
+        3 => chunk().prop_map(SessionUpdate::UserMessageChunk),
+        3 => chunk().prop_map(SessionUpdate::AgentMessageChunk),
+        1 => chunk().prop_map(SessionUpdate::AgentThoughtChunk),
+        2 => tool_call().prop_map(SessionUpdate::ToolCall),
+        2 => tool_call_update().prop_map(SessionUpdate::ToolCallUpdate),
+        1 => plan().prop_map(SessionUpdate::Plan),
+        1 => available_commands().prop_map(SessionUpdate::AvailableCommandsUpdate),
+        1 => mode_id().prop_map(|current_mode_id| {
+            SessionUpdate::CurrentModeUpdate(CurrentModeUpdate { current_mode_id })
+        }),
+        // Decoding keeps the tag in the payload.
+        1 => (ext_method(), json_object()).prop_map(|(tag, mut payload)| {
+            payload.insert("sessionUpdate".into(), Value::String(tag.clone()));
+            SessionUpdate::Ext { tag, payload }
+        }),
+    ]
+    .boxed()
+}
+
+pub fn session_update_notification(
+    session_id: SessionId,
+) -> impl Strategy<Value = SessionUpdateNotification> {
+    (session_update(), option::of(json_object())).prop_map(move |(update, meta)| {
+        SessionUpdateNotification {
+            session_id: session_id.clone(),
+            update,
+            meta,
+        }
+    })
+}
+
+pub fn prompt_params(session_id: SessionId) -> impl Strategy<Value = SessionPromptParams> {
+    (vec(content_block(), 0..4), option::of(json_object())).prop_map(move |(prompt, meta)| {
+        SessionPromptParams {
+            session_id: session_id.clone(),
+            prompt,
+            meta,
+        }
+    })
+}
+
+pub fn permission_params(session_id: SessionId) -> impl Strategy<Value = RequestPermissionParams> {
+    let option_kind = prop::sample::select(vec![
+        PermissionOptionKind::AllowOnce,
+        PermissionOptionKind::AllowAlways,
+        PermissionOptionKind::RejectOnce,
+        PermissionOptionKind::RejectAlways,
+    ]);
+    let options = vec(
+        (small_string(), text(), option_kind).prop_map(|(option_id, name, kind)| {
+            PermissionOption {
+                option_id,
+                name,
+                kind,
+            }
+        }),
+        0..4,
+    );
+    (tool_call_update(), options).prop_map(move |(tool_call, options)| RequestPermissionParams {
+        session_id: session_id.clone(),
+        tool_call,
+        options,
+    })
+}
+
+fn proxy_successor_params() -> impl Strategy<Value = ProxySuccessorParams> {
+    (
+        small_string(),
+        option::of(json_present()),
+        option::of(json_object()),
+    )
+        .prop_map(|(method, params, meta)| ProxySuccessorParams {
+            method,
+            params,
+            meta,
+        })
+}
+
+// -----------------
+// Exchanges
+// -----------------
+
+/// One message as sent, with its JSON-RPC id.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub direction: Direction,
+    pub id: Option<RequestId>,
+    pub message: Message,
+}
+
+impl Frame {
+    fn client(id: Option<RequestId>, message: ClientToAgentMessage) -> Frame {
+        Frame {
+            direction: Direction::FromClient,
+            id,
+            message: Message::FromClient(message),
+        }
+    }
+
+    fn agent(id: Option<RequestId>, message: AgentToClientMessage) -> Frame {
+        Frame {
+            direction: Direction::FromAgent,
+            id,
+            message: Message::FromAgent(message),
+        }
+    }
+}
+
+/// A client request and the agent's response to it, sharing `id`.
+fn client_call(
+    id: RequestId,
+    request: ClientToAgentMessage,
+    response: AgentToClientMessage,
+) -> Vec<Frame> {
+    vec![
+        Frame::client(Some(id.clone()), request),
+        Frame::agent(Some(id), response),
+    ]
+}
+
+/// An agent request and the client's response to it, sharing `id`.
+fn agent_call(
+    id: RequestId,
+    request: AgentToClientMessage,
+    response: ClientToAgentMessage,
+) -> Vec<Frame> {
+    vec![
+        Frame::agent(Some(id.clone()), request),
+        Frame::client(Some(id), response),
+    ]
+}
+
+/// Agent-side responses: a result built from the request, or an error.
+fn agent_reply<R: Clone + std::fmt::Debug + 'static>(
+    request: R,
+    result: impl Strategy<Value = AgentToClientMessage> + 'static,
+    error: impl Fn(R, JsonRpcError) -> AgentToClientMessage + 'static,
+) -> BoxedStrategy<AgentToClientMessage> {
+    prop_oneof![
+        2 => result,
+        1 => rpc_error().prop_map(move |e| error(request.clone(), e)),
+    ]
+    .boxed()
+}
+
+fn client_reply<R: Clone + std::fmt::Debug + 'static>(
+    request: R,
+    result: impl Strategy<Value = ClientToAgentMessage> + 'static,
+    error: impl Fn(R, JsonRpcError) -> ClientToAgentMessage + 'static,
+) -> BoxedStrategy<ClientToAgentMessage> {
+    prop_oneof![
+        2 => result,
+        1 => rpc_error().prop_map(move |e| error(request.clone(), e)),
+    ]
+    .boxed()
+}
+
+/// Every client-to-agent request with a matching response.
+fn client_requests() -> BoxedStrategy<Vec<Frame>> {
+    use AgentToClientMessage as A;
+    use ClientToAgentMessage as C;
+
+    let call = |request: C, response: BoxedStrategy<A>| {
+        (request_id(), response).prop_map(move |(id, r)| client_call(id, request.clone(), r))
+    };
+
+    prop_oneof![
+        initialize_params().prop_flat_map(move |p| call(
+            C::Initialize(p),
+            agent_reply(
+                (),
+                initialize_result().prop_map(A::InitializeResult),
+                |_, e| { A::InitializeError(e) }
+            )
+        )),
+        initialize_params().prop_flat_map(move |p| call(
+            C::ProxyInitialize(p),
+            agent_reply(
+                (),
+                initialize_result().prop_map(A::ProxyInitializeResult),
+                |_, e| A::ProxyInitializeError(e)
+            )
+        )),
+        small_string().prop_flat_map(move |method_id| call(
+            C::Authenticate(AuthenticateParams { method_id }),
+            agent_reply(
+                (),
+                Just(A::AuthenticateResult(AuthenticateResult {})),
+                |_, e| A::AuthenticateError(e)
+            )
+        )),
+        new_session_params().prop_flat_map(move |p| call(
+            C::SessionNew(p),
+            agent_reply(
+                (),
+                (session_id(), option::of(session_mode_state())).prop_map(|(session_id, modes)| {
+                    A::SessionNewResult(NewSessionResult { session_id, modes })
+                }),
+                |_, e| A::SessionNewError(e)
+            )
+        )),
+        load_session_params().prop_flat_map(move |p| {
+            let session_id = p.session_id.clone();
+            call(
+                C::SessionLoad(p.clone()),
+                agent_reply(
+                    p,
+                    option::of(session_mode_state()).prop_map(move |modes| {
+                        A::SessionLoadResult(LoadSessionResult {
+                            session_id: session_id.clone(),
+                            modes,
+                        })
+                    }),
+                    A::SessionLoadError,
+                ),
+            )
+        }),
+        session_id()
+            .prop_flat_map(prompt_params)
+            .prop_flat_map(move |p| {
+                let session_id = p.session_id.clone();
+                call(
+                    C::SessionPrompt(p.clone()),
+                    agent_reply(
+                        p,
+                        (
+                            stop_reason(),
+                            option::of(json_object()),
+                            option::of(json_object()),
+                        )
+                            .prop_map(move |(stop_reason, usage, meta)| {
+                                A::SessionPromptResult(SessionPromptResult {
+                                    session_id: session_id.clone(),
+                                    stop_reason,
+                                    usage,
+                                    meta,
+                                })
+                            }),
+                        A::SessionPromptError,
+                    ),
+                )
+            }),
+        (session_id(), mode_id()).prop_flat_map(move |(session_id, mode_id)| {
+            let p = SetSessionModeParams {
+                session_id,
+                mode_id,
+            };
+            let result = A::SessionSetModeResult(SetSessionModeResult {
+                session_id: p.session_id.clone(),
+                mode_id: p.mode_id.clone(),
+            });
+            call(
+                C::SessionSetMode(p.clone()),
+                agent_reply(p, Just(result), A::SessionSetModeError),
+            )
+        }),
+        proxy_successor_params().prop_flat_map(move |p| {
+            let method = p.method.clone();
+            call(
+                C::ProxySuccessorRequest(p),
+                agent_reply(
+                    method.clone(),
+                    json_present().prop_map(move |r| A::ProxySuccessorResponse {
+                        method: method.clone(),
+                        result: Some(r),
+                    }),
+                    |method, error| A::ProxySuccessorError { method, error },
+                ),
+            )
+        }),
+        (ext_method(), json_present()).prop_flat_map(move |(method, params)| {
+            let m = method.clone();
+            call(
+                C::ExtRequest {
+                    method: method.clone(),
+                    params: Some(params),
+                },
+                agent_reply(
+                    method,
+                    json_present().prop_map(move |r| A::ExtResponse {
+                        method: m.clone(),
+                        result: Some(r),
+                    }),
+                    |method, error| A::ExtError { method, error },
+                ),
+            )
+        }),
+    ]
+    .boxed()
+}
+
+/// Every agent-to-client request with a matching response.
+fn agent_requests() -> BoxedStrategy<Vec<Frame>> {
+    use AgentToClientMessage as A;
+    use ClientToAgentMessage as C;
+
+    let call = |request: A, response: BoxedStrategy<C>| {
+        (request_id(), response).prop_map(move |(id, r)| agent_call(id, request.clone(), r))
+    };
+    let terminal = || (session_id(), small_string());
+
+    prop_oneof![
+        (
+            session_id(),
+            text(),
+            option::of(any::<u32>()),
+            option::of(any::<u32>())
+        )
+            .prop_flat_map(move |(session_id, path, line, limit)| {
+                let p = ReadTextFileParams {
+                    session_id,
+                    path,
+                    line,
+                    limit,
+                };
+                call(
+                    A::FsReadTextFileRequest(p.clone()),
+                    client_reply(
+                        p,
+                        text().prop_map(|content| {
+                            C::FsReadTextFileResult(ReadTextFileResult { content })
+                        }),
+                        C::FsReadTextFileError,
+                    ),
+                )
+            }),
+        (session_id(), text(), text()).prop_flat_map(move |(session_id, path, content)| {
+            let p = WriteTextFileParams {
+                session_id,
+                path,
+                content,
+            };
+            call(
+                A::FsWriteTextFileRequest(p.clone()),
+                client_reply(
+                    p,
+                    Just(C::FsWriteTextFileResult(WriteTextFileResult {})),
+                    C::FsWriteTextFileError,
+                ),
+            )
+        }),
+        session_id()
+            .prop_flat_map(permission_params)
+            .prop_flat_map(move |p| {
+                let outcome = prop_oneof![
+                    Just(RequestPermissionOutcome::Cancelled),
+                    small_string()
+                        .prop_map(|option_id| RequestPermissionOutcome::Selected { option_id }),
+                ];
+                call(
+                    A::SessionRequestPermissionRequest(p.clone()),
+                    client_reply(
+                        p,
+                        outcome.prop_map(|outcome| {
+                            C::SessionRequestPermissionResult(RequestPermissionResult { outcome })
+                        }),
+                        C::SessionRequestPermissionError,
+                    ),
+                )
+            }),
+        (
+            session_id(),
+            text(),
+            vec(text(), 0..3),
+            option::of(text()),
+            vec(
+                name_value().prop_map(|(name, value)| EnvVariable { name, value }),
+                0..3
+            ),
+            option::of(any::<u64>()),
+        )
+            .prop_flat_map(move |(session_id, command, args, cwd, env, limit)| {
+                let p = CreateTerminalParams {
+                    session_id,
+                    command,
+                    args,
+                    cwd,
+                    env,
+                    output_byte_limit: limit,
+                };
+                call(
+                    A::TerminalCreateRequest(p.clone()),
+                    client_reply(
+                        p,
+                        small_string().prop_map(|terminal_id| {
+                            C::TerminalCreateResult(CreateTerminalResult { terminal_id })
+                        }),
+                        C::TerminalCreateError,
+                    ),
+                )
+            }),
+        terminal().prop_flat_map(move |(session_id, terminal_id)| {
+            let p = TerminalOutputParams {
+                session_id,
+                terminal_id,
+            };
+            call(
+                A::TerminalOutputRequest(p.clone()),
+                client_reply(
+                    p,
+                    (text(), any::<bool>(), option::of(exit_status())).prop_map(
+                        |(output, truncated, exit_status)| {
+                            C::TerminalOutputResult(TerminalOutputResult {
+                                output,
+                                truncated,
+                                exit_status,
+                            })
+                        },
+                    ),
+                    C::TerminalOutputError,
+                ),
+            )
+        }),
+        terminal().prop_flat_map(move |(session_id, terminal_id)| {
+            let p = WaitForTerminalExitParams {
+                session_id,
+                terminal_id,
+            };
+            call(
+                A::TerminalWaitForExitRequest(p.clone()),
+                client_reply(
+                    p,
+                    exit_status().prop_map(C::TerminalWaitForExitResult),
+                    C::TerminalWaitForExitError,
+                ),
+            )
+        }),
+        terminal().prop_flat_map(move |(session_id, terminal_id)| {
+            let p = KillTerminalCommandParams {
+                session_id,
+                terminal_id,
+            };
+            call(
+                A::TerminalKillRequest(p.clone()),
+                client_reply(
+                    p,
+                    Just(C::TerminalKillResult(KillTerminalCommandResult {})),
+                    C::TerminalKillError,
+                ),
+            )
+        }),
+        terminal().prop_flat_map(move |(session_id, terminal_id)| {
+            let p = ReleaseTerminalParams {
+                session_id,
+                terminal_id,
+            };
+            call(
+                A::TerminalReleaseRequest(p.clone()),
+                client_reply(
+                    p,
+                    Just(C::TerminalReleaseResult(ReleaseTerminalResult {})),
+                    C::TerminalReleaseError,
+                ),
+            )
+        }),
+        proxy_successor_params().prop_flat_map(move |p| {
+            let method = p.method.clone();
+            call(
+                A::ProxySuccessorRequest(p),
+                client_reply(
+                    method.clone(),
+                    json_present().prop_map(move |r| C::ProxySuccessorResponse {
+                        method: method.clone(),
+                        result: Some(r),
+                    }),
+                    |method, error| C::ProxySuccessorError { method, error },
+                ),
+            )
+        }),
+        (ext_method(), json_present()).prop_flat_map(move |(method, params)| {
+            let m = method.clone();
+            call(
+                A::ExtRequest {
+                    method: method.clone(),
+                    params: Some(params),
+                },
+                client_reply(
+                    method,
+                    json_present().prop_map(move |r| C::ExtResponse {
+                        method: m.clone(),
+                        result: Some(r),
+                    }),
+                    |method, error| C::ExtError { method, error },
+                ),
+            )
+        }),
+    ]
+    .boxed()
+}
+
+fn exit_status() -> impl Strategy<Value = TerminalExitStatus> {
+    (option::of(any::<i32>()), option::of(small_string()))
+        .prop_map(|(exit_code, signal)| TerminalExitStatus { exit_code, signal })
+}
+
+/// Every notification, in either direction.
+fn notifications() -> BoxedStrategy<Vec<Frame>> {
+    use AgentToClientMessage as A;
+    use ClientToAgentMessage as C;
+
+    let ext = || (ext_method(), option::of(json_present()));
+    prop_oneof![
+        1 => session_id().prop_map(|session_id| vec![Frame::client(
+            None,
+            C::SessionCancel(SessionCancelParams { session_id })
+        )]),
+        1 => proxy_successor_params()
+            .prop_map(|p| vec![Frame::client(None, C::ProxySuccessorNotification(p))]),
+        1 => ext().prop_map(|(method, params)| vec![Frame::client(
+            None,
+            C::ExtNotification { method, params }
+        )]),
+        3 => session_id()
+            .prop_flat_map(session_update_notification)
+            .prop_map(|n| vec![Frame::agent(None, A::SessionUpdate(n))]),
+        1 => proxy_successor_params()
+            .prop_map(|p| vec![Frame::agent(None, A::ProxySuccessorNotification(p))]),
+        1 => ext().prop_map(|(method, params)| vec![Frame::agent(
+            None,
+            A::ExtNotification { method, params }
+        )]),
+    ]
+    .boxed()
+}
+
+/// One well-formed exchange: a request and its response, or a notification.
+pub fn exchange() -> BoxedStrategy<Vec<Frame>> {
+    prop_oneof![
+        3 => client_requests(),
+        3 => agent_requests(),
+        2 => notifications(),
+    ]
+    .boxed()
+}
+
+/// Messages in arbitrary order: valid exchanges, flattened and shuffled,
+/// for "invalid injection" into the protocol spec.
+pub fn arbitrary_trace() -> impl Strategy<Value = Vec<Message>> {
+    vec(exchange(), 0..12)
+        .prop_map(|exchanges| {
+            exchanges
+                .into_iter()
+                .flatten()
+                .map(|frame| frame.message)
+                .collect::<Vec<_>>()
+        })
+        .prop_shuffle()
+}
+
+// -----------------
+// Valid traces
+// -----------------
+
+/// One abstract step of a valid trace; see [`valid_trace`].
+type Step = (
+    u8,
+    prop::sample::Index,
+    SessionId,
+    StopReason,
+    SessionUpdate,
+);
+
+/// A trace the protocol spec accepts: the handshake, then session creation,
+/// prompt turns, updates, permission requests and cancels, each emitted only
+/// when the tracked state allows it. Mirrors `genValidStep`: steps are drawn
+/// up front and interpreted against the state, skipping inapplicable ones.
+pub fn valid_trace() -> impl Strategy<Value = Vec<Message>> {
+    let step = (
+        0u8..6,
+        any::<prop::sample::Index>(),
+        session_id(),
+        stop_reason(),
+        session_update(),
+    );
+    (initialize_params(), initialize_result(), vec(step, 0..40))
+        .prop_map(|(params, result, steps)| build_trace(params, result, steps))
+}
+
+fn build_trace(
+    params: InitializeParams,
+    result: InitializeResult,
+    steps: Vec<Step>,
+) -> Vec<Message> {
+    use AgentToClientMessage as A;
+    use ClientToAgentMessage as C;
+
+    // Per session: `None` when idle, `Some(cancelled)` while a prompt is in flight.
+    let mut sessions: Vec<(SessionId, Option<bool>)> = Vec::new();
+    let mut trace = vec![
+        Message::FromClient(C::Initialize(params)),
+        Message::FromAgent(A::InitializeResult(result)),
+    ];
+
+    for (kind, index, session_id, stop_reason, update) in steps {
+        let idle: Vec<usize> = (0..sessions.len())
+            .filter(|&i| sessions[i].1.is_none())
+            .collect();
+        let busy: Vec<usize> = (0..sessions.len())
+            .filter(|&i| sessions[i].1.is_some())
+            .collect();
+        let pick = |candidates: &[usize]| match candidates {
+            [] => None,
+            _ => Some(candidates[index.index(candidates.len())]),
+        };
+
+        match kind {
+            0 if sessions.iter().all(|(s, _)| *s != session_id) => {
+                trace.push(Message::FromClient(C::SessionNew(NewSessionParams {
+                    cwd: "/".into(),
+                    mcp_servers: vec![],
+                })));
+                trace.push(Message::FromAgent(A::SessionNewResult(NewSessionResult {
+                    session_id: session_id.clone(),
+                    modes: None,
+                })));
+                sessions.push((session_id, None));
+            }
+            1 => {
+                if let Some(i) = pick(&idle) {
+                    trace.push(Message::FromClient(C::SessionPrompt(SessionPromptParams {
+                        session_id: sessions[i].0.clone(),
+                        prompt: vec![],
+                        meta: None,
+                    })));
+                    sessions[i].1 = Some(false);
+                }
+            }
+            2 => {
+                if let Some(i) = pick(&(0..sessions.len()).collect::<Vec<_>>()) {
+                    trace.push(Message::FromAgent(A::SessionUpdate(
+                        SessionUpdateNotification {
+                            session_id: sessions[i].0.clone(),
+                            update,
+                            meta: None,
+                        },
+                    )));
+                }
+            }
+            3 => {
+                if let Some(i) = pick(&busy) {
+                    trace.push(Message::FromClient(C::SessionCancel(SessionCancelParams {
+                        session_id: sessions[i].0.clone(),
+                    })));
+                    sessions[i].1 = Some(true);
+                }
+            }
+            4 => {
+                if let Some(i) = pick(&busy) {
+                    // A cancelled turn must report `cancelled`.
+                    let stop_reason = match sessions[i].1 {
+                        Some(true) => StopReason::Cancelled,
+                        _ => stop_reason,
+                    };
+                    trace.push(Message::FromAgent(A::SessionPromptResult(
+                        SessionPromptResult {
+                            session_id: sessions[i].0.clone(),
+                            stop_reason,
+                            usage: None,
+                            meta: None,
+                        },
+                    )));
+                    sessions[i].1 = None;
+                }
+            }
+            5 => {
+                if let Some(i) = pick(&busy) {
+                    let session_id = sessions[i].0.clone();
+                    trace.push(Message::FromAgent(A::SessionRequestPermissionRequest(
+                        RequestPermissionParams {
+                            session_id,
+                            tool_call: ToolCallUpdate {
+                                tool_call_id: "call-1".into(),
+                                title: None,
+                                kind: None,
+                                status: None,
+                                content: None,
+                                locations: None,
+                                raw_input: None,
+                                raw_output: None,
+                            },
+                            options: vec![],
+                        },
+                    )));
+                    trace.push(Message::FromClient(C::SessionRequestPermissionResult(
+                        RequestPermissionResult {
+                            outcome: RequestPermissionOutcome::Cancelled,
+                        },
+                    )));
+                }
+            }
+            _ => {}
+        }
+    }
+    trace
+}
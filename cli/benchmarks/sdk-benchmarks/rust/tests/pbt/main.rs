@@ -0,0 +1,6 @@
+//! Property-based tests. Mirrors `sentinel/tests/Pbt`: generators for the
+//! ACP domain, then codec and protocol properties over them.
+
+mod codec_properties;
+mod generators;
+mod protocol_properties;
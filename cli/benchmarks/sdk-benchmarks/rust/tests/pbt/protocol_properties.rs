@@ -0,0 +1,83 @@
+//! Protocol state machine properties. Mirrors
+//! `sentinel/tests/Pbt/ProtocolProperties.fs` and `ProtocolStateMachine.fs`.
+
+use crate::generators::{arbitrary_trace, valid_trace};
+use acp_benchmark::domain::Message;
+use acp_benchmark::protocol::{self, Phase};
+use acp_benchmark::validation::{self, Lane, Severity};
+use proptest::prelude::*;
+
+/// Fold `messages` through the spec, collecting each step's outcome.
+fn run(messages: &[Message]) -> (Phase, Vec<Result<(), String>>) {
+    let spec = protocol::spec();
+    let mut phase = spec.initial;
+    let outcomes = messages
+        .iter()
+        .map(|m| (spec.step)(&mut phase, m).map_err(|e| e.code().to_owned()))
+        .collect();
+    (phase, outcomes)
+}
+
+/// Every tracked session is keyed by its own id.
+fn check_phase(phase: &Phase) -> Result<(), TestCaseError> {
+    if let Phase::Ready(ctx) = phase {
+        for (key, session) in &ctx.sessions {
+            prop_assert_eq!(key, &session.session_id);
+        }
+    }
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn spec_is_deterministic(trace in arbitrary_trace()) {
+        prop_assert_eq!(run(&trace), run(&trace));
+    }
+
+    #[test]
+    fn never_reaches_an_impossible_state(trace in arbitrary_trace()) {
+        let spec = protocol::spec();
+        let mut phase = spec.initial.clone();
+        let mut ready = false;
+        for message in &trace {
+            let before = phase.clone();
+            match (spec.step)(&mut phase, message) {
+                // A rejected message leaves the phase untouched.
+                Err(_) => prop_assert_eq!(&phase, &before),
+                Ok(()) => check_phase(&phase)?,
+            }
+            // Once ready, a connection never leaves the ready phase.
+            prop_assert!(!ready || matches!(phase, Phase::Ready(_)), "left Ready on {:?}", message);
+            ready = matches!(phase, Phase::Ready(_));
+        }
+    }
+
+    #[test]
+    fn protocol_findings_are_protocol_lane_errors(trace in arbitrary_trace()) {
+        let result = validation::run_with_validation(&protocol::spec(), &trace);
+        for finding in result.findings.iter().filter(|f| f.lane == Lane::Protocol) {
+            prop_assert_eq!(finding.severity, Severity::Error);
+        }
+        if let Err(e) = &result.final_phase {
+            let codes: Vec<_> = result
+                .findings
+                .iter()
+                .filter_map(|f| f.failure.as_ref().map(|x| x.code.as_str()))
+                .collect();
+            prop_assert!(codes.contains(&e.code()), "{:?} not in {:?}", e.code(), codes);
+        }
+    }
+
+    #[test]
+    fn valid_traces_have_no_protocol_or_session_findings(trace in valid_trace()) {
+        let result = validation::run_with_validation(&protocol::spec(), &trace);
+        prop_assert!(result.final_phase.is_ok(), "{:?}", result.final_phase);
+        let lanes: Vec<_> = result
+            .findings
+            .iter()
+            .filter(|f| matches!(f.lane, Lane::Protocol | Lane::Session))
+            .collect();
+        prop_assert!(lanes.is_empty(), "{:?}", lanes);
+    }
+
+}
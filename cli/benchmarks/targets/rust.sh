@@ -2,12 +2,11 @@
 set -euo pipefail
 
 # Rust SDK Benchmark Target
-# Placeholder - replace with actual SDK path
+# Uses the in-repo acp-benchmark crate unless RUST_SDK_PATH points elsewhere
 
 SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
 
-# TODO: Update these paths to your Rust SDK location
-SDK_PATH="${RUST_SDK_PATH:-/path/to/rust-sdk}"
+SDK_PATH="${RUST_SDK_PATH:-$SCRIPT_DIR/../sdk-benchmarks/rust}"
 BINARY="$SDK_PATH/target/release/acp-benchmark"
 
 MODE="${1:-roundtrip}"